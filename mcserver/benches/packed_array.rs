@@ -0,0 +1,57 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use mcserver::objs::{PackedArray, Packing};
+
+const SECTION_VOLUME: usize = 4096;
+
+/// Unpacks spanning entries bit by bit, as a straightforward port of the
+/// format description would.
+fn naive_unpack(longs: &[u64], bits: usize, len: usize) -> Vec<u64> {
+    (0..len)
+        .map(|index| {
+            (0..bits).fold(0, |value, bit| {
+                let position = index * bits + bit;
+                let set = (longs[position / 64] >> (position % 64)) & 1;
+                value | (set << bit)
+            })
+        })
+        .collect()
+}
+
+fn section(bits: u8, packing: Packing) -> PackedArray {
+    let mask = (1 << bits) - 1;
+    let values = (0..SECTION_VOLUME as u64).map(|n| n.wrapping_mul(0x9e37_79b9) & mask);
+    PackedArray::from_values(SECTION_VOLUME, bits, packing, values).unwrap()
+}
+
+fn bench_unpack(c: &mut Criterion) {
+    for &bits in &[4, 5, 14] {
+        let spanning = section(bits, Packing::Spanning);
+        let padded = section(bits, Packing::Padded);
+
+        c.bench_function(&format!("naive unpack {} bits", bits), |b| {
+            b.iter(|| naive_unpack(black_box(spanning.as_longs()), bits.into(), SECTION_VOLUME))
+        });
+        c.bench_function(&format!("spanning get {} bits", bits), |b| {
+            b.iter(|| black_box(&spanning).iter().collect::<Vec<_>>())
+        });
+        c.bench_function(&format!("padded get {} bits", bits), |b| {
+            b.iter(|| black_box(&padded).iter().collect::<Vec<_>>())
+        });
+    }
+}
+
+fn bench_set(c: &mut Criterion) {
+    let mut array = section(5, Packing::Padded);
+
+    c.bench_function("padded set 5 bits", |b| {
+        b.iter(|| {
+            for index in 0..SECTION_VOLUME {
+                array.set(index, black_box(index as u64 & 31)).unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, bench_unpack, bench_set);
+criterion_main!(benches);
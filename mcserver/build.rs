@@ -0,0 +1,292 @@
+//! Generates the registries in `src/registry` from vanilla data reports,
+//! which live in `data/<protocol version>/` as the server's `--reports`
+//! option writes them.
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+/// Returns the report directories, ordered by protocol version.
+fn versions(data: &Path) -> Vec<(i32, PathBuf)> {
+    let mut versions = fs::read_dir(data)
+        .expect("can't read the data directory")
+        .map(|entry| entry.expect("can't read the data directory").path())
+        .filter_map(|path| {
+            let version = path.file_name()?.to_str()?.parse().ok()?;
+            Some((version, path))
+        })
+        .collect::<Vec<_>>();
+
+    versions.sort();
+    versions
+}
+
+fn read_json(path: &Path) -> Value {
+    let file = fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("can't read {}: {}", path.display(), err));
+
+    serde_json::from_str(&file)
+        .unwrap_or_else(|err| panic!("can't parse {}: {}", path.display(), err))
+}
+
+struct Block {
+    name: String,
+    first_state: u64,
+    default_state: u64,
+    properties: Vec<(String, Vec<String>)>,
+}
+
+impl Block {
+    fn state_count(&self) -> u64 {
+        self.properties
+            .iter()
+            .map(|(_, values)| values.len() as u64)
+            .product()
+    }
+}
+
+/// Reads a block from `blocks.json`, checking that its states are numbered
+/// the way the runtime registry computes them: consecutively, with the last
+/// property changing fastest.
+fn parse_block(name: &str, block: &Value) -> Block {
+    let properties = match block.get("properties") {
+        Some(properties) => properties
+            .as_object()
+            .expect("properties must be an object")
+            .iter()
+            .map(|(property, values)| {
+                let values = values
+                    .as_array()
+                    .expect("property values must be an array")
+                    .iter()
+                    .map(|value| value.as_str().expect("values must be strings").to_owned())
+                    .collect();
+                (property.clone(), values)
+            })
+            .collect::<Vec<(String, Vec<String>)>>(),
+        None => Vec::new(),
+    };
+
+    let states = block["states"].as_array().expect("states must be an array");
+    let id = |state: &Value| state["id"].as_u64().expect("state ids must be integers");
+
+    let first_state = states.iter().map(id).min().expect("blocks have states");
+    let mut default_state = None;
+
+    for state in states {
+        let mut index = 0;
+        for (property, values) in &properties {
+            let value = &state["properties"][property];
+            let position = values
+                .iter()
+                .position(|v| value == v.as_str())
+                .unwrap_or_else(|| panic!("{} has an invalid {}", name, property));
+            index = index * values.len() as u64 + position as u64;
+        }
+
+        assert_eq!(
+            id(state),
+            first_state + index,
+            "{} isn't numbered in order",
+            name
+        );
+
+        if state.get("default").and_then(Value::as_bool) == Some(true) {
+            default_state = Some(id(state));
+        }
+    }
+
+    let count = properties
+        .iter()
+        .map(|(_, values)| values.len())
+        .product::<usize>();
+    assert_eq!(states.len(), count, "{} is missing states", name);
+
+    Block {
+        name: name.to_owned(),
+        first_state,
+        default_state: default_state.unwrap_or(first_state),
+        properties,
+    }
+}
+
+fn generate_blocks(versions: &[(i32, PathBuf)]) -> String {
+    let mut out = String::new();
+    let mut registries = Vec::new();
+
+    for (version, dir) in versions {
+        let path = dir.join("blocks.json");
+        if !path.exists() {
+            continue;
+        }
+        println!("cargo:rerun-if-changed={}", path.display());
+
+        let report = read_json(&path);
+        let mut blocks = report
+            .as_object()
+            .expect("blocks.json must be an object")
+            .iter()
+            .map(|(name, block)| parse_block(name, block))
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|block| block.first_state);
+
+        // A gap means blocks are missing, and clients would read the global
+        // palette with a different number of bits than we write it with.
+        let mut next_state = 0;
+        for block in &blocks {
+            assert_eq!(
+                block.first_state,
+                next_state,
+                "{} is missing the blocks before {}",
+                path.display(),
+                block.name
+            );
+            next_state += block.state_count();
+        }
+
+        writeln!(out, "static BLOCKS_{}: &[Block] = &[", version).unwrap();
+        for block in &blocks {
+            write!(
+                out,
+                "    Block {{ name: {:?}, first_state: {}, default_state: {}, properties: &[",
+                block.name, block.first_state, block.default_state
+            )
+            .unwrap();
+            for (name, values) in &block.properties {
+                write!(
+                    out,
+                    "Property {{ name: {:?}, values: &{:?} }}, ",
+                    name, values
+                )
+                .unwrap();
+            }
+            writeln!(out, "] }},").unwrap();
+        }
+        writeln!(out, "];").unwrap();
+
+        let mut by_name = (0..blocks.len()).collect::<Vec<_>>();
+        by_name.sort_by_key(|&i| &blocks[i].name);
+        writeln!(
+            out,
+            "static BLOCKS_BY_NAME_{}: &[u16] = &{:?};",
+            version, by_name
+        )
+        .unwrap();
+
+        registries.push(*version);
+    }
+
+    writeln!(out, "static REGISTRIES: &[BlockRegistry] = &[").unwrap();
+    for version in registries {
+        writeln!(
+            out,
+            "    BlockRegistry {{ protocol_version: {0}, blocks: BLOCKS_{0}, by_name: BLOCKS_BY_NAME_{0} }},",
+            version
+        )
+        .unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    out
+}
+
+/// Registries that packets refer to, and so must be in every report.
+const REQUIRED_REGISTRIES: &[&str] = &[
+    "minecraft:biome",
+    "minecraft:enchantment",
+    "minecraft:entity_type",
+    "minecraft:item",
+    "minecraft:mob_effect",
+    "minecraft:particle_type",
+    "minecraft:sound_event",
+];
+
+fn generate_registries(versions: &[(i32, PathBuf)]) -> String {
+    let mut out = String::new();
+    let mut registries = Vec::new();
+
+    for (version, dir) in versions {
+        let path = dir.join("registries.json");
+        if !path.exists() {
+            continue;
+        }
+        println!("cargo:rerun-if-changed={}", path.display());
+
+        let report = read_json(&path);
+        let report = report
+            .as_object()
+            .expect("registries.json must be an object");
+
+        for required in REQUIRED_REGISTRIES {
+            assert!(
+                report.contains_key(*required),
+                "{} is missing {}",
+                path.display(),
+                required
+            );
+        }
+
+        // serde_json's maps are ordered by key, so registries end up ordered
+        // by name.
+        writeln!(out, "static REGISTRIES_{}: &[Registry] = &[", version).unwrap();
+        for (name, registry) in report {
+            let mut entries = registry["entries"]
+                .as_object()
+                .expect("entries must be an object")
+                .iter()
+                .map(|(entry, value)| {
+                    let id = value["protocol_id"]
+                        .as_i64()
+                        .expect("protocol ids must be integers");
+                    (entry.as_str(), id)
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|&(_, id)| id);
+
+            let mut by_name = (0..entries.len()).collect::<Vec<_>>();
+            by_name.sort_by_key(|&i| entries[i].0);
+
+            writeln!(
+                out,
+                "    Registry {{ name: {:?}, default: {:?}, entries: &{:?}, by_name: &{:?} }},",
+                name,
+                registry.get("default").and_then(Value::as_str),
+                entries,
+                by_name
+            )
+            .unwrap();
+        }
+        writeln!(out, "];").unwrap();
+
+        registries.push(*version);
+    }
+
+    writeln!(out, "static REGISTRIES: &[Registries] = &[").unwrap();
+    for version in registries {
+        writeln!(
+            out,
+            "    Registries {{ protocol_version: {0}, registries: REGISTRIES_{0} }},",
+            version
+        )
+        .unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    out
+}
+
+fn main() {
+    let data = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("data");
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed={}", data.display());
+
+    let versions = versions(&data);
+
+    fs::write(out.join("blocks.rs"), generate_blocks(&versions)).unwrap();
+    fs::write(out.join("registries.rs"), generate_registries(&versions)).unwrap();
+}
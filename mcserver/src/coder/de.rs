@@ -1,17 +1,244 @@
-use std::{io::Read, mem::size_of};
+use std::{
+    io::{self, Read},
+    mem::size_of,
+};
 
-use serde::de::{self, Deserialize, Visitor};
+use serde::de::{self, Deserialize, IntoDeserializer, Visitor};
 
 use super::{
-    super::objs::VarInt,
+    super::objs::{
+        position::POSITION_TOKEN,
+        varint::{VARINT_TOKEN, VARLONG_TOKEN},
+        Position, PositionLayout, VarInt, VarLong,
+    },
     error::{Error, Result},
+    variant_discriminant, Options,
 };
 
-pub struct Deserializer<R: Read>(R);
+/// Keeps track of how many bytes have been read, so that errors can point at
+/// where they happened.
+struct CountingReader<R: Read> {
+    inner: R,
+    count: u64,
+    /// A byte read ahead by `at_end`, handed out by the next read.
+    peeked: Option<u8>,
+}
+
+impl<R: Read> CountingReader<R> {
+    /// Returns whether the input is exhausted, which needs reading ahead.
+    fn at_end(&mut self) -> io::Result<bool> {
+        if self.peeked.is_some() {
+            return Ok(false);
+        }
+
+        let mut byte = [0];
+        loop {
+            match self.inner.read(&mut byte) {
+                Ok(0) => return Ok(true),
+                Ok(_) => {
+                    self.peeked = Some(byte[0]);
+                    return Ok(false);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match (self.peeked.take(), buf.first_mut()) {
+            (Some(byte), Some(first)) => {
+                *first = byte;
+                1
+            }
+            (peeked, _) => {
+                self.peeked = peeked;
+                self.inner.read(buf)?
+            }
+        };
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+pub struct Deserializer<R: Read> {
+    r: CountingReader<R>,
+    options: Options,
+}
 
 impl<R: Read> Deserializer<R> {
     pub fn new(r: R) -> Self {
-        Self(r)
+        Self::with_options(r, Options::default())
+    }
+
+    pub fn with_options(r: R, options: Options) -> Self {
+        Self {
+            r: CountingReader {
+                inner: r,
+                count: 0,
+                peeked: None,
+            },
+            options,
+        }
+    }
+
+    /// Returns how many bytes have been read so far.
+    pub fn offset(&self) -> u64 {
+        self.r.count
+    }
+
+    pub fn read_varint(&mut self) -> Result<i32> {
+        VarInt::read_from(&mut self.r).map(|n| n.0)
+    }
+
+    pub fn read_varlong(&mut self) -> Result<i64> {
+        VarLong::read_from(&mut self.r).map(|n| n.0)
+    }
+
+    /// Fills `buf` with the next bytes as they are, for formats built on top
+    /// of this one.
+    pub fn read_bytes(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.r.read_exact(buf).map_err(Into::into)
+    }
+}
+
+/// Hands out exactly `len` elements, for tuples and structs whose number of
+/// fields is known up front.
+///
+/// Errors are annotated with the name (or index, for tuples) of the field that
+/// caused them and the offset it started at.
+struct Fields<'a, R: Read> {
+    de: &'a mut Deserializer<R>,
+    names: Option<&'static [&'static str]>,
+    index: usize,
+    len: usize,
+}
+
+impl<'a, R: Read> Fields<'a, R> {
+    fn new(de: &'a mut Deserializer<R>, len: usize) -> Self {
+        Self {
+            de,
+            names: None,
+            index: 0,
+            len,
+        }
+    }
+
+    fn named(de: &'a mut Deserializer<R>, names: &'static [&'static str]) -> Self {
+        Self {
+            names: Some(names),
+            ..Self::new(de, names.len())
+        }
+    }
+}
+
+impl<'de, R: Read> de::SeqAccess<'de> for Fields<'_, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+
+        let index = self.index;
+        let start = self.de.offset();
+        self.index += 1;
+
+        seed.deserialize(&mut *self.de).map(Some).map_err(|err| {
+            err.with_context(|context| {
+                let name = match self.names {
+                    Some(names) => names[index].to_owned(),
+                    None => index.to_string(),
+                };
+
+                context.field_path.insert(0, name);
+                context.offset.get_or_insert(start);
+            })
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.index)
+    }
+}
+
+/// Reads a VarInt discriminant and picks the matching variant, see
+/// `variant_discriminant` for how discriminants are assigned.
+struct Enum<'a, R: Read> {
+    de: &'a mut Deserializer<R>,
+    variants: &'static [&'static str],
+}
+
+/// The body of the variant picked by `Enum`, which annotates errors with the
+/// variant's name.
+struct Variant<'a, R: Read> {
+    de: &'a mut Deserializer<R>,
+    name: &'static str,
+}
+
+impl<'de, 'a, R: Read> de::EnumAccess<'de> for Enum<'a, R> {
+    type Error = Error;
+    type Variant = Variant<'a, R>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant)> {
+        let discriminant = self.de.read_varint()?;
+
+        let (index, name) = (0..)
+            .zip(self.variants)
+            .find(|&(index, variant)| variant_discriminant(index, variant) == discriminant)
+            .ok_or(Error::InvalidDiscriminant(discriminant))?;
+
+        let index: de::value::U32Deserializer<Error> = index.into_deserializer();
+        let variant = Variant { de: self.de, name };
+        Ok((seed.deserialize(index)?, variant))
+    }
+}
+
+impl<R: Read> Variant<'_, R> {
+    fn annotate(&self, err: Error) -> Error {
+        err.with_context(|context| context.field_path.insert(0, self.name.to_owned()))
+    }
+}
+
+impl<'de, R: Read> de::VariantAccess<'de> for Variant<'_, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        let start = self.de.offset();
+
+        seed.deserialize(&mut *self.de).map_err(|err| {
+            self.annotate(err).with_context(|context| {
+                context.offset.get_or_insert(start);
+            })
+        })
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor
+            .visit_seq(Fields::new(&mut *self.de, len))
+            .map_err(|err| self.annotate(err))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor
+            .visit_seq(Fields::named(&mut *self.de, fields))
+            .map_err(|err| self.annotate(err))
     }
 }
 
@@ -20,7 +247,7 @@ macro_rules! de_int {
         $(
         fn $name<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
             let mut buf = [0; size_of::<$ty>()];
-            self.0.read_exact(&mut buf)?;
+            self.r.read_exact(&mut buf)?;
 
             visitor.$visitor_method(<$ty>::from_be_bytes(buf))
         }
@@ -33,7 +260,7 @@ macro_rules! de_float {
         $(
         fn $name<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
             let mut buf = [0; size_of::<$bits_ty>()];
-            self.0.read_exact(&mut buf)?;
+            self.r.read_exact(&mut buf)?;
 
             visitor.$visitor_method(<$ty>::from_bits(<$bits_ty>::from_be_bytes(buf)))
         }
@@ -45,22 +272,19 @@ impl<'de, R: Read> de::Deserializer<'de> for &'_ mut Deserializer<R> {
     type Error = Error;
 
     fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(Error::Unsupported {
+            method: "deserialize_any",
+        })
     }
 
     fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         let mut buf = [0; 1];
-        self.0.read_exact(&mut buf)?;
+        self.r.read_exact(&mut buf)?;
 
         let value = match buf[0] {
             0 => false,
             1 => true,
-            _ => {
-                return Err(de::Error::invalid_value(
-                    de::Unexpected::Bytes(&buf),
-                    &visitor,
-                ))
-            }
+            n => return Err(Error::InvalidBooleanValue(n)),
         };
 
         visitor.visit_bool(value)
@@ -88,15 +312,17 @@ impl<'de, R: Read> de::Deserializer<'de> for &'_ mut Deserializer<R> {
     );
 
     fn deserialize_char<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(Error::Unsupported {
+            method: "deserialize_char",
+        })
     }
 
-    fn deserialize_str<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
     }
 
     fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
-        let size = VarInt::deserialize(&mut *self)?.0;
+        let size = self.read_varint()?;
 
         visitor.visit_string(
             (0..size)
@@ -109,40 +335,62 @@ impl<'de, R: Read> de::Deserializer<'de> for &'_ mut Deserializer<R> {
     }
 
     fn deserialize_bytes<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(Error::Unsupported {
+            method: "deserialize_bytes",
+        })
     }
 
     fn deserialize_byte_buf<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(Error::Unsupported {
+            method: "deserialize_byte_buf",
+        })
     }
 
     fn deserialize_option<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(Error::Unsupported {
+            method: "deserialize_option",
+        })
     }
 
-    fn deserialize_unit<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
     }
 
     fn deserialize_unit_struct<V: Visitor<'de>>(
         self,
         _name: &'static str,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value> {
-        unimplemented!()
+        visitor.visit_unit()
     }
 
     // As is done here, serializers are encouraged to treat newtype structs as
     // insignificant wrappers around the data they contain. That means not
     // parsing anything other than the contained value.
+    //
+    // VarInts and VarLongs are the exception, since reading them directly is
+    // much cheaper than going through `deserialize_seq` for every byte, and so
+    // are Positions, which expect their pre-1.14 packing whatever this
+    // connection uses.
     fn deserialize_newtype_struct<V: Visitor<'de>>(
         self,
-        _name: &'static str,
-        _visitor: V,
+        name: &'static str,
+        visitor: V,
     ) -> Result<V::Value> {
-        unimplemented!()
+        match name {
+            VARINT_TOKEN => visitor.visit_i32(self.read_varint()?),
+            VARLONG_TOKEN => visitor.visit_i64(self.read_varlong()?),
+            POSITION_TOKEN => {
+                let n = u64::deserialize(&mut *self)?;
+                let position = Position::unpack(n, self.options.position_layout);
+                visitor.visit_u64(position.pack(PositionLayout::Xyz)?)
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
     }
 
+    // Sequences don't carry their length on the wire, so they take up the rest
+    // of the input.
     fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
         struct SeqAccess<'a, R: Read>(&'a mut Deserializer<R>);
 
@@ -153,53 +401,129 @@ impl<'de, R: Read> de::Deserializer<'de> for &'_ mut Deserializer<R> {
                 &mut self,
                 seed: T,
             ) -> Result<Option<T::Value>> {
+                if self.0.r.at_end()? {
+                    return Ok(None);
+                }
+
                 seed.deserialize(&mut *self.0).map(Some)
             }
         }
 
-        visitor.visit_seq(SeqAccess(&mut *self))
+        visitor.visit_seq(SeqAccess(self))
     }
 
-    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Fields::new(self, len))
     }
 
     fn deserialize_tuple_struct<V: Visitor<'de>>(
         self,
         _name: &'static str,
-        _len: usize,
-        _visitor: V,
+        len: usize,
+        visitor: V,
     ) -> Result<V::Value> {
-        unimplemented!()
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(Error::Unsupported {
+            method: "deserialize_map",
+        })
     }
 
     fn deserialize_struct<V: Visitor<'de>>(
         self,
         _name: &'static str,
-        _fields: &'static [&'static str],
-        _visitor: V,
+        fields: &'static [&'static str],
+        visitor: V,
     ) -> Result<V::Value> {
-        unimplemented!()
+        visitor.visit_seq(Fields::named(self, fields))
     }
 
     fn deserialize_enum<V: Visitor<'de>>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
-        _visitor: V,
+        variants: &'static [&'static str],
+        visitor: V,
     ) -> Result<V::Value> {
-        unimplemented!()
+        visitor.visit_enum(Enum { de: self, variants })
     }
 
     fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(Error::Unsupported {
+            method: "deserialize_identifier",
+        })
     }
 
     fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(Error::Unsupported {
+            method: "deserialize_ignored_any",
+        })
+    }
+}
+
+/// Deserializes a value that has its own binary encoding, by handing `read`
+/// the raw bytes as a sequence of `u8`s.
+///
+/// Only as many bytes as `read` asks for are consumed, so this works for
+/// encodings like NBT that don't carry their length. It pairs with
+/// `serialize_bytes`, which our serializer writes without a length prefix.
+pub(crate) fn read_raw<'de, D, T, F>(
+    deserializer: D,
+    expecting: &'static str,
+    read: F,
+) -> std::result::Result<T, D::Error>
+where
+    D: de::Deserializer<'de>,
+    F: FnOnce(&mut dyn Read) -> Result<T>,
+{
+    struct RawVisitor<F> {
+        expecting: &'static str,
+        read: F,
     }
+
+    impl<'de, T, F: FnOnce(&mut dyn Read) -> Result<T>> Visitor<'de> for RawVisitor<F> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str(self.expecting)
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<T, A::Error> {
+            // The error that stopped the sequence is kept, rather than lost
+            // behind an `io::Error`.
+            let mut error = None;
+            let result = (self.read)(&mut ReadFn(|buf: &mut [u8]| {
+                let first = match buf.first_mut() {
+                    Some(first) => first,
+                    None => return Ok(0),
+                };
+
+                match seq.next_element() {
+                    Ok(Some(byte)) => {
+                        *first = byte;
+                        Ok(1)
+                    }
+                    Ok(None) => Ok(0),
+                    Err(err) => {
+                        error = Some(err);
+                        Err(io::Error::other("invalid byte"))
+                    }
+                }
+            }));
+
+            result.map_err(|err| error.unwrap_or_else(|| de::Error::custom(err)))
+        }
+    }
+
+    /// Adapts a closure into a reader.
+    struct ReadFn<F>(F);
+
+    impl<F: FnMut(&mut [u8]) -> io::Result<usize>> Read for ReadFn<F> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            (self.0)(buf)
+        }
+    }
+
+    deserializer.deserialize_seq(RawVisitor { expecting, read })
 }
@@ -1,3 +1,11 @@
+use super::{
+    super::{
+        nbt,
+        objs::{PositionError, SlotError},
+    },
+    framing::State,
+};
+
 #[derive(Debug)]
 pub enum Error {
     InvalidBooleanValue(u8),
@@ -6,6 +14,86 @@ pub enum Error {
     HumongousString,
     HumongousVarInt,
     InvalidString,
+    InvalidDiscriminant(i32),
+    InvalidPacketLength(i32),
+    InvalidPosition(PositionError),
+    InvalidSlot(SlotError),
+    InvalidNbt(nbt::error::Error),
+    TrailingBytes(usize),
+
+    /// The protocol has no encoding for this kind of value, e.g. maps.
+    Unsupported {
+        method: &'static str,
+    },
+
+    /// Another error, along with where it happened.
+    Context(Box<Error>, Context),
+}
+
+/// Where in the stream an error happened, as far as it's known.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Context {
+    pub state: Option<State>,
+    pub packet_id: Option<i32>,
+
+    /// Names of the fields (or indices, for tuples) leading to the value that
+    /// failed, outermost first.
+    pub field_path: Vec<String>,
+
+    /// Offset in bytes from the start of the packet body.
+    pub offset: Option<u64>,
+}
+
+impl Error {
+    /// Returns the error without any context attached.
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::Context(err, _) => err,
+            err => err,
+        }
+    }
+
+    pub fn context(&self) -> Option<&Context> {
+        match self {
+            Error::Context(_, context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Attaches context to the error, or updates the context it already has.
+    pub(crate) fn with_context(self, f: impl FnOnce(&mut Context)) -> Self {
+        let (err, mut context) = match self {
+            Error::Context(err, context) => (err, context),
+            err => (Box::new(err), Context::default()),
+        };
+
+        f(&mut context);
+        Error::Context(err, context)
+    }
+}
+
+impl std::fmt::Display for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts = Vec::new();
+
+        if let Some(packet_id) = self.packet_id {
+            parts.push(format!("in packet {:#04x}", packet_id));
+        }
+
+        if let Some(state) = self.state {
+            parts.push(format!("in state {}", state));
+        }
+
+        if !self.field_path.is_empty() {
+            parts.push(format!("in field `{}`", self.field_path.join(".")));
+        }
+
+        if let Some(offset) = self.offset {
+            parts.push(format!("at byte {}", offset));
+        }
+
+        write!(f, "{}", parts.join(", "))
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -19,18 +107,34 @@ impl std::fmt::Display for Error {
                 n
             ),
 
-            IoError(err) => write!(f, "{}", err.to_string()),
+            IoError(err) => write!(f, "{}", err),
 
             HumongousString => write!(
                 f,
                 "tried to serialize string with a size larger than {}",
-                i32::max_value()
+                i32::MAX
             ),
 
             HumongousVarInt => write!(f, "tried to deserialize a VarInt with too many bytes"),
 
             InvalidString => write!(f, "string contained non-utf8 chars"),
 
+            InvalidDiscriminant(n) => write!(f, "no enum variant has discriminant {}", n),
+
+            InvalidPacketLength(n) => write!(f, "invalid packet length {}", n),
+
+            InvalidPosition(err) => write!(f, "{}", err),
+
+            InvalidSlot(err) => write!(f, "{}", err),
+
+            InvalidNbt(err) => write!(f, "invalid NBT: {}", err),
+
+            TrailingBytes(n) => write!(f, "{} bytes left over after decoding packet", n),
+
+            Unsupported { method } => write!(f, "`{}` is not supported by the protocol", method),
+
+            Context(err, context) => write!(f, "{} ({})", err, context),
+
             Custom(s) => write!(f, "{}", s),
         }
     }
@@ -44,6 +148,24 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<PositionError> for Error {
+    fn from(value: PositionError) -> Self {
+        Error::InvalidPosition(value)
+    }
+}
+
+impl From<SlotError> for Error {
+    fn from(value: SlotError) -> Self {
+        Error::InvalidSlot(value)
+    }
+}
+
+impl From<nbt::error::Error> for Error {
+    fn from(value: nbt::error::Error) -> Self {
+        Error::InvalidNbt(value)
+    }
+}
+
 impl serde::ser::Error for Error {
     fn custom<T: std::fmt::Display>(msg: T) -> Self {
         Error::Custom(format!("{}", msg))
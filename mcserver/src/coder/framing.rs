@@ -0,0 +1,121 @@
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+};
+
+use serde::{de::DeserializeOwned, ser::Serialize};
+
+use super::{
+    super::objs::VarInt,
+    de::Deserializer,
+    error::{Error, Result},
+    ser::Serializer,
+    Options,
+};
+
+/// Largest packet vanilla accepts, i.e. the largest three byte VarInt.
+pub const MAX_PACKET_LENGTH: i32 = lsb!(21);
+
+/// The state a connection is in, which decides what each packet id means.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum State {
+    Handshaking,
+    Status,
+    Login,
+    Play,
+}
+
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A packet that's been split off the stream, but whose body hasn't been
+/// decoded yet.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RawPacket {
+    pub id: i32,
+    pub body: Vec<u8>,
+}
+
+impl RawPacket {
+    /// Encodes `value` as the body of a packet with the given id.
+    pub fn encode<T: Serialize>(
+        state: State,
+        options: Options,
+        id: i32,
+        value: &T,
+    ) -> Result<Self> {
+        let mut body = Vec::new();
+
+        value
+            .serialize(&mut Serializer::with_options(&mut body, options))
+            .map_err(|err| {
+                err.with_context(|context| {
+                    context.state = Some(state);
+                    context.packet_id = Some(id);
+                })
+            })?;
+
+        Ok(Self { id, body })
+    }
+
+    /// Decodes the body of the packet, annotating any error with `state`, the
+    /// packet's id and the offset it happened at.
+    pub fn decode<T: DeserializeOwned>(&self, state: State, options: Options) -> Result<T> {
+        let mut deserializer = Deserializer::with_options(&self.body[..], options);
+
+        let result = T::deserialize(&mut deserializer).and_then(|value| {
+            match self.body.len() - deserializer.offset() as usize {
+                0 => Ok(value),
+                n => Err(Error::TrailingBytes(n)),
+            }
+        });
+
+        result.map_err(|err| {
+            err.with_context(|context| {
+                context.state = Some(state);
+                context.packet_id = Some(self.id);
+                context.offset.get_or_insert(deserializer.offset());
+            })
+        })
+    }
+
+    /// Reads a length-prefixed packet from `r`.
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+        let length = VarInt::read_from(r)?.0;
+        if length <= 0 || length > MAX_PACKET_LENGTH {
+            return Err(Error::InvalidPacketLength(length));
+        }
+
+        let mut buf = vec![0; length as usize];
+        r.read_exact(&mut buf)?;
+
+        let mut rest = &buf[..];
+        let id = VarInt::read_from(&mut rest)?.0;
+
+        Ok(Self {
+            id,
+            body: rest.to_vec(),
+        })
+    }
+
+    /// Writes the packet to `w`, prefixed by its length.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+        let mut id = Vec::new();
+        VarInt(self.id).write_to(&mut id)?;
+
+        let length = id.len() + self.body.len();
+        if length > MAX_PACKET_LENGTH as usize {
+            let length = i32::try_from(length).unwrap_or(i32::MAX);
+            return Err(Error::InvalidPacketLength(length));
+        }
+
+        VarInt(length as i32).write_to(w)?;
+        w.write_all(&id)?;
+        w.write_all(&self.body)?;
+
+        Ok(())
+    }
+}
@@ -2,25 +2,110 @@
 
 pub mod de;
 pub mod error;
+pub mod framing;
 pub mod ser;
 
+use super::objs::PositionLayout;
+
+/// Encoding choices that differ between protocol versions, and so are made
+/// per connection.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct Options {
+    pub position_layout: PositionLayout,
+}
+
+impl Options {
+    /// Returns the options clients speaking `protocol_version` expect.
+    pub fn for_protocol(protocol_version: i32) -> Self {
+        Self {
+            position_layout: PositionLayout::for_protocol(protocol_version),
+        }
+    }
+}
+
+/// Returns the discriminant written on the wire for an enum variant.
+///
+/// Variants are numbered in declaration order, unless they've been renamed to
+/// an integer literal (e.g. `#[serde(rename = "0x03")]`), in which case that
+/// value is used instead. Nothing stops two variants from ending up with the
+/// same discriminant, so enums that rename variants this way should be
+/// checked with `duplicate_discriminant` in their tests.
+fn variant_discriminant(index: u32, variant: &str) -> i32 {
+    let explicit = match variant.strip_prefix("0x") {
+        Some(hex) => i32::from_str_radix(hex, 16),
+        None => variant.parse(),
+    };
+
+    explicit.unwrap_or(index as i32)
+}
+
+/// Returns a discriminant shared by two variants of `T`, which would make
+/// them indistinguishable on the wire.
+#[cfg(test)]
+pub(crate) fn duplicate_discriminant<'de, T: serde::Deserialize<'de>>() -> Option<i32> {
+    use serde::de::{self, Visitor};
+
+    /// Records the variants of the enum it's asked to deserialize.
+    struct Variants<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> de::Deserializer<'de> for Variants<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not an enum"))
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            variants: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = variants;
+            Err(de::Error::custom("variants recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct identifier ignored_any
+        }
+    }
+
+    let mut variants: &'static [&'static str] = &[];
+    let _ = T::deserialize(Variants(&mut variants));
+    assert!(!variants.is_empty(), "not an enum");
+
+    let mut seen = std::collections::HashSet::new();
+    (0..)
+        .zip(variants)
+        .map(|(index, variant)| variant_discriminant(index, variant))
+        .find(|&discriminant| !seen.insert(discriminant))
+}
+
 #[cfg(test)]
 mod tests {
     use proptest::prelude::*;
 
+    use crate::coder::error::Error;
+
     macro_rules! coder_roundtrip {
-        ($expr:block$(: $expr_ty:ty)?) => {{
+        ($expr:block$(: $expr_ty:ty)?) => {
+            coder_roundtrip!($expr$(: $expr_ty)?, options: crate::coder::Options::default())
+        };
+
+        ($expr:block$(: $expr_ty:ty)?, options: $options:expr) => {{
             let value = $expr;
 
             let mut cursor = std::io::Cursor::new(Vec::new());
 
-            let mut serializer = crate::coder::ser::Serializer::new(&mut cursor);
+            let mut serializer = crate::coder::ser::Serializer::with_options(&mut cursor, $options);
             serde::ser::Serialize::serialize(&value, &mut serializer).unwrap();
 
             cursor.set_position(0);
 
             let deserialized$(: $expr_ty)? = {
-                let mut deserializer = crate::coder::de::Deserializer::new(&mut cursor);
+                let mut deserializer = crate::coder::de::Deserializer::with_options(&mut cursor, $options);
                 serde::de::Deserialize::deserialize(&mut deserializer).unwrap()
             };
 
@@ -59,11 +144,286 @@ mod tests {
         coder_roundtrip_proptest!(n: i32 => { VarInt(n) }, n: i64 => { VarLong(n) });
     }
 
+    #[test]
+    fn test_varint_golden() {
+        use crate::objs::{VarInt, VarLong};
+
+        macro_rules! golden {
+            ($($ty:ident($n:expr) => [$($byte:literal),*]),* $(,)?) => {$(
+                assert_eq!(to_bytes(&$ty($n)), [$($byte),*]);
+                assert_eq!(from_bytes::<$ty>(&[$($byte),*]).unwrap(), $ty($n));
+            )*};
+        }
+
+        // Sample values from https://wiki.vg/Protocol#VarInt_and_VarLong
+        golden!(
+            VarInt(0) => [0x00],
+            VarInt(1) => [0x01],
+            VarInt(2) => [0x02],
+            VarInt(127) => [0x7f],
+            VarInt(128) => [0x80, 0x01],
+            VarInt(255) => [0xff, 0x01],
+            VarInt(25565) => [0xdd, 0xc7, 0x01],
+            VarInt(2_097_151) => [0xff, 0xff, 0x7f],
+            VarInt(2_147_483_647) => [0xff, 0xff, 0xff, 0xff, 0x07],
+            VarInt(-1) => [0xff, 0xff, 0xff, 0xff, 0x0f],
+            VarInt(-2_147_483_648) => [0x80, 0x80, 0x80, 0x80, 0x08],
+        );
+
+        golden!(
+            VarLong(0) => [0x00],
+            VarLong(1) => [0x01],
+            VarLong(2) => [0x02],
+            VarLong(127) => [0x7f],
+            VarLong(128) => [0x80, 0x01],
+            VarLong(255) => [0xff, 0x01],
+            VarLong(2_147_483_647) => [0xff, 0xff, 0xff, 0xff, 0x07],
+            VarLong(9_223_372_036_854_775_807) => [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f],
+            VarLong(-1) => [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            VarLong(-2_147_483_648) => [0x80, 0x80, 0x80, 0x80, 0xf8, 0xff, 0xff, 0xff, 0xff, 0x01],
+            VarLong(-9_223_372_036_854_775_808) => [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01],
+        );
+    }
+
+    #[test]
+    fn test_varint_limits() {
+        use crate::objs::{VarInt, VarLong};
+
+        assert_eq!(
+            from_bytes::<VarInt>(&[0xff, 0xff, 0xff, 0xff, 0x07]).unwrap(),
+            VarInt(i32::MAX)
+        );
+        assert!(matches!(
+            from_bytes::<VarInt>(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x01]),
+            Err(Error::HumongousVarInt)
+        ));
+        assert!(matches!(
+            from_bytes::<VarLong>(&[0x80; 11]),
+            Err(Error::HumongousVarInt)
+        ));
+        assert!(matches!(
+            from_bytes::<VarInt>(&[0x81, 0x00]),
+            Err(Error::HumongousVarInt)
+        ));
+        assert!(matches!(
+            from_bytes::<VarInt>(&[0x81]),
+            Err(Error::IoError(_))
+        ));
+    }
+
     #[test]
     fn test_string() {
         coder_roundtrip_proptest!(String);
     }
 
+    #[test]
+    fn test_seq() {
+        coder_roundtrip_proptest!(Vec<u8>, Vec<i32>, Vec<String>);
+
+        assert_eq!(from_bytes::<Vec<u16>>(&[]).unwrap(), Vec::<u16>::new());
+        assert!(matches!(
+            from_bytes::<Vec<u16>>(&[0x00, 0x01, 0x02]),
+            Err(Error::IoError(_))
+        ));
+    }
+
+    fn to_bytes<T: serde::ser::Serialize>(value: &T) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut serializer = crate::coder::ser::Serializer::new(&mut buf);
+        value.serialize(&mut serializer).unwrap();
+        buf
+    }
+
+    fn from_bytes<'de, T: serde::de::Deserialize<'de>>(
+        bytes: &[u8],
+    ) -> crate::coder::error::Result<T> {
+        let mut deserializer = crate::coder::de::Deserializer::new(bytes);
+        T::deserialize(&mut deserializer)
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Unit,
+        Newtype(i32),
+        Tuple(u8, bool),
+        Struct { a: i16, b: String },
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    enum Explicit {
+        A,
+        #[serde(rename = "0x80")]
+        B,
+        #[serde(rename = "7")]
+        C(u8),
+        D,
+    }
+
+    #[test]
+    fn test_enum() {
+        coder_roundtrip_proptest!(
+            _x: () => { Shape::Unit },
+            x: i32 => { Shape::Newtype(x) },
+            x: (u8, bool) => { Shape::Tuple(x.0, x.1) },
+            x: (i16, String) => { Shape::Struct { a: x.0, b: x.1 } }
+        );
+    }
+
+    #[test]
+    fn test_enum_discriminants() {
+        assert_eq!(to_bytes(&Shape::Unit), [0x00]);
+        assert_eq!(to_bytes(&Shape::Tuple(0xab, true)), [0x02, 0xab, 0x01]);
+        assert_eq!(to_bytes(&Explicit::A), [0x00]);
+        assert_eq!(to_bytes(&Explicit::B), [0x80, 0x01]);
+        assert_eq!(to_bytes(&Explicit::C(9)), [0x07, 0x09]);
+        assert_eq!(to_bytes(&Explicit::D), [0x03]);
+
+        assert_eq!(from_bytes::<Explicit>(&[0x80, 0x01]).unwrap(), Explicit::B);
+        assert_eq!(
+            from_bytes::<Explicit>(&[0x07, 0x09]).unwrap(),
+            Explicit::C(9)
+        );
+        assert!(from_bytes::<Explicit>(&[0x01]).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_discriminant() {
+        use crate::coder::duplicate_discriminant;
+
+        #[derive(serde::Deserialize)]
+        enum Clash {
+            A,
+            #[serde(rename = "0")]
+            B,
+        }
+
+        assert_eq!(duplicate_discriminant::<Shape>(), None);
+        assert_eq!(duplicate_discriminant::<Explicit>(), None);
+        assert_eq!(duplicate_discriminant::<Clash>(), Some(0));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        macro_rules! assert_unsupported {
+            ($result:expr, $method:literal) => {
+                assert!(matches!(
+                    $result,
+                    Err(Error::Unsupported { method: $method })
+                ));
+            };
+        }
+
+        let map = std::iter::once((1u8, 2u8)).collect::<std::collections::HashMap<_, _>>();
+
+        let mut buf = Vec::new();
+        let mut serializer = crate::coder::ser::Serializer::new(&mut buf);
+        assert_unsupported!(
+            serde::Serialize::serialize(&'a', &mut serializer),
+            "serialize_char"
+        );
+        assert_unsupported!(
+            serde::Serialize::serialize(&map, &mut serializer),
+            "serialize_map"
+        );
+
+        assert_unsupported!(from_bytes::<char>(&[0x61]), "deserialize_char");
+        assert_unsupported!(from_bytes::<Option<u8>>(&[0x01]), "deserialize_option");
+        assert_unsupported!(
+            from_bytes::<std::collections::HashMap<u8, u8>>(&[0x01]),
+            "deserialize_map"
+        );
+        assert_unsupported!(
+            from_bytes::<serde::de::IgnoredAny>(&[0x01]),
+            "deserialize_ignored_any"
+        );
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Packet {
+        a: u8,
+        shape: Shape,
+    }
+
+    #[test]
+    fn test_packet_framing() {
+        use crate::coder::{
+            framing::{RawPacket, State},
+            Options,
+        };
+
+        let value = Packet {
+            a: 0xff,
+            shape: Shape::Struct {
+                a: -2,
+                b: "hi".into(),
+            },
+        };
+
+        let packet = RawPacket::encode(State::Play, Options::default(), 0x1a, &value).unwrap();
+
+        let mut buf = Vec::new();
+        packet.write_to(&mut buf).unwrap();
+        assert_eq!(buf[..2], [buf.len() as u8 - 1, 0x1a]);
+
+        let read = RawPacket::read_from(&mut &buf[..]).unwrap();
+        assert_eq!(read, packet);
+        assert_eq!(
+            read.decode::<Packet>(State::Play, Options::default())
+                .unwrap(),
+            value
+        );
+
+        assert!(matches!(
+            RawPacket::read_from(&mut &[0x80, 0x80, 0x80, 0x01][..]),
+            Err(Error::InvalidPacketLength(0x20_00_00))
+        ));
+    }
+
+    #[test]
+    fn test_error_context() {
+        use crate::coder::{
+            error::Context,
+            framing::{RawPacket, State},
+            Options,
+        };
+
+        // `Shape::Tuple(1, <invalid bool>)` inside the `shape` field.
+        let packet = RawPacket {
+            id: 0x05,
+            body: vec![0x01, 0x02, 0x01, 0x02],
+        };
+
+        let err = packet
+            .decode::<Packet>(State::Login, Options::default())
+            .unwrap_err();
+        assert!(matches!(err.kind(), Error::InvalidBooleanValue(2)));
+        assert_eq!(
+            err.context(),
+            Some(&Context {
+                state: Some(State::Login),
+                packet_id: Some(0x05),
+                field_path: vec!["shape".into(), "Tuple".into(), "1".into()],
+                offset: Some(3),
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid value for boolean: expected 0x00 or 0x01, found 0x2 \
+             (in packet 0x05, in state Login, in field `shape.Tuple.1`, at byte 3)"
+        );
+
+        let packet = RawPacket {
+            id: 0x05,
+            body: vec![0x01, 0x00, 0xff],
+        };
+
+        let err = packet
+            .decode::<Packet>(State::Login, Options::default())
+            .unwrap_err();
+        assert!(matches!(err.kind(), Error::TrailingBytes(1)));
+        assert_eq!(err.context().unwrap().offset, Some(2));
+    }
+
     macro_rules! signed_int_range {
         ($bits:literal) => {
             -(1 << ($bits - 1))..(1 << ($bits - 1)) - 1
@@ -73,8 +433,52 @@ mod tests {
     proptest! {
         #[test]
         fn test_position(x in signed_int_range!(26), y in signed_int_range!(12), z in signed_int_range!(26)) {
-            use crate::objs::Position;
-            coder_roundtrip!({ Position { x, y: y as _, z } });
+            use crate::{coder::Options, objs::{Position, PositionLayout}};
+
+            for &position_layout in &[PositionLayout::Xyz, PositionLayout::Xzy] {
+                coder_roundtrip!({ Position { x, y: y as _, z } }, options: Options { position_layout });
+            }
         }
     }
+
+    #[test]
+    fn test_position_layout() {
+        use crate::{
+            coder::{de::Deserializer, ser::Serializer, Options},
+            objs::{Position, PositionLayout},
+        };
+
+        // Sample value from https://wiki.vg/Protocol#Position
+        let position = Position {
+            x: 18_357_644,
+            y: 831,
+            z: -20_882_616,
+        };
+
+        for &(protocol_version, packed) in &[
+            (404, 0x4607_630c_fec1_5b48u64),
+            (477, 0x4607_632c_15b4_833fu64),
+        ] {
+            let options = Options::for_protocol(protocol_version);
+
+            let mut buf = Vec::new();
+            let mut serializer = Serializer::with_options(&mut buf, options);
+            serde::Serialize::serialize(&position, &mut serializer).unwrap();
+            assert_eq!(buf, packed.to_be_bytes());
+
+            let mut deserializer = Deserializer::with_options(&buf[..], options);
+            let deserialized: Position =
+                serde::Deserialize::deserialize(&mut deserializer).unwrap();
+            assert_eq!(deserialized, position);
+        }
+
+        assert_eq!(
+            Options::for_protocol(404).position_layout,
+            PositionLayout::Xyz
+        );
+        assert_eq!(
+            Options::for_protocol(477).position_layout,
+            PositionLayout::Xzy
+        );
+    }
 }
@@ -3,15 +3,40 @@ use std::io::Write;
 use serde::ser::{self, Serialize};
 
 use super::{
-    super::objs::VarInt,
+    super::objs::{position::POSITION_TOKEN, Position, PositionLayout, VarInt, VarLong},
     error::{Error, Result},
+    variant_discriminant, Options,
 };
 
-pub struct Serializer<W: Write>(W);
+pub struct Serializer<W: Write> {
+    w: W,
+    options: Options,
+}
 
 impl<W: Write> Serializer<W> {
     pub fn new(w: W) -> Self {
-        Self(w)
+        Self::with_options(w, Options::default())
+    }
+
+    pub fn with_options(w: W, options: Options) -> Self {
+        Self { w, options }
+    }
+
+    pub fn write_varint(&mut self, value: i32) -> Result<()> {
+        VarInt(value).write_to(&mut self.w).map_err(Into::into)
+    }
+
+    pub fn write_varlong(&mut self, value: i64) -> Result<()> {
+        VarLong(value).write_to(&mut self.w).map_err(Into::into)
+    }
+
+    /// Writes `bytes` as they are, for formats built on top of this one.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.w.write_all(bytes).map_err(Into::into)
+    }
+
+    fn serialize_variant_tag(&mut self, index: u32, variant: &'static str) -> Result<()> {
+        self.write_varint(variant_discriminant(index, variant))
     }
 }
 
@@ -19,7 +44,7 @@ macro_rules! ser_int {
     ($($name:ident: $ty:ty),*) => {
         $(
         fn $name(self, v: $ty) -> Result<()> {
-            self.0.write_all(&v.to_be_bytes()).map_err(Into::into)
+            self.w.write_all(&v.to_be_bytes()).map_err(Into::into)
         }
         )*
     }
@@ -35,6 +60,8 @@ macro_rules! ser_float {
     }
 }
 
+/// Stands in for the serializers of types the protocol has no encoding for,
+/// which are never handed out since the methods returning them always fail.
 pub enum NoSerialize {}
 
 impl serde::ser::SerializeMap for NoSerialize {
@@ -42,80 +69,15 @@ impl serde::ser::SerializeMap for NoSerialize {
     type Error = Error;
 
     fn serialize_key<T: ?Sized>(&mut self, _key: &T) -> Result<()> {
-        unreachable!()
+        match *self {}
     }
 
     fn serialize_value<T: ?Sized>(&mut self, _value: &T) -> Result<()> {
-        unreachable!()
-    }
-
-    fn end(self) -> Result<()> {
-        unreachable!()
-    }
-}
-
-impl serde::ser::SerializeTuple for NoSerialize {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_element<T: ?Sized>(&mut self, _element: &T) -> Result<()> {
-        unreachable!()
-    }
-
-    fn end(self) -> Result<()> {
-        unreachable!()
-    }
-}
-
-impl serde::ser::SerializeTupleStruct for NoSerialize {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, _element: &T) -> Result<()> {
-        unreachable!()
-    }
-
-    fn end(self) -> Result<()> {
-        unreachable!()
-    }
-}
-
-impl serde::ser::SerializeTupleVariant for NoSerialize {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, _element: &T) -> Result<()> {
-        unreachable!()
-    }
-
-    fn end(self) -> Result<()> {
-        unreachable!()
-    }
-}
-
-impl serde::ser::SerializeStruct for NoSerialize {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, _value: &T) -> Result<()> {
-        unreachable!()
-    }
-
-    fn end(self) -> Result<()> {
-        unreachable!()
-    }
-}
-
-impl serde::ser::SerializeStructVariant for NoSerialize {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, _key: &'static str, _value: &T) -> Result<()> {
-        unreachable!()
+        match *self {}
     }
 
     fn end(self) -> Result<()> {
-        unreachable!()
+        match self {}
     }
 }
 
@@ -125,15 +87,15 @@ impl<W: Write> ser::Serializer for &'_ mut Serializer<W> {
     type Error = Error;
 
     type SerializeSeq = Self;
-    type SerializeTuple = NoSerialize;
-    type SerializeTupleStruct = NoSerialize;
-    type SerializeTupleVariant = NoSerialize;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
     type SerializeMap = NoSerialize;
-    type SerializeStruct = NoSerialize;
-    type SerializeStructVariant = NoSerialize;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
 
     fn serialize_bool(self, v: bool) -> Result<()> {
-        self.0
+        self.w
             .write_all(&[if v { 0x01u8 } else { 0x00u8 }] as &[_])
             .map_err(Into::into)
     }
@@ -157,7 +119,9 @@ impl<W: Write> ser::Serializer for &'_ mut Serializer<W> {
     ser_float!(serialize_f32: f32, serialize_f64: f64);
 
     fn serialize_char(self, _v: char) -> Result<()> {
-        unimplemented!();
+        Err(Error::Unsupported {
+            method: "serialize_char",
+        })
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
@@ -165,19 +129,15 @@ impl<W: Write> ser::Serializer for &'_ mut Serializer<W> {
 
         let cs = v.chars().map(|c| c as u32).collect::<Vec<_>>();
 
-        i32::try_from(cs.len())
-            .map(VarInt)
-            .map_err(|_| Error::HumongousString)?
-            .serialize(&mut *self)?;
+        let len = i32::try_from(cs.len()).map_err(|_| Error::HumongousString)?;
+        self.write_varint(len)?;
 
-        cs.into_iter()
-            .map(|c| c.serialize(&mut *self))
-            .collect::<Result<()>>()
+        cs.into_iter().try_for_each(|c| c.serialize(&mut *self))
     }
 
     // Useful for VarInt and VarLong
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.0.write_all(v).map_err(Into::into)
+        self.w.write_all(v).map_err(Into::into)
     }
 
     fn serialize_none(self) -> Result<()> {
@@ -203,32 +163,43 @@ impl<W: Write> ser::Serializer for &'_ mut Serializer<W> {
         self,
         _enum_name: &'static str,
         index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<()> {
-        self.serialize_u32(index)
+        self.serialize_variant_tag(index, variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        // Positions hand us their pre-1.14 packing, which we might need to
+        // repack into the layout this connection uses.
+        if name == POSITION_TOKEN {
+            let mut buf = [0; 8];
+            value.serialize(&mut Serializer::new(&mut buf[..]))?;
+
+            let position = Position::unpack(u64::from_be_bytes(buf), PositionLayout::Xyz);
+            return self.serialize_u64(position.pack(self.options.position_layout)?);
+        }
+
         value.serialize(self)
     }
 
     // Note that newtype variant (and all of the other variant serialization
-    // methods) refer exclusively to the "externally tagged" enum
-    // representation.
+    // methods) are written as the VarInt discriminant followed by the fields,
+    // which is how the protocol encodes its tagged unions.
     fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
     ) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        self.serialize_variant_tag(variant_index, variant)?;
+        value.serialize(self)
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
@@ -236,59 +207,79 @@ impl<W: Write> ser::Serializer for &'_ mut Serializer<W> {
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        unimplemented!();
+        Ok(self)
     }
 
-    // Tuple structs look just like sequences in JSON.
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        unimplemented!();
+        Ok(self)
     }
 
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
+        variant_index: u32,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        unimplemented!()
+        self.serialize_variant_tag(variant_index, variant)?;
+        Ok(self)
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        unimplemented!();
+        Err(Error::Unsupported {
+            method: "serialize_map",
+        })
     }
 
-    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
-        self.serialize_map(Some(len))
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Ok(self)
     }
 
     fn serialize_struct_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
+        variant_index: u32,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        unimplemented!();
+        self.serialize_variant_tag(variant_index, variant)?;
+        Ok(self)
     }
 }
 
-impl<W: Write> ser::SerializeSeq for &'_ mut Serializer<W> {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        value.serialize(&mut **self)
-    }
+// Sequences, tuples and structs are all written as their elements back to
+// back, without any length prefix or field names.
+macro_rules! ser_compound {
+    ($($trait:ident::$method:ident$(($key:ty))?),*) => {
+        $(
+        impl<W: Write> ser::$trait for &'_ mut Serializer<W> {
+            type Ok = ();
+            type Error = Error;
+
+            fn $method<T>(&mut self, $(_key: $key,)? value: &T) -> Result<()>
+            where
+                T: ?Sized + Serialize,
+            {
+                value.serialize(&mut **self)
+            }
 
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
+            fn end(self) -> Result<()> {
+                Ok(())
+            }
+        }
+        )*
+    };
 }
+
+ser_compound!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field,
+    SerializeStruct::serialize_field(&'static str),
+    SerializeStructVariant::serialize_field(&'static str)
+);
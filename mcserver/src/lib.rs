@@ -1,10 +1,11 @@
-#![feature(never_type)]
-
 macro_rules! lsb {
     ($n:expr) => {
-        (1 << $n) - 1
+        ((1 << $n) - 1)
     };
 }
 
-mod coder;
-mod objs;
+pub mod coder;
+pub mod nbt;
+pub mod objs;
+pub mod registry;
+pub mod world;
@@ -0,0 +1,90 @@
+use std::marker::PhantomData;
+
+use serde::{
+    de::{self, Deserialize, Deserializer, Visitor},
+    ser::{Serialize, Serializer},
+};
+
+/// Newtype struct names our own serializer recognizes, so that it can write
+/// these wrappers as typed arrays instead of lists.
+pub(crate) const BYTE_ARRAY_TOKEN: &str = "$mcserver::nbt::ByteArray";
+pub(crate) const INT_ARRAY_TOKEN: &str = "$mcserver::nbt::IntArray";
+pub(crate) const LONG_ARRAY_TOKEN: &str = "$mcserver::nbt::LongArray";
+
+macro_rules! nbt_array_impl {
+    ($($name:ident: $ty:ty => $token:ident),*) => {
+        $(
+        /// A field serialized as a typed array tag, rather than the list tag a
+        /// plain `Vec` becomes.
+        #[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+        pub struct $name(pub Vec<$ty>);
+
+        impl From<Vec<$ty>> for $name {
+            fn from(array: Vec<$ty>) -> Self {
+                $name(array)
+            }
+        }
+
+        impl From<$name> for Vec<$ty> {
+            fn from(array: $name) -> Self {
+                array.0
+            }
+        }
+
+        impl std::ops::Deref for $name {
+            type Target = Vec<$ty>;
+
+            fn deref(&self) -> &Vec<$ty> {
+                &self.0
+            }
+        }
+
+        impl std::ops::DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut Vec<$ty> {
+                &mut self.0
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct($token, &self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer
+                    .deserialize_newtype_struct($token, ArrayVisitor(PhantomData))
+                    .map($name)
+            }
+        }
+        )*
+    };
+}
+
+nbt_array_impl!(
+    ByteArray: i8 => BYTE_ARRAY_TOKEN,
+    IntArray: i32 => INT_ARRAY_TOKEN,
+    LongArray: i64 => LONG_ARRAY_TOKEN
+);
+
+struct ArrayVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for ArrayVisitor<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "an array")
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        Vec::deserialize(de::value::SeqAccessDeserializer::new(seq))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        Vec::deserialize(deserializer)
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    error::{Error, Result},
+    id, Compound, Limits, Tag,
+};
+use crate::coder;
+
+/// Most elements preallocated for an array or list, so that a bogus length
+/// can't make us allocate more than the input could ever fill.
+const MAX_PREALLOCATED: usize = 1024;
+
+pub(crate) struct Reader<R: Read> {
+    de: coder::de::Deserializer<R>,
+    limits: Limits,
+    size: u64,
+    depth: usize,
+}
+
+impl<R: Read> Reader<R> {
+    pub(crate) fn new(r: R, limits: Limits) -> Self {
+        Self {
+            de: coder::de::Deserializer::new(r),
+            limits,
+            size: 0,
+            depth: 0,
+        }
+    }
+
+    /// Accounts for `n` more bytes of tag data, failing once they exceed the
+    /// size limit.
+    fn charge(&mut self, n: u64) -> Result<()> {
+        self.size = self.size.saturating_add(n);
+
+        if self.size > self.limits.max_size {
+            Err(Error::SizeLimit(self.limits.max_size))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read<T: DeserializeOwned>(&mut self) -> Result<T> {
+        T::deserialize(&mut self.de).map_err(Into::into)
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        let len: i32 = self.read()?;
+        usize::try_from(len).map_err(|_| Error::NegativeLength(len))
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let len: u16 = self.read()?;
+        self.charge(2 + u64::from(len))?;
+
+        let mut buf = vec![0; usize::from(len)];
+        self.de.read_bytes(&mut buf)?;
+        decode_mutf8(&buf)
+    }
+
+    fn read_array<T: DeserializeOwned>(&mut self, element_size: u64) -> Result<Vec<T>> {
+        let len = self.read_len()?;
+        self.charge(4 + len as u64 * element_size)?;
+
+        let mut array = Vec::with_capacity(len.min(MAX_PREALLOCATED));
+        for _ in 0..len {
+            array.push(self.read()?);
+        }
+
+        Ok(array)
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+
+        if self.depth > self.limits.max_depth {
+            Err(Error::DepthLimit(self.limits.max_depth))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads a tag id, returning `None` for an end tag.
+    pub(crate) fn read_id(&mut self) -> Result<Option<u8>> {
+        self.charge(1)?;
+
+        match self.read()? {
+            id::END => Ok(None),
+            id => Ok(Some(id)),
+        }
+    }
+
+    pub(crate) fn read_named(&mut self) -> Result<Option<(String, Tag)>> {
+        let id = match self.read_id()? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+
+        let name = self.read_string()?;
+        Ok(Some((name, self.read_payload(id)?)))
+    }
+
+    pub(crate) fn read_unnamed(&mut self) -> Result<Option<Tag>> {
+        match self.read_id()? {
+            Some(id) => self.read_payload(id).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read_payload(&mut self, id: u8) -> Result<Tag> {
+        Ok(match id {
+            id::BYTE => {
+                self.charge(1)?;
+                Tag::Byte(self.read()?)
+            }
+
+            id::SHORT => {
+                self.charge(2)?;
+                Tag::Short(self.read()?)
+            }
+
+            id::INT => {
+                self.charge(4)?;
+                Tag::Int(self.read()?)
+            }
+
+            id::LONG => {
+                self.charge(8)?;
+                Tag::Long(self.read()?)
+            }
+
+            id::FLOAT => {
+                self.charge(4)?;
+                Tag::Float(self.read()?)
+            }
+
+            id::DOUBLE => {
+                self.charge(8)?;
+                Tag::Double(self.read()?)
+            }
+
+            id::BYTE_ARRAY => Tag::ByteArray(self.read_array(1)?),
+            id::INT_ARRAY => Tag::IntArray(self.read_array(4)?),
+            id::LONG_ARRAY => Tag::LongArray(self.read_array(8)?),
+
+            id::STRING => Tag::String(self.read_string()?),
+
+            id::LIST => {
+                self.enter()?;
+
+                let element_id: u8 = self.read()?;
+                let len = self.read_len()?;
+                self.charge(5 + len as u64 * 4)?;
+
+                if element_id == id::END && len > 0 {
+                    return Err(Error::InvalidTagId(element_id));
+                }
+
+                let mut list = Vec::with_capacity(len.min(MAX_PREALLOCATED));
+                for _ in 0..len {
+                    list.push(self.read_payload(element_id)?);
+                }
+
+                self.depth -= 1;
+                Tag::List(list)
+            }
+
+            id::COMPOUND => {
+                self.enter()?;
+
+                let mut compound = Compound::new();
+                while let Some(id) = self.read_id()? {
+                    let name = self.read_string()?;
+                    let tag = self.read_payload(id)?;
+                    compound.insert(name, tag);
+                }
+
+                self.depth -= 1;
+                Tag::Compound(compound)
+            }
+
+            id => return Err(Error::InvalidTagId(id)),
+        })
+    }
+}
+
+pub(crate) struct Writer<W: Write> {
+    ser: coder::ser::Serializer<W>,
+}
+
+impl<W: Write> Writer<W> {
+    pub(crate) fn new(w: W) -> Self {
+        Self {
+            ser: coder::ser::Serializer::new(w),
+        }
+    }
+
+    fn write<T: Serialize>(&mut self, value: T) -> Result<()> {
+        value.serialize(&mut self.ser).map_err(Into::into)
+    }
+
+    fn write_len(&mut self, len: usize) -> Result<()> {
+        let len =
+            i32::try_from(len).map_err(|_| Error::Unsupported("more than i32::MAX elements"))?;
+        self.write(len)
+    }
+
+    fn write_string(&mut self, s: &str) -> Result<()> {
+        let buf = encode_mutf8(s);
+        let len = u16::try_from(buf.len()).map_err(|_| Error::HumongousString(buf.len()))?;
+
+        self.write(len)?;
+        self.ser.write_bytes(&buf).map_err(Into::into)
+    }
+
+    fn write_array<T: Serialize + Copy>(&mut self, array: &[T]) -> Result<()> {
+        self.write_len(array.len())?;
+        array.iter().try_for_each(|&n| self.write(n))
+    }
+
+    pub(crate) fn write_named(&mut self, name: &str, tag: &Tag) -> Result<()> {
+        self.write(tag.id())?;
+        self.write_string(name)?;
+        self.write_payload(tag)
+    }
+
+    pub(crate) fn write_unnamed(&mut self, tag: &Tag) -> Result<()> {
+        self.write(tag.id())?;
+        self.write_payload(tag)
+    }
+
+    pub(crate) fn write_end(&mut self) -> Result<()> {
+        self.write(id::END)
+    }
+
+    fn write_payload(&mut self, tag: &Tag) -> Result<()> {
+        match tag {
+            Tag::Byte(n) => self.write(n),
+            Tag::Short(n) => self.write(n),
+            Tag::Int(n) => self.write(n),
+            Tag::Long(n) => self.write(n),
+            Tag::Float(n) => self.write(n),
+            Tag::Double(n) => self.write(n),
+            Tag::ByteArray(array) => self.write_array(array),
+            Tag::String(s) => self.write_string(s),
+            Tag::IntArray(array) => self.write_array(array),
+            Tag::LongArray(array) => self.write_array(array),
+
+            Tag::List(list) => {
+                let element_id = list_element_id(list)?;
+                self.write(element_id)?;
+                self.write_len(list.len())?;
+                list.iter().try_for_each(|tag| self.write_payload(tag))
+            }
+
+            Tag::Compound(compound) => {
+                for (name, tag) in compound {
+                    self.write_named(name, tag)?;
+                }
+
+                self.write_end()
+            }
+        }
+    }
+}
+
+/// Returns the id of the tags in `list`, making sure they're all the same.
+pub(crate) fn list_element_id(list: &[Tag]) -> Result<u8> {
+    let expected = list.first().map_or(id::END, Tag::id);
+
+    match list.iter().find(|tag| tag.id() != expected) {
+        Some(tag) => Err(Error::MixedList {
+            expected,
+            found: tag.id(),
+        }),
+        None => Ok(expected),
+    }
+}
+
+/// Encodes a string as Java's Modified UTF-8, which encodes U+0000 as two
+/// bytes and characters outside the BMP as a surrogate pair of three bytes
+/// each.
+pub fn encode_mutf8(s: &str) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '\0' => buf.extend_from_slice(&[0xc0, 0x80]),
+
+            // Otherwise Modified UTF-8 only differs from UTF-8 for characters
+            // that take 4 bytes there.
+            c if c.len_utf8() < 4 => {
+                buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+
+            _ => {
+                let mut utf16 = [0; 2];
+                for &unit in c.encode_utf16(&mut utf16).iter() {
+                    buf.extend_from_slice(&[
+                        0xe0 | (unit >> 12) as u8,
+                        0x80 | ((unit >> 6) & lsb!(6)) as u8,
+                        0x80 | (unit & lsb!(6)) as u8,
+                    ]);
+                }
+            }
+        }
+    }
+
+    buf
+}
+
+/// Decodes Java's Modified UTF-8, see `encode_mutf8`.
+pub fn decode_mutf8(buf: &[u8]) -> Result<String> {
+    // Strings without NULs, surrogates and characters outside the BMP are
+    // encoded the same in both, which covers nearly all of them.
+    if !buf.iter().any(|&b| matches!(b, 0 | 0xed | 0xf0..=0xff)) {
+        if let Ok(s) = std::str::from_utf8(buf) {
+            return Ok(s.to_owned());
+        }
+    }
+
+    let mut units = Vec::with_capacity(buf.len());
+    let mut bytes = buf.iter().copied();
+
+    fn continuation(bytes: &mut impl Iterator<Item = u8>) -> Result<u16> {
+        match bytes.next() {
+            Some(b) if b & 0xc0 == 0x80 => Ok(u16::from(b & lsb!(6))),
+            _ => Err(Error::InvalidString),
+        }
+    }
+
+    while let Some(b) = bytes.next() {
+        let unit = match b {
+            0x01..=0x7f => u16::from(b),
+
+            0xc0..=0xdf => {
+                let unit = (u16::from(b & lsb!(5)) << 6) | continuation(&mut bytes)?;
+                if unit != 0 && unit < 0x80 {
+                    return Err(Error::InvalidString);
+                }
+                unit
+            }
+
+            0xe0..=0xef => {
+                let unit = (u16::from(b & lsb!(4)) << 12)
+                    | (continuation(&mut bytes)? << 6)
+                    | continuation(&mut bytes)?;
+                if unit < 0x800 {
+                    return Err(Error::InvalidString);
+                }
+                unit
+            }
+
+            _ => return Err(Error::InvalidString),
+        };
+
+        units.push(unit);
+    }
+
+    String::from_utf16(&units).map_err(|_| Error::InvalidString)
+}
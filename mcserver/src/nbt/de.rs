@@ -0,0 +1,174 @@
+use serde::{
+    de::{self, IntoDeserializer, Visitor},
+    forward_to_deserialize_any,
+};
+
+use super::{
+    error::{Error, Result},
+    tag::{ARRAY_VARIANTS, TAG_TOKEN},
+    Tag,
+};
+
+impl<'de> IntoDeserializer<'de, Error> for Tag {
+    type Deserializer = Tag;
+
+    fn into_deserializer(self) -> Tag {
+        self
+    }
+}
+
+fn visit_seq<'de, V, I>(visitor: V, iter: I) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+    I: Iterator,
+    I::Item: IntoDeserializer<'de, Error>,
+{
+    let mut seq = de::value::SeqDeserializer::new(iter);
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+/// The variant an enum was serialized as, i.e. the only key of a compound and
+/// its value.
+struct Enum {
+    variant: String,
+    value: Tag,
+}
+
+impl<'de> de::EnumAccess<'de> for Enum {
+    type Error = Error;
+    type Variant = Tag;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Tag)> {
+        let variant: de::value::StringDeserializer<Error> = self.variant.into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Tag {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Tag {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Tag::Byte(n) => visitor.visit_i8(n),
+            Tag::Short(n) => visitor.visit_i16(n),
+            Tag::Int(n) => visitor.visit_i32(n),
+            Tag::Long(n) => visitor.visit_i64(n),
+            Tag::Float(n) => visitor.visit_f32(n),
+            Tag::Double(n) => visitor.visit_f64(n),
+            Tag::String(s) => visitor.visit_string(s),
+            Tag::ByteArray(array) => visit_seq(visitor, array.into_iter()),
+            Tag::IntArray(array) => visit_seq(visitor, array.into_iter()),
+            Tag::LongArray(array) => visit_seq(visitor, array.into_iter()),
+            Tag::List(list) => visit_seq(visitor, list.into_iter()),
+            Tag::Compound(compound) => {
+                let mut map = de::value::MapDeserializer::new(compound.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    // Booleans are bytes, where vanilla treats anything but 0 as true.
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Tag::Byte(n) => visitor.visit_bool(n != 0),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    // Missing fields are handled by serde itself, so any tag that is there
+    // holds a value.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    // When deserializing a `Tag`, typed arrays are handed over as an enum
+    // variant named after their type, since as sequences they'd be
+    // indistinguishable from lists.
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name != TAG_TOKEN {
+            return visitor.visit_newtype_struct(self);
+        }
+
+        let variant = match self {
+            Tag::ByteArray(_) => ARRAY_VARIANTS[0],
+            Tag::IntArray(_) => ARRAY_VARIANTS[1],
+            Tag::LongArray(_) => ARRAY_VARIANTS[2],
+            _ => return visitor.visit_newtype_struct(self),
+        };
+
+        visitor.visit_enum(Enum {
+            variant: variant.to_owned(),
+            value: self,
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self {
+            Tag::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+
+            Tag::Compound(compound) if compound.len() == 1 => {
+                let (variant, value) = compound.into_iter().next().unwrap();
+                visitor.visit_enum(Enum { variant, value })
+            }
+
+            _ => Err(de::Error::custom(
+                "expected a string or a compound with a single key for an enum",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
@@ -0,0 +1,110 @@
+use crate::coder;
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(std::io::Error),
+    Custom(String),
+    InvalidTagId(u8),
+
+    /// A string wasn't valid Modified UTF-8, or held unpaired surrogates.
+    InvalidString,
+
+    /// A string is longer than the 65535 bytes its length prefix allows.
+    HumongousString(usize),
+
+    /// An array or list claims a negative length.
+    NegativeLength(i32),
+
+    /// A list held tags of different types.
+    MixedList {
+        expected: u8,
+        found: u8,
+    },
+
+    /// Lists and compounds were nested deeper than `Limits::max_depth`.
+    DepthLimit(usize),
+
+    /// Reading the tag would take more than `Limits::max_size` bytes.
+    SizeLimit(u64),
+
+    /// The stream held an end tag where the root tag was expected.
+    MissingRoot,
+
+    KeyMustBeString,
+
+    /// NBT has no encoding for this value, e.g. `None` in a list or a `u64`
+    /// beyond `i64::MAX`.
+    Unsupported(&'static str),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use Error::*;
+
+        match self {
+            IoError(err) => write!(f, "{}", err),
+
+            Custom(s) => write!(f, "{}", s),
+
+            InvalidTagId(id) => write!(f, "invalid tag id {}", id),
+
+            InvalidString => write!(f, "string is not valid Modified UTF-8"),
+
+            HumongousString(len) => write!(
+                f,
+                "string of {} bytes is longer than the maximum of {}",
+                len,
+                u16::MAX
+            ),
+
+            NegativeLength(len) => write!(f, "invalid length {}", len),
+
+            MixedList { expected, found } => write!(
+                f,
+                "list of tags with id {} contains a tag with id {}",
+                expected, found
+            ),
+
+            DepthLimit(max) => write!(f, "tags are nested deeper than {} levels", max),
+
+            SizeLimit(max) => write!(f, "tag is larger than the limit of {} bytes", max),
+
+            MissingRoot => write!(f, "expected a root tag, found an end tag"),
+
+            KeyMustBeString => write!(f, "compound keys must be strings"),
+
+            Unsupported(what) => write!(f, "NBT cannot represent {}", what),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Error::IoError(value)
+    }
+}
+
+impl From<coder::error::Error> for Error {
+    fn from(value: coder::error::Error) -> Self {
+        match value {
+            coder::error::Error::IoError(err) => Error::IoError(err),
+            err => Error::Custom(err.to_string()),
+        }
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Custom(format!("{}", msg))
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        serde::ser::Error::custom(msg)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
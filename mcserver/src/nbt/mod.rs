@@ -0,0 +1,382 @@
+//! NBT (Named Binary Tag), the format used for chunks, items, block entities,
+//! registries and `level.dat`.
+//!
+//! Tags can be read and written in their binary form or as SNBT, and
+//! converted from and to any type implementing `Serialize` and `Deserialize`.
+
+mod array;
+mod binary;
+pub mod de;
+pub mod error;
+pub(crate) mod network;
+pub mod ser;
+pub mod snbt;
+mod tag;
+
+use std::io::{Read, Write};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use array::{ByteArray, IntArray, LongArray};
+pub use binary::{decode_mutf8, encode_mutf8};
+use error::{Error, Result};
+pub use network::NetworkTag;
+pub use snbt::{SnbtError, SnbtErrorKind};
+pub use tag::{Compound, Tag};
+
+/// Tag ids, as written before every tag.
+pub mod id {
+    pub const END: u8 = 0;
+    pub const BYTE: u8 = 1;
+    pub const SHORT: u8 = 2;
+    pub const INT: u8 = 3;
+    pub const LONG: u8 = 4;
+    pub const FLOAT: u8 = 5;
+    pub const DOUBLE: u8 = 6;
+    pub const BYTE_ARRAY: u8 = 7;
+    pub const STRING: u8 = 8;
+    pub const LIST: u8 = 9;
+    pub const COMPOUND: u8 = 10;
+    pub const INT_ARRAY: u8 = 11;
+    pub const LONG_ARRAY: u8 = 12;
+}
+
+/// Bounds on the tags we're willing to read, so that untrusted data can't
+/// exhaust our memory or stack.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Limits {
+    /// How deep lists and compounds may be nested.
+    pub max_depth: usize,
+
+    /// Roughly how many bytes the tag may take, including the overhead of
+    /// lists and compounds.
+    pub max_size: u64,
+}
+
+impl Limits {
+    /// The limits vanilla uses for NBT sent by clients.
+    pub const NETWORK: Self = Self {
+        max_depth: 512,
+        max_size: 2 * 1024 * 1024,
+    };
+
+    /// The limits vanilla uses for files, which are trusted and so only
+    /// limited in depth.
+    pub const FILE: Self = Self {
+        max_depth: 512,
+        max_size: u64::MAX,
+    };
+}
+
+/// Reads a named tag, as stored in files and sent before 1.20.2, returning
+/// `None` if there's an end tag instead.
+pub fn read_named<R: Read>(r: R, limits: Limits) -> Result<Option<(String, Tag)>> {
+    binary::Reader::new(r, limits).read_named()
+}
+
+/// Reads a tag without a name, as sent since 1.20.2, returning `None` if
+/// there's an end tag instead.
+pub fn read_unnamed<R: Read>(r: R, limits: Limits) -> Result<Option<Tag>> {
+    binary::Reader::new(r, limits).read_unnamed()
+}
+
+pub fn write_named<W: Write>(w: W, name: &str, tag: &Tag) -> Result<()> {
+    binary::Writer::new(w).write_named(name, tag)
+}
+
+pub fn write_unnamed<W: Write>(w: W, tag: &Tag) -> Result<()> {
+    binary::Writer::new(w).write_unnamed(tag)
+}
+
+/// Writes an end tag, which stands in for a missing tag.
+pub fn write_end<W: Write>(w: W) -> Result<()> {
+    binary::Writer::new(w).write_end()
+}
+
+pub fn to_tag<T: ?Sized + Serialize>(value: &T) -> Result<Tag> {
+    value
+        .serialize(ser::Serializer)?
+        .ok_or(Error::Unsupported("`None` as the root tag"))
+}
+
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> Result<T> {
+    T::deserialize(tag)
+}
+
+/// Serializes `value` and writes it as a named tag.
+pub fn to_writer<W: Write, T: ?Sized + Serialize>(w: W, name: &str, value: &T) -> Result<()> {
+    write_named(w, name, &to_tag(value)?)
+}
+
+/// Reads a named tag and deserializes it, ignoring its name.
+pub fn from_reader<R: Read, T: DeserializeOwned>(r: R, limits: Limits) -> Result<T> {
+    let (_, tag) = read_named(r, limits)?.ok_or(Error::MissingRoot)?;
+    from_tag(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use proptest::prelude::*;
+    use serde::Deserialize;
+
+    use super::*;
+
+    fn write(tag: &Tag) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_named(&mut buf, "", tag).unwrap();
+        buf
+    }
+
+    fn read(buf: &[u8], limits: Limits) -> Result<Tag> {
+        read_named(buf, limits).map(|tag| tag.unwrap().1)
+    }
+
+    pub(super) fn tag() -> impl Strategy<Value = Tag> {
+        let leaf = prop_oneof![
+            any::<i8>().prop_map(Tag::Byte),
+            any::<i16>().prop_map(Tag::Short),
+            any::<i32>().prop_map(Tag::Int),
+            any::<i64>().prop_map(Tag::Long),
+            any::<f32>()
+                .prop_filter("NaN", |n| !n.is_nan())
+                .prop_map(Tag::Float),
+            any::<f64>()
+                .prop_filter("NaN", |n| !n.is_nan())
+                .prop_map(Tag::Double),
+            any::<Vec<i8>>().prop_map(Tag::ByteArray),
+            any::<String>().prop_map(Tag::String),
+            any::<Vec<i32>>().prop_map(Tag::IntArray),
+            any::<Vec<i64>>().prop_map(Tag::LongArray),
+        ];
+
+        leaf.prop_recursive(4, 64, 8, |inner| {
+            prop_oneof![
+                // Lists must be homogeneous, so repeat a single tag.
+                (inner.clone(), 0..8usize).prop_map(|(tag, n)| Tag::List(vec![tag; n])),
+                prop::collection::btree_map(".*", inner, 0..8).prop_map(Tag::Compound),
+            ]
+        })
+    }
+
+    #[test]
+    fn test_hello_world() {
+        // The `hello_world.nbt` example from the original specification.
+        let buf = b"\x0a\x00\x0bhello world\x08\x00\x04name\x00\x09Bananrama\x00";
+
+        let (name, tag) = read_named(&buf[..], Limits::NETWORK).unwrap().unwrap();
+        assert_eq!(name, "hello world");
+
+        let mut expected = Compound::new();
+        expected.insert("name".into(), Tag::String("Bananrama".into()));
+        assert_eq!(tag, Tag::Compound(expected));
+
+        let mut written = Vec::new();
+        write_named(&mut written, &name, &tag).unwrap();
+        assert_eq!(written, &buf[..]);
+    }
+
+    #[test]
+    fn test_end_tag() {
+        assert_eq!(read_named(&[0][..], Limits::NETWORK).unwrap(), None);
+        assert_eq!(read_unnamed(&[0][..], Limits::NETWORK).unwrap(), None);
+
+        let mut buf = Vec::new();
+        write_unnamed(&mut buf, &Tag::Short(-2)).unwrap();
+        write_end(&mut buf).unwrap();
+        assert_eq!(buf, [id::SHORT, 0xff, 0xfe, id::END]);
+
+        assert!(matches!(
+            from_reader::<_, i32>(&[0][..], Limits::NETWORK),
+            Err(Error::MissingRoot)
+        ));
+    }
+
+    #[test]
+    fn test_mutf8() {
+        assert_eq!(encode_mutf8("a\0é"), b"a\xc0\x80\xc3\xa9");
+        assert_eq!(encode_mutf8("\u{1f600}"), b"\xed\xa0\xbd\xed\xb8\x80");
+        assert_eq!(
+            decode_mutf8(b"\xed\xa0\xbd\xed\xb8\x80").unwrap(),
+            "\u{1f600}"
+        );
+        assert_eq!(decode_mutf8(b"a\xc0\x80").unwrap(), "a\0");
+
+        // NULs, 4 byte sequences, overlong encodings and unpaired surrogates.
+        for invalid in &[
+            &b"\0"[..],
+            b"\xf0\x9f\x98\x80",
+            b"\xc1\x81",
+            b"\xed\xa0\xbd",
+            b"\xc3",
+        ] {
+            assert!(decode_mutf8(invalid).is_err(), "{:x?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        // A byte array claiming to be 2 GiB long.
+        let buf = b"\x07\x00\x00\x7f\xff\xff\xff";
+        assert!(matches!(
+            read(buf, Limits::NETWORK),
+            Err(Error::SizeLimit(_))
+        ));
+        assert!(matches!(read(buf, Limits::FILE), Err(Error::IoError(_))));
+
+        let mut nested = Tag::List(vec![]);
+        for _ in 0..512 {
+            nested = Tag::List(vec![nested]);
+        }
+        let buf = write(&nested);
+        assert!(matches!(
+            read(&buf, Limits::NETWORK),
+            Err(Error::DepthLimit(512))
+        ));
+
+        let limits = Limits {
+            max_depth: 513,
+            ..Limits::NETWORK
+        };
+        assert_eq!(read(&buf, limits).unwrap(), nested);
+
+        assert!(matches!(
+            read(b"\x09\x00\x00\x01\xff\xff\xff\xff", Limits::NETWORK),
+            Err(Error::NegativeLength(-1))
+        ));
+        assert!(matches!(
+            read(b"\x0d\x00\x00", Limits::NETWORK),
+            Err(Error::InvalidTagId(13))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_tags() {
+        let mixed = Tag::List(vec![Tag::Byte(0), Tag::Int(0)]);
+        assert!(matches!(
+            write_unnamed(Vec::new(), &mixed),
+            Err(Error::MixedList {
+                expected: id::BYTE,
+                found: id::INT
+            })
+        ));
+
+        let long = Tag::String("a".repeat(65536));
+        assert!(matches!(
+            write_unnamed(Vec::new(), &long),
+            Err(Error::HumongousString(65536))
+        ));
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "PascalCase")]
+    struct Player {
+        name: String,
+        health: f32,
+        on_ground: bool,
+        pos: Vec<f64>,
+        uuid: IntArray,
+        xp_level: Option<i32>,
+        game_mode: GameMode,
+        inventory: Vec<Item>,
+        attributes: HashMap<String, f64>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "snake_case")]
+    enum GameMode {
+        Survival,
+        Creative,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Item {
+        id: String,
+        #[serde(rename = "Count")]
+        count: i8,
+        #[serde(rename = "Slot")]
+        slot: u8,
+    }
+
+    #[test]
+    fn test_serde() {
+        let player = Player {
+            name: "Steve".into(),
+            health: 20.0,
+            on_ground: true,
+            pos: vec![0.5, 64.0, -0.5],
+            uuid: vec![1, 2, 3, 4].into(),
+            xp_level: None,
+            game_mode: GameMode::Creative,
+            inventory: vec![Item {
+                id: "minecraft:stone".into(),
+                count: 64,
+                slot: 200,
+            }],
+            attributes: vec![("generic.max_health".to_owned(), 20.0)]
+                .into_iter()
+                .collect(),
+        };
+
+        let tag = to_tag(&player).unwrap();
+        let compound = tag.as_compound().unwrap();
+        assert_eq!(compound["OnGround"], Tag::Byte(1));
+        assert_eq!(compound["Uuid"], Tag::IntArray(vec![1, 2, 3, 4]));
+        assert_eq!(compound["GameMode"], Tag::String("creative".into()));
+        assert!(!compound.contains_key("XpLevel"));
+
+        let item = match &compound["Inventory"] {
+            Tag::List(items) => items[0].as_compound().unwrap(),
+            tag => panic!("expected a list, found {:?}", tag),
+        };
+        assert_eq!(item["Slot"], Tag::Short(200));
+
+        let mut buf = Vec::new();
+        to_writer(&mut buf, "", &player).unwrap();
+        assert_eq!(
+            from_reader::<_, Player>(&buf[..], Limits::FILE).unwrap(),
+            player
+        );
+    }
+
+    #[test]
+    fn test_serde_errors() {
+        assert!(matches!(to_tag(&None::<i32>), Err(Error::Unsupported(_))));
+        assert!(matches!(
+            to_tag(&vec![Some(1), None]),
+            Err(Error::Unsupported(_))
+        ));
+        assert!(matches!(to_tag(&u64::MAX), Err(Error::Unsupported(_))));
+
+        let mut map = HashMap::new();
+        map.insert(vec![1], 1);
+        assert!(matches!(to_tag(&map), Err(Error::KeyMustBeString)));
+
+        assert!(from_tag::<Item>(Tag::Int(1)).is_err());
+        assert!(from_tag::<u8>(Tag::Short(-1)).is_err());
+        assert!(from_tag::<GameMode>(Tag::String("hardcore".into())).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_roundtrip(tag in tag(), name: String) {
+            let mut buf = Vec::new();
+            write_named(&mut buf, &name, &tag).unwrap();
+
+            let (read_name, read_tag) = read_named(&buf[..], Limits::NETWORK).unwrap().unwrap();
+            prop_assert_eq!(read_name, name);
+            prop_assert_eq!(read_tag, tag);
+        }
+
+        #[test]
+        fn test_mutf8_roundtrip(s: String) {
+            prop_assert_eq!(decode_mutf8(&encode_mutf8(&s)).unwrap(), s);
+        }
+
+        #[test]
+        fn test_serde_roundtrip(tag in tag()) {
+            prop_assert_eq!(from_tag::<Tag>(to_tag(&tag).unwrap()).unwrap(), tag);
+        }
+    }
+}
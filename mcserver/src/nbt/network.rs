@@ -0,0 +1,43 @@
+use serde::{
+    de::{Deserialize, Deserializer},
+    ser::{self, Serialize, Serializer},
+};
+
+use super::{read_named, write_end, write_named, Limits, Tag};
+use crate::coder::de::read_raw;
+
+/// A tag embedded in a packet, which is named with an empty name, or just an
+/// end tag if it's missing.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct NetworkTag(pub Option<Tag>);
+
+impl NetworkTag {
+    pub(crate) fn encode(&self) -> super::error::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+
+        match &self.0 {
+            Some(tag) => write_named(&mut buf, "", tag)?,
+            None => write_end(&mut buf)?,
+        }
+
+        Ok(buf)
+    }
+}
+
+impl Serialize for NetworkTag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let buf = self.encode().map_err(ser::Error::custom)?;
+        serializer.serialize_bytes(&buf)
+    }
+}
+
+// Tags don't carry their length, so they're parsed as they're read to find
+// where they end.
+impl<'de> Deserialize<'de> for NetworkTag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        read_raw(deserializer, "an encoded NBT tag", |r| {
+            let tag = read_named(r, Limits::NETWORK)?;
+            Ok(NetworkTag(tag.map(|(_, tag)| tag)))
+        })
+    }
+}
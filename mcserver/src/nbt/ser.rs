@@ -0,0 +1,365 @@
+use std::convert::TryFrom;
+
+use serde::ser::{self, Serialize};
+
+use super::{
+    array::{BYTE_ARRAY_TOKEN, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN},
+    binary::list_element_id,
+    error::{Error, Result},
+    Compound, Tag,
+};
+
+/// Serializes values into tags.
+///
+/// Values that are skipped entirely, i.e. `None`, serialize to `Ok(None)` so
+/// that compounds can leave them out, like vanilla does for missing fields.
+pub struct Serializer;
+
+fn some(tag: Tag) -> Result<Option<Tag>> {
+    Ok(Some(tag))
+}
+
+fn element(value: Option<Tag>) -> Result<Tag> {
+    value.ok_or(Error::Unsupported("`None` in a list"))
+}
+
+/// Turns the list a typed array wrapper serialized its elements to back into
+/// that typed array.
+fn typed_array(name: &str, tag: Tag) -> Result<Tag> {
+    let list = match tag {
+        Tag::List(list) => list,
+        tag => return Ok(tag),
+    };
+
+    macro_rules! collect {
+        ($variant:ident, $array:ident) => {
+            list.into_iter()
+                .map(|tag| match tag {
+                    Tag::$variant(n) => Ok(n),
+                    tag => Err(Error::MixedList {
+                        expected: Tag::$variant(Default::default()).id(),
+                        found: tag.id(),
+                    }),
+                })
+                .collect::<Result<_>>()
+                .map(Tag::$array)
+        };
+    }
+
+    match name {
+        BYTE_ARRAY_TOKEN => collect!(Byte, ByteArray),
+        INT_ARRAY_TOKEN => collect!(Int, IntArray),
+        _ => collect!(Long, LongArray),
+    }
+}
+
+pub struct SerializeList {
+    list: Vec<Tag>,
+
+    /// For tuple variants, the variant the list gets wrapped in.
+    variant: Option<&'static str>,
+}
+
+impl SerializeList {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.list.push(element(value.serialize(Serializer)?)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>> {
+        list_element_id(&self.list)?;
+        wrap(self.variant, Tag::List(self.list))
+    }
+}
+
+pub struct SerializeCompound {
+    compound: Compound,
+    key: Option<String>,
+
+    /// For struct variants, the variant the compound gets wrapped in.
+    variant: Option<&'static str>,
+}
+
+impl SerializeCompound {
+    fn insert<T: ?Sized + Serialize>(&mut self, key: String, value: &T) -> Result<()> {
+        if let Some(tag) = value.serialize(Serializer)? {
+            self.compound.insert(key, tag);
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>> {
+        wrap(self.variant, Tag::Compound(self.compound))
+    }
+}
+
+/// Wraps the body of an enum variant in a compound with its name as the only
+/// key, or returns it as is for anything else.
+fn wrap(variant: Option<&'static str>, tag: Tag) -> Result<Option<Tag>> {
+    match variant {
+        Some(variant) => {
+            let mut compound = Compound::new();
+            compound.insert(variant.to_owned(), tag);
+            some(Tag::Compound(compound))
+        }
+        None => some(tag),
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeCompound;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        some(Tag::Byte(v as i8))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        some(Tag::Byte(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        some(Tag::Short(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        some(Tag::Int(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        some(Tag::Long(v))
+    }
+
+    // NBT only has signed integers, so unsigned ones take the next bigger type
+    // to make sure they always fit.
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        some(Tag::Short(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        some(Tag::Int(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        some(Tag::Long(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        match i64::try_from(v) {
+            Ok(v) => some(Tag::Long(v)),
+            Err(_) => Err(Error::Unsupported("a u64 larger than i64::MAX")),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        some(Tag::Float(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        some(Tag::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        some(Tag::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        some(Tag::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        some(Tag::ByteArray(v.iter().map(|&b| b as i8).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        some(Tag::Compound(Compound::new()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.serialize_str(variant)
+    }
+
+    // Newtype structs are transparent, except for the typed array wrappers,
+    // which would otherwise come out as lists.
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        match name {
+            BYTE_ARRAY_TOKEN | INT_ARRAY_TOKEN | LONG_ARRAY_TOKEN => {
+                typed_array(name, element(value.serialize(self)?)?).map(Some)
+            }
+            _ => value.serialize(self),
+        }
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        wrap(Some(variant), element(value.serialize(self)?)?)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeList {
+            list: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SerializeList {
+            list: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeCompound {
+            compound: Compound::new(),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeCompound {
+            compound: Compound::new(),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+macro_rules! ser_list {
+    ($($trait:ident: $method:ident),*) => {
+        $(
+        impl ser::$trait for SerializeList {
+            type Ok = Option<Tag>;
+            type Error = Error;
+
+            fn $method<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+                self.push(value)
+            }
+
+            fn end(self) -> Result<Self::Ok> {
+                self.finish()
+            }
+        }
+        )*
+    };
+}
+
+ser_list!(
+    SerializeSeq: serialize_element,
+    SerializeTuple: serialize_element,
+    SerializeTupleStruct: serialize_field,
+    SerializeTupleVariant: serialize_field
+);
+
+macro_rules! ser_struct {
+    ($($trait:ident),*) => {
+        $(
+        impl ser::$trait for SerializeCompound {
+            type Ok = Option<Tag>;
+            type Error = Error;
+
+            fn serialize_field<T: ?Sized + Serialize>(
+                &mut self,
+                key: &'static str,
+                value: &T,
+            ) -> Result<()> {
+                self.insert(key.to_owned(), value)
+            }
+
+            fn end(self) -> Result<Self::Ok> {
+                self.finish()
+            }
+        }
+        )*
+    };
+}
+
+ser_struct!(SerializeStruct, SerializeStructVariant);
+
+impl ser::SerializeMap for SerializeCompound {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(Serializer)? {
+            Some(Tag::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error::KeyMustBeString),
+        }
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().ok_or(Error::KeyMustBeString)?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
@@ -0,0 +1,713 @@
+//! SNBT (stringified NBT), the text form of tags used by commands like
+//! `/give` and `/data`.
+
+use std::{fmt::Write, str::FromStr};
+
+use super::{id, Compound, Tag};
+
+/// How deep lists and compounds may be nested, like in binary NBT.
+const MAX_DEPTH: usize = 512;
+
+/// How much of the input before an error is shown, like vanilla does.
+const CONTEXT_LENGTH: usize = 35;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum SnbtErrorKind {
+    /// Something else was found where the described token was expected.
+    Expected(&'static str),
+
+    /// The input continues after the root tag.
+    TrailingData,
+
+    UnterminatedString,
+    InvalidEscape(char),
+
+    /// An array type other than `B`, `I` or `L`.
+    InvalidArrayType(char),
+
+    /// A list or typed array held a tag of a different type than the ones
+    /// before it.
+    MixedList {
+        expected: u8,
+        found: u8,
+    },
+
+    DepthLimit,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SnbtError {
+    pub kind: SnbtErrorKind,
+
+    /// The column the error was found at, starting from 1.
+    pub column: usize,
+
+    /// The input leading up to the error.
+    pub context: String,
+}
+
+impl std::fmt::Display for SnbtErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use SnbtErrorKind::*;
+
+        match self {
+            Expected(what) => write!(f, "expected {}", what),
+            TrailingData => write!(f, "unexpected trailing data"),
+            UnterminatedString => write!(f, "unterminated string"),
+            InvalidEscape(c) => write!(f, "invalid escape sequence `\\{}`", c),
+            InvalidArrayType(c) => write!(f, "invalid array type `{}`", c),
+            MixedList { expected, found } => write!(
+                f,
+                "can't insert a tag with id {} into a list of tags with id {}",
+                found, expected
+            ),
+            DepthLimit => write!(f, "tags are nested deeper than {} levels", MAX_DEPTH),
+        }
+    }
+}
+
+impl std::fmt::Display for SnbtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at column {}: {}<--[HERE]",
+            self.kind, self.column, self.context
+        )
+    }
+}
+
+impl std::error::Error for SnbtError {}
+
+/// Parses a tag from its SNBT form.
+pub fn parse(s: &str) -> Result<Tag, SnbtError> {
+    let mut parser = Parser {
+        s,
+        pos: 0,
+        depth: 0,
+    };
+
+    let tag = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos != s.len() {
+        return Err(parser.error(SnbtErrorKind::TrailingData));
+    }
+
+    Ok(tag)
+}
+
+impl FromStr for Tag {
+    type Err = SnbtError;
+
+    fn from_str(s: &str) -> Result<Self, SnbtError> {
+        parse(s)
+    }
+}
+
+fn is_unquoted_char(c: char) -> bool {
+    matches!(c, '0'..='9' | 'A'..='Z' | 'a'..='z' | '_' | '-' | '.' | '+')
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, kind: SnbtErrorKind) -> SnbtError {
+        let before = &self.s[..self.pos];
+        let column = before.chars().count() + 1;
+        let skip = (column - 1).saturating_sub(CONTEXT_LENGTH);
+
+        let mut context: String = before.chars().skip(skip).collect();
+        if skip > 0 {
+            context.insert_str(0, "...");
+        }
+
+        SnbtError {
+            kind,
+            column,
+            context,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, c: char, what: &'static str) -> Result<(), SnbtError> {
+        self.skip_whitespace();
+
+        if self.peek() == Some(c) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(SnbtErrorKind::Expected(what)))
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), SnbtError> {
+        self.depth += 1;
+
+        if self.depth > MAX_DEPTH {
+            Err(self.error(SnbtErrorKind::DepthLimit))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read_unquoted(&mut self) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(is_unquoted_char) {
+            self.bump();
+        }
+
+        &self.s[start..self.pos]
+    }
+
+    fn read_quoted(&mut self) -> Result<String, SnbtError> {
+        let quote = self.bump();
+        let mut s = String::new();
+
+        loop {
+            match self.bump() {
+                Some('\\') => match self.bump() {
+                    Some(c) if c == '\\' || Some(c) == quote => s.push(c),
+                    Some(c) => {
+                        self.pos -= c.len_utf8();
+                        return Err(self.error(SnbtErrorKind::InvalidEscape(c)));
+                    }
+                    None => return Err(self.error(SnbtErrorKind::UnterminatedString)),
+                },
+                Some(c) if Some(c) == quote => return Ok(s),
+                Some(c) => s.push(c),
+                None => return Err(self.error(SnbtErrorKind::UnterminatedString)),
+            }
+        }
+    }
+
+    fn read_key(&mut self) -> Result<String, SnbtError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('"') | Some('\'') => self.read_quoted(),
+            _ => match self.read_unquoted() {
+                "" => Err(self.error(SnbtErrorKind::Expected("key"))),
+                key => Ok(key.to_owned()),
+            },
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Tag, SnbtError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('{') => self.parse_compound(),
+            Some('[') => self.parse_list_or_array(),
+            Some('"') | Some('\'') => self.read_quoted().map(Tag::String),
+            _ => match self.read_unquoted() {
+                "" => Err(self.error(SnbtErrorKind::Expected("value"))),
+                token => Ok(parse_unquoted(token)),
+            },
+        }
+    }
+
+    /// Parses the elements of a compound, list or array up to `close`, which
+    /// may be preceded by a trailing comma.
+    fn parse_elements(
+        &mut self,
+        close: char,
+        mut parse_element: impl FnMut(&mut Self) -> Result<(), SnbtError>,
+    ) -> Result<(), SnbtError> {
+        self.enter()?;
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(close) {
+                break;
+            }
+
+            parse_element(self)?;
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(c) if c == close => break,
+                _ => {
+                    return Err(self.error(SnbtErrorKind::Expected(match close {
+                        '}' => "`,` or `}`",
+                        _ => "`,` or `]`",
+                    })))
+                }
+            }
+        }
+
+        self.bump();
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn parse_compound(&mut self) -> Result<Tag, SnbtError> {
+        self.bump();
+
+        let mut compound = Compound::new();
+        self.parse_elements('}', |parser| {
+            let key = parser.read_key()?;
+            parser.expect(':', "`:`")?;
+            compound.insert(key, parser.parse_value()?);
+            Ok(())
+        })?;
+
+        Ok(Tag::Compound(compound))
+    }
+
+    fn parse_list_or_array(&mut self) -> Result<Tag, SnbtError> {
+        self.bump();
+
+        let mut rest = self.s[self.pos..].chars();
+        if let (Some(c), Some(';')) = (rest.next(), rest.next()) {
+            if c != '"' && c != '\'' {
+                return self.parse_array(c);
+            }
+        }
+
+        let mut list = Vec::new();
+        self.parse_elements(']', |parser| {
+            let start = parser.pos;
+            let tag = parser.parse_value()?;
+
+            if let Some(first) = list.first() {
+                let (expected, found) = (Tag::id(first), tag.id());
+                if expected != found {
+                    parser.pos = start;
+                    return Err(parser.error(SnbtErrorKind::MixedList { expected, found }));
+                }
+            }
+
+            list.push(tag);
+            Ok(())
+        })?;
+
+        Ok(Tag::List(list))
+    }
+
+    fn parse_array(&mut self, kind: char) -> Result<Tag, SnbtError> {
+        let expected = match kind {
+            'B' => id::BYTE,
+            'I' => id::INT,
+            'L' => id::LONG,
+            c => return Err(self.error(SnbtErrorKind::InvalidArrayType(c))),
+        };
+
+        self.pos += 2;
+
+        let mut elements = Vec::new();
+        self.parse_elements(']', |parser| {
+            let start = parser.pos;
+            let tag = parser.parse_value()?;
+
+            match tag.as_i64() {
+                Some(n) if tag.id() == expected => {
+                    elements.push(n);
+                    Ok(())
+                }
+                _ => {
+                    parser.pos = start;
+                    Err(parser.error(SnbtErrorKind::MixedList {
+                        expected,
+                        found: tag.id(),
+                    }))
+                }
+            }
+        })?;
+
+        // The elements were checked to be of the right type above.
+        Ok(match expected {
+            id::BYTE => Tag::ByteArray(elements.into_iter().map(|n| n as i8).collect()),
+            id::INT => Tag::IntArray(elements.into_iter().map(|n| n as i32).collect()),
+            _ => Tag::LongArray(elements),
+        })
+    }
+}
+
+/// Returns whether `s` is an integer without leading zeros, like `-12`.
+fn is_integer(s: &str) -> bool {
+    let digits = s.strip_prefix(&['-', '+'][..]).unwrap_or(s);
+
+    match digits.as_bytes() {
+        [b'0'] => true,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    }
+}
+
+/// Returns whether `s` is a decimal number like `1.`, `-.5` or `2e10`,
+/// optionally requiring it to have a decimal point.
+fn is_decimal(s: &str, require_point: bool) -> bool {
+    let s = s.strip_prefix(&['-', '+'][..]).unwrap_or(s);
+
+    let (mantissa, exponent) = match s.find(&['e', 'E'][..]) {
+        Some(e) => (&s[..e], Some(&s[e + 1..])),
+        None => (s, None),
+    };
+
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+
+    let valid_mantissa = match mantissa.find('.') {
+        Some(point) => {
+            let (int, frac) = (&mantissa[..point], &mantissa[point + 1..]);
+            all_digits(int) && all_digits(frac) && !(int.is_empty() && frac.is_empty())
+        }
+        None => !require_point && !mantissa.is_empty() && all_digits(mantissa),
+    };
+
+    let valid_exponent = match exponent {
+        Some(exponent) => {
+            let digits = exponent.strip_prefix(&['-', '+'][..]).unwrap_or(exponent);
+            !digits.is_empty() && all_digits(digits)
+        }
+        None => true,
+    };
+
+    valid_mantissa && valid_exponent
+}
+
+/// Parses an unquoted token, which is a string unless it's a number or a
+/// boolean.
+///
+/// Like in vanilla, integers too big for their type are strings too.
+fn parse_unquoted(token: &str) -> Tag {
+    let string = || Tag::String(token.to_owned());
+
+    if is_decimal(token, true) {
+        return token.parse().map_or_else(|_| string(), Tag::Double);
+    }
+
+    let (body, suffix) = token.split_at(token.len() - 1);
+
+    let tag = match suffix {
+        "b" | "B" if is_integer(body) => body.parse().ok().map(Tag::Byte),
+        "s" | "S" if is_integer(body) => body.parse().ok().map(Tag::Short),
+        "l" | "L" if is_integer(body) => body.parse().ok().map(Tag::Long),
+        "f" | "F" if is_decimal(body, false) => body.parse().ok().map(Tag::Float),
+        "d" | "D" if is_decimal(body, false) => body.parse().ok().map(Tag::Double),
+        _ if is_integer(token) => token.parse().ok().map(Tag::Int),
+        _ if token.eq_ignore_ascii_case("true") => Some(Tag::Byte(1)),
+        _ if token.eq_ignore_ascii_case("false") => Some(Tag::Byte(0)),
+        _ => None,
+    };
+
+    tag.unwrap_or_else(string)
+}
+
+/// Writes a string in quotes, using single quotes if that saves escaping
+/// double quotes.
+fn write_quoted(s: &str, out: &mut String) {
+    let quote = if s.contains('"') && !s.contains('\'') {
+        '\''
+    } else {
+        '"'
+    };
+
+    out.push(quote);
+    for c in s.chars() {
+        if c == quote || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push(quote);
+}
+
+fn write_key(key: &str, out: &mut String) {
+    if !key.is_empty() && key.chars().all(is_unquoted_char) {
+        out.push_str(key);
+    } else {
+        write_quoted(key, out);
+    }
+}
+
+/// Writes `tag` as SNBT, on a single line if `indent` is `None` or spread
+/// over multiple lines indented by `indent` spaces per level otherwise.
+fn write_snbt(tag: &Tag, out: &mut String, indent: Option<usize>, level: usize) {
+    let newline = |out: &mut String, level: usize| {
+        if let Some(indent) = indent {
+            out.push('\n');
+            out.extend(std::iter::repeat_n(' ', indent * level));
+        }
+    };
+
+    let separator = if indent.is_some() { ", " } else { "," };
+
+    macro_rules! write_array {
+        ($prefix:literal, $array:expr, $suffix:literal) => {{
+            out.push_str($prefix);
+            for (i, n) in $array.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(separator);
+                }
+                write!(out, concat!("{}", $suffix), n).unwrap();
+            }
+            out.push(']');
+        }};
+    }
+
+    match tag {
+        Tag::Byte(n) => write!(out, "{}b", n).unwrap(),
+        Tag::Short(n) => write!(out, "{}s", n).unwrap(),
+        Tag::Int(n) => write!(out, "{}", n).unwrap(),
+        Tag::Long(n) => write!(out, "{}L", n).unwrap(),
+
+        // Debug formatting always includes a decimal point or an exponent,
+        // and prints the shortest representation that parses back the same.
+        Tag::Float(n) => write!(out, "{:?}f", n).unwrap(),
+        Tag::Double(n) => write!(out, "{:?}d", n).unwrap(),
+
+        Tag::String(s) => write_quoted(s, out),
+        Tag::ByteArray(array) => write_array!("[B;", array, "b"),
+        Tag::IntArray(array) => write_array!("[I;", array, ""),
+        Tag::LongArray(array) => write_array!("[L;", array, "L"),
+
+        Tag::List(list) => {
+            // Only lists of lists and compounds are worth breaking up.
+            let nested = matches!(list.first(), Some(Tag::List(_)) | Some(Tag::Compound(_)));
+
+            out.push('[');
+            for (i, tag) in list.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                    if !nested && indent.is_some() {
+                        out.push(' ');
+                    }
+                }
+                if nested {
+                    newline(out, level + 1);
+                }
+                write_snbt(tag, out, indent, level + 1);
+            }
+            if nested {
+                newline(out, level);
+            }
+            out.push(']');
+        }
+
+        Tag::Compound(compound) => {
+            out.push('{');
+            for (i, (key, tag)) in compound.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, level + 1);
+                write_key(key, out);
+                out.push(':');
+                if indent.is_some() {
+                    out.push(' ');
+                }
+                write_snbt(tag, out, indent, level + 1);
+            }
+            if !compound.is_empty() {
+                newline(out, level);
+            }
+            out.push('}');
+        }
+    }
+}
+
+impl Tag {
+    /// Returns the tag as SNBT spread over multiple lines, for showing to
+    /// people.
+    pub fn to_pretty_snbt(&self) -> String {
+        let mut out = String::new();
+        write_snbt(self, &mut out, Some(4), 0);
+        out
+    }
+}
+
+/// Formats the tag as SNBT on a single line.
+///
+/// Non-finite floats are printed like vanilla does, which means they're read
+/// back as strings.
+impl std::fmt::Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut out = String::new();
+        write_snbt(self, &mut out, None, 0);
+        f.write_str(&out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::nbt::{read_named, tests::tag, write_named, Limits};
+
+    fn compound(entries: Vec<(&str, Tag)>) -> Tag {
+        Tag::Compound(
+            entries
+                .into_iter()
+                .map(|(key, tag)| (key.to_owned(), tag))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let tag = parse(
+            r#"{ Enchantments: [{id: "minecraft:sharpness", lvl: 5s}, {id: 'knockback', lvl: 2s},],
+                 "display name": 'say "hi"', Damage: 0, Unbreakable: true, weird.key-1: +.5e1f,
+                 bytes: [B; 1b, -2B], ints: [I;], longs: [L; 9000000000L], d: 1.5, also_d: 2D,
+                 s: 012, big: 300b }"#,
+        )
+        .unwrap();
+
+        let enchantment = |id: &str, lvl| {
+            compound(vec![
+                ("id", Tag::String(id.into())),
+                ("lvl", Tag::Short(lvl)),
+            ])
+        };
+
+        assert_eq!(
+            tag,
+            compound(vec![
+                (
+                    "Enchantments",
+                    Tag::List(vec![
+                        enchantment("minecraft:sharpness", 5),
+                        enchantment("knockback", 2)
+                    ])
+                ),
+                ("display name", Tag::String("say \"hi\"".into())),
+                ("Damage", Tag::Int(0)),
+                ("Unbreakable", Tag::Byte(1)),
+                ("weird.key-1", Tag::Float(5.0)),
+                ("bytes", Tag::ByteArray(vec![1, -2])),
+                ("ints", Tag::IntArray(vec![])),
+                ("longs", Tag::LongArray(vec![9_000_000_000])),
+                ("d", Tag::Double(1.5)),
+                ("also_d", Tag::Double(2.0)),
+                // Leading zeros and out of range numbers make for strings.
+                ("s", Tag::String("012".into())),
+                ("big", Tag::String("300b".into())),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |s: &str| {
+            let err = parse(s).unwrap_err();
+            (err.kind, err.column)
+        };
+
+        assert_eq!(error("{a:1,b}"), (SnbtErrorKind::Expected("`:`"), 7));
+        assert_eq!(error("{a:}"), (SnbtErrorKind::Expected("value"), 4));
+        assert_eq!(error("{:1}"), (SnbtErrorKind::Expected("key"), 2));
+        assert_eq!(
+            error("{a:1 b:2}"),
+            (SnbtErrorKind::Expected("`,` or `}`"), 6)
+        );
+        assert_eq!(
+            error("[1, 2b]"),
+            (
+                SnbtErrorKind::MixedList {
+                    expected: id::INT,
+                    found: id::BYTE
+                },
+                5
+            )
+        );
+        assert_eq!(
+            error("[B; 1b, 2]"),
+            (
+                SnbtErrorKind::MixedList {
+                    expected: id::BYTE,
+                    found: id::INT
+                },
+                9
+            )
+        );
+        assert_eq!(error("[X; 1]"), (SnbtErrorKind::InvalidArrayType('X'), 2));
+        assert_eq!(error("'abc"), (SnbtErrorKind::UnterminatedString, 5));
+        assert_eq!(error(r#""a\n""#), (SnbtErrorKind::InvalidEscape('n'), 4));
+        assert_eq!(error("{} {}"), (SnbtErrorKind::TrailingData, 4));
+        assert_eq!(error(&"[".repeat(513)), (SnbtErrorKind::DepthLimit, 514));
+
+        let err = parse(&format!("{{key: {}}}", "x".repeat(40) + "!")).unwrap_err();
+        assert_eq!(err.column, 47);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "expected `,` or `}}` at column 47: ...{}<--[HERE]",
+                &(" ".to_owned() + &"x".repeat(40))[6..]
+            )
+        );
+    }
+
+    #[test]
+    fn test_print() {
+        let tag = compound(vec![
+            ("id", Tag::String("minecraft:stone".into())),
+            ("Count", Tag::Byte(64)),
+            ("my key", Tag::String("it's \"quoted\"".into())),
+            ("Pos", Tag::List(vec![Tag::Double(0.5), Tag::Double(1e20)])),
+            ("Items", Tag::List(vec![compound(vec![])])),
+            ("Heights", Tag::LongArray(vec![1, -1])),
+            ("f", Tag::Float(1.0)),
+        ]);
+
+        assert_eq!(
+            tag.to_string(),
+            r#"{Count:64b,Heights:[L;1L,-1L],Items:[{}],Pos:[0.5d,1e20d],f:1.0f,id:"minecraft:stone","my key":"it's \"quoted\""}"#
+        );
+
+        assert_eq!(
+            tag.to_pretty_snbt(),
+            r#"{
+    Count: 64b,
+    Heights: [L;1L, -1L],
+    Items: [
+        {}
+    ],
+    Pos: [0.5d, 1e20d],
+    f: 1.0f,
+    id: "minecraft:stone",
+    "my key": "it's \"quoted\""
+}"#
+        );
+
+        assert_eq!(
+            Tag::String("say \"hi\"".into()).to_string(),
+            r#"'say "hi"'"#
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_roundtrip(tag in tag().prop_filter("non-finite floats", |tag| !tag.to_string().contains("inf"))) {
+            prop_assert_eq!(&parse(&tag.to_string()).unwrap(), &tag);
+            prop_assert_eq!(&parse(&tag.to_pretty_snbt()).unwrap(), &tag);
+
+            // And through the binary form.
+            let mut buf = Vec::new();
+            write_named(&mut buf, "", &parse(&tag.to_string()).unwrap()).unwrap();
+            let (_, read) = read_named(&buf[..], Limits::FILE).unwrap().unwrap();
+            prop_assert_eq!(read.to_string(), tag.to_string());
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+
+use serde::{
+    de::{self, Deserialize, Deserializer, Visitor},
+    ser::{Serialize, Serializer},
+};
+
+use super::{
+    array::{BYTE_ARRAY_TOKEN, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN},
+    id,
+};
+
+/// Newtype struct name our own deserializer recognizes, so that it can hand
+/// over typed arrays as such instead of as plain sequences.
+pub(crate) const TAG_TOKEN: &str = "$mcserver::nbt::Tag";
+
+pub type Compound = BTreeMap<String, Tag>;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+
+    /// A list of tags, which must all be of the same type.
+    List(Vec<Tag>),
+
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    pub fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => id::BYTE,
+            Tag::Short(_) => id::SHORT,
+            Tag::Int(_) => id::INT,
+            Tag::Long(_) => id::LONG,
+            Tag::Float(_) => id::FLOAT,
+            Tag::Double(_) => id::DOUBLE,
+            Tag::ByteArray(_) => id::BYTE_ARRAY,
+            Tag::String(_) => id::STRING,
+            Tag::List(_) => id::LIST,
+            Tag::Compound(_) => id::COMPOUND,
+            Tag::IntArray(_) => id::INT_ARRAY,
+            Tag::LongArray(_) => id::LONG_ARRAY,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Tag::Compound(compound) => Some(compound),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound_mut(&mut self) -> Option<&mut Compound> {
+        match self {
+            Tag::Compound(compound) => Some(compound),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of any integer tag, widened to an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(n) => Some(n.into()),
+            Tag::Short(n) => Some(n.into()),
+            Tag::Int(n) => Some(n.into()),
+            Tag::Long(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Tag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Tag::Byte(n) => n.serialize(serializer),
+            Tag::Short(n) => n.serialize(serializer),
+            Tag::Int(n) => n.serialize(serializer),
+            Tag::Long(n) => n.serialize(serializer),
+            Tag::Float(n) => n.serialize(serializer),
+            Tag::Double(n) => n.serialize(serializer),
+            Tag::ByteArray(array) => serializer.serialize_newtype_struct(BYTE_ARRAY_TOKEN, array),
+            Tag::String(s) => s.serialize(serializer),
+            Tag::List(list) => list.serialize(serializer),
+            Tag::Compound(compound) => compound.serialize(serializer),
+            Tag::IntArray(array) => serializer.serialize_newtype_struct(INT_ARRAY_TOKEN, array),
+            Tag::LongArray(array) => serializer.serialize_newtype_struct(LONG_ARRAY_TOKEN, array),
+        }
+    }
+}
+
+/// Names of the variants typed arrays are handed over as by our own
+/// deserializer, see `TAG_TOKEN`.
+pub(crate) const ARRAY_VARIANTS: &[&str] = &["ByteArray", "IntArray", "LongArray"];
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TagVisitor;
+
+        impl<'de> Visitor<'de> for TagVisitor {
+            type Value = Tag;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "an NBT tag")
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<Tag, E> {
+                Ok(Tag::Byte(value as i8))
+            }
+
+            fn visit_i8<E: de::Error>(self, value: i8) -> Result<Tag, E> {
+                Ok(Tag::Byte(value))
+            }
+
+            fn visit_i16<E: de::Error>(self, value: i16) -> Result<Tag, E> {
+                Ok(Tag::Short(value))
+            }
+
+            fn visit_i32<E: de::Error>(self, value: i32) -> Result<Tag, E> {
+                Ok(Tag::Int(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Tag, E> {
+                Ok(Tag::Long(value))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Tag, E> {
+                use std::convert::TryFrom;
+
+                i64::try_from(value)
+                    .map(Tag::Long)
+                    .map_err(|_| E::custom("integer too large for NBT"))
+            }
+
+            fn visit_f32<E: de::Error>(self, value: f32) -> Result<Tag, E> {
+                Ok(Tag::Float(value))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Tag, E> {
+                Ok(Tag::Double(value))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Tag, E> {
+                Ok(Tag::String(value.to_owned()))
+            }
+
+            fn visit_string<E: de::Error>(self, value: String) -> Result<Tag, E> {
+                Ok(Tag::String(value))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Tag, A::Error> {
+                let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1024));
+                while let Some(tag) = seq.next_element()? {
+                    list.push(tag);
+                }
+
+                Ok(Tag::List(list))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Tag, A::Error> {
+                let mut compound = Compound::new();
+                while let Some((key, tag)) = map.next_entry()? {
+                    compound.insert(key, tag);
+                }
+
+                Ok(Tag::Compound(compound))
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Tag, D::Error> {
+                deserializer.deserialize_any(self)
+            }
+
+            fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<Tag, A::Error> {
+                use de::VariantAccess;
+
+                let (variant, array): (String, _) = data.variant()?;
+
+                match &*variant {
+                    "ByteArray" => array.newtype_variant().map(Tag::ByteArray),
+                    "IntArray" => array.newtype_variant().map(Tag::IntArray),
+                    "LongArray" => array.newtype_variant().map(Tag::LongArray),
+                    variant => Err(de::Error::unknown_variant(variant, ARRAY_VARIANTS)),
+                }
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(TAG_TOKEN, TagVisitor)
+    }
+}
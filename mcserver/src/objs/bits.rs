@@ -0,0 +1,474 @@
+use std::iter::FromIterator;
+
+use serde::{
+    de::{self, Deserialize, Deserializer, SeqAccess, Visitor},
+    ser::{Serialize, SerializeTuple, Serializer},
+};
+
+use super::VarInt;
+
+/// How many longs are allocated up front when reading a length-prefixed
+/// array, so that a bogus length can't exhaust memory by itself.
+const MAX_PREALLOCATED: usize = 1024;
+
+/// Writes longs prefixed by their count, as the protocol sends arrays.
+fn serialize_longs<S: Serializer>(serializer: S, longs: &[u64]) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_tuple(longs.len() + 1)?;
+    seq.serialize_element(&VarInt(longs.len() as i32))?;
+    for &long in longs {
+        seq.serialize_element(&(long as i64))?;
+    }
+    seq.end()
+}
+
+/// A growable set of bits, sent like Java's `BitSet::toLongArray`, i.e. with
+/// bit `i` in long `i / 64`, least significant bit first, and no trailing
+/// zero longs.
+#[derive(Clone, Default, Eq, PartialEq, Hash, Debug)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_longs(longs: &[i64]) -> Self {
+        let mut set = Self {
+            words: longs.iter().map(|&long| long as u64).collect(),
+        };
+        set.trim();
+        set
+    }
+
+    pub fn to_longs(&self) -> Vec<i64> {
+        self.words.iter().map(|&word| word as i64).collect()
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    pub fn set(&mut self, index: usize, value: bool) {
+        let word = index / 64;
+
+        if value {
+            if word >= self.words.len() {
+                self.words.resize(word + 1, 0);
+            }
+            self.words[word] |= 1 << (index % 64);
+        } else if word < self.words.len() {
+            self.words[word] &= !(1 << (index % 64));
+            self.trim();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns the indices of the set bits, in increasing order.
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| i * 64 + bit)
+        })
+    }
+
+    fn trim(&mut self) {
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for index in iter {
+            set.set(index, true);
+        }
+        set
+    }
+}
+
+impl Serialize for BitSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_longs(serializer, &self.words)
+    }
+}
+
+impl<'de> Deserialize<'de> for BitSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BitSetVisitor;
+
+        impl<'de> Visitor<'de> for BitSetVisitor {
+            type Value = BitSet;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a length-prefixed array of longs")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let missing = || de::Error::custom("bit set ended early");
+
+                let VarInt(len) = seq.next_element()?.ok_or_else(missing)?;
+                if len < 0 {
+                    return Err(de::Error::custom(format!(
+                        "negative bit set length {}",
+                        len
+                    )));
+                }
+
+                let mut longs = Vec::with_capacity((len as usize).min(MAX_PREALLOCATED));
+                for _ in 0..len {
+                    longs.push(seq.next_element::<i64>()?.ok_or_else(missing)?);
+                }
+
+                Ok(BitSet::from_longs(&longs))
+            }
+        }
+
+        // The actual length is only known after reading the prefix.
+        deserializer.deserialize_tuple(usize::MAX, BitSetVisitor)
+    }
+}
+
+/// How entries are laid out in the longs of a `PackedArray`.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub enum Packing {
+    /// Entries follow each other directly, so one may start in a long and
+    /// end in the next. Used up to 1.15.
+    #[default]
+    Spanning,
+    /// Every long holds as many whole entries as fit, leaving the rest of
+    /// its bits unused. Used since 1.16.
+    Padded,
+}
+
+impl Packing {
+    pub fn for_protocol(protocol_version: i32) -> Self {
+        if protocol_version >= 735 {
+            Packing::Padded
+        } else {
+            Packing::Spanning
+        }
+    }
+
+    /// Returns how many longs `len` entries of `bits` bits take up.
+    pub fn longs_needed(self, len: usize, bits: u8) -> usize {
+        let bits = usize::from(bits);
+
+        match self {
+            Packing::Spanning => (len * bits).div_ceil(64),
+            Packing::Padded => len.div_ceil(64 / bits),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum PackedArrayError {
+    /// Entries must take between 1 and 64 bits.
+    InvalidBits(u8),
+    ValueTooLarge {
+        value: u64,
+        bits: u8,
+    },
+    WrongLength {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl std::fmt::Display for PackedArrayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PackedArrayError::InvalidBits(bits) => {
+                write!(f, "can't pack entries of {} bits", bits)
+            }
+            PackedArrayError::ValueTooLarge { value, bits } => {
+                write!(f, "{} doesn't fit in {} bits", value, bits)
+            }
+            PackedArrayError::WrongLength { expected, found } => {
+                write!(f, "expected {} longs, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PackedArrayError {}
+
+/// A fixed amount of unsigned entries of any width up to 64 bits, packed
+/// into longs as chunk sections and heightmaps are sent.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PackedArray {
+    len: usize,
+    bits: u8,
+    packing: Packing,
+    longs: Vec<u64>,
+}
+
+impl PackedArray {
+    /// Creates an array of `len` zeroes.
+    pub fn new(len: usize, bits: u8, packing: Packing) -> Result<Self, PackedArrayError> {
+        if bits == 0 || bits > 64 {
+            return Err(PackedArrayError::InvalidBits(bits));
+        }
+
+        Ok(Self {
+            len,
+            bits,
+            packing,
+            longs: vec![0; packing.longs_needed(len, bits)],
+        })
+    }
+
+    /// Wraps longs as they were received, whose entry count and width are
+    /// known from elsewhere.
+    pub fn from_longs(
+        len: usize,
+        bits: u8,
+        packing: Packing,
+        longs: Vec<i64>,
+    ) -> Result<Self, PackedArrayError> {
+        let mut array = Self::new(len, bits, packing)?;
+
+        if longs.len() != array.longs.len() {
+            return Err(PackedArrayError::WrongLength {
+                expected: array.longs.len(),
+                found: longs.len(),
+            });
+        }
+
+        array.longs = longs.into_iter().map(|long| long as u64).collect();
+        Ok(array)
+    }
+
+    pub fn from_values<I>(
+        len: usize,
+        bits: u8,
+        packing: Packing,
+        values: I,
+    ) -> Result<Self, PackedArrayError>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        let mut array = Self::new(len, bits, packing)?;
+        for (index, value) in values.into_iter().take(len).enumerate() {
+            array.set(index, value)?;
+        }
+        Ok(array)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    pub fn packing(&self) -> Packing {
+        self.packing
+    }
+
+    pub fn as_longs(&self) -> &[u64] {
+        &self.longs
+    }
+
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.bits)
+    }
+
+    /// Returns the long an entry starts in and the bit it starts at.
+    fn locate(&self, index: usize) -> (usize, u32) {
+        let bits = usize::from(self.bits);
+
+        match self.packing {
+            Packing::Spanning => {
+                let bit = index * bits;
+                (bit / 64, (bit % 64) as u32)
+            }
+            Packing::Padded => {
+                let per_long = 64 / bits;
+                (index / per_long, ((index % per_long) * bits) as u32)
+            }
+        }
+    }
+
+    /// Returns the entry at `index`, which must be less than the length.
+    pub fn get(&self, index: usize) -> u64 {
+        assert!(index < self.len, "index {} out of {}", index, self.len);
+
+        let (long, offset) = self.locate(index);
+        let mut value = self.longs[long] >> offset;
+
+        // Only spanning entries can continue in the next long.
+        if offset + u32::from(self.bits) > 64 {
+            value |= self.longs[long + 1] << (64 - offset);
+        }
+
+        value & self.mask()
+    }
+
+    /// Sets the entry at `index`, which must be less than the length.
+    pub fn set(&mut self, index: usize, value: u64) -> Result<(), PackedArrayError> {
+        assert!(index < self.len, "index {} out of {}", index, self.len);
+
+        let mask = self.mask();
+        if value & !mask != 0 {
+            return Err(PackedArrayError::ValueTooLarge {
+                value,
+                bits: self.bits,
+            });
+        }
+
+        let (long, offset) = self.locate(index);
+        self.longs[long] = (self.longs[long] & !(mask << offset)) | (value << offset);
+
+        if offset + u32::from(self.bits) > 64 {
+            let spilled = 64 - offset;
+            self.longs[long + 1] = (self.longs[long + 1] & !(mask >> spilled)) | (value >> spilled);
+        }
+
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.len).map(move |index| self.get(index))
+    }
+
+    /// Copies the entries into an array of another width and packing, as
+    /// when a palette grows or a different protocol version is targeted.
+    pub fn repack(&self, bits: u8, packing: Packing) -> Result<Self, PackedArrayError> {
+        Self::from_values(self.len, bits, packing, self.iter())
+    }
+}
+
+impl Serialize for PackedArray {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_longs(serializer, &self.longs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::coder::{de, ser};
+
+    fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut buf = Vec::new();
+        value
+            .serialize(&mut ser::Serializer::new(&mut buf))
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_bit_set() {
+        let mut set: BitSet = vec![0, 3, 64].into_iter().collect();
+        assert!(set.get(3) && set.get(64) && !set.get(1) && !set.get(1000));
+        assert_eq!(set.to_longs(), [0b1001, 1]);
+        assert_eq!(set.iter_ones().collect::<Vec<_>>(), [0, 3, 64]);
+
+        set.set(64, false);
+        assert_eq!(set.to_longs(), [0b1001]);
+        assert_eq!(set, BitSet::from_longs(&[0b1001, 0, 0]));
+
+        assert_eq!(encode(&set), [0x01, 0, 0, 0, 0, 0, 0, 0, 0b1001]);
+        assert_eq!(encode(&BitSet::new()), [0x00]);
+
+        let decoded = BitSet::deserialize(&mut de::Deserializer::new(&encode(&set)[..]));
+        assert_eq!(decoded.unwrap(), set);
+        assert!(BitSet::deserialize(&mut de::Deserializer::new(&[0x02, 0, 0][..])).is_err());
+    }
+
+    #[test]
+    fn test_packing() {
+        // 12 entries of 5 bits fit in a padded long, 12.8 in a spanning one.
+        assert_eq!(Packing::Spanning.longs_needed(4096, 5), 320);
+        assert_eq!(Packing::Padded.longs_needed(4096, 5), 342);
+        assert_eq!(Packing::Spanning.longs_needed(256, 9), 36);
+        assert_eq!(Packing::Padded.longs_needed(256, 9), 37);
+
+        let values = (1..=13).collect::<Vec<u64>>();
+        let spanning = PackedArray::from_values(13, 5, Packing::Spanning, values.clone()).unwrap();
+        let padded = PackedArray::from_values(13, 5, Packing::Padded, values).unwrap();
+
+        // The 13th entry starts at bit 60 and spills into the next long.
+        assert_eq!(spanning.as_longs()[1], 13 >> 4);
+        assert_eq!(padded.as_longs()[1], 13);
+        assert_eq!(spanning.as_longs()[0] >> 60, 13 & 0b1111);
+        assert_eq!(padded.as_longs()[0] >> 60, 0);
+
+        assert_eq!(padded.repack(5, Packing::Spanning).unwrap(), spanning);
+        assert_eq!(encode(&padded)[0], 0x02);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            PackedArray::new(1, 0, Packing::Padded),
+            Err(PackedArrayError::InvalidBits(0))
+        );
+        assert_eq!(
+            PackedArray::new(1, 65, Packing::Padded),
+            Err(PackedArrayError::InvalidBits(65))
+        );
+
+        let mut array = PackedArray::new(4, 4, Packing::Spanning).unwrap();
+        assert_eq!(
+            array.set(0, 16),
+            Err(PackedArrayError::ValueTooLarge { value: 16, bits: 4 })
+        );
+        assert_eq!(
+            PackedArray::from_longs(64, 4, Packing::Spanning, vec![0; 3]),
+            Err(PackedArrayError::WrongLength {
+                expected: 4,
+                found: 3
+            })
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_roundtrip(bits in 1..=64u8, seed in prop::collection::vec(any::<u64>(), 0..100), padded: bool) {
+            let packing = if padded { Packing::Padded } else { Packing::Spanning };
+            let values = seed.iter().map(|value| value >> (64 - bits)).collect::<Vec<_>>();
+
+            let array = PackedArray::from_values(values.len(), bits, packing, values.clone()).unwrap();
+            prop_assert_eq!(array.iter().collect::<Vec<_>>(), values.clone());
+
+            let longs = array.as_longs().iter().map(|&long| long as i64).collect();
+            prop_assert_eq!(&PackedArray::from_longs(values.len(), bits, packing, longs).unwrap(), &array);
+
+            // Widening keeps the values, whatever the packing.
+            let wide = array.repack(64, Packing::Spanning).unwrap();
+            prop_assert_eq!(wide.as_longs(), &values[..]);
+        }
+
+        #[test]
+        fn test_bit_set_roundtrip(indices in prop::collection::btree_set(0..1000usize, 0..50)) {
+            let set: BitSet = indices.iter().cloned().collect();
+            prop_assert_eq!(set.iter_ones().collect::<Vec<_>>(), indices.into_iter().collect::<Vec<_>>());
+            prop_assert_eq!(BitSet::from_longs(&set.to_longs()), set);
+        }
+    }
+}
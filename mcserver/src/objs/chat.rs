@@ -0,0 +1,1075 @@
+use std::convert::TryFrom;
+
+use serde::{
+    de::{self, Deserialize, Deserializer, Visitor},
+    ser::{Serialize, Serializer},
+};
+use serde_json::{Map, Value};
+
+use super::Identifier;
+use crate::nbt::{self, Compound, Tag};
+
+/// A text component, as used by chat messages, disconnect reasons, titles,
+/// MOTDs and item names.
+///
+/// On the wire components are sent as a string holding their JSON form.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Chat {
+    pub content: Content,
+    pub style: Style,
+
+    /// Components appended after this one, which inherit its style.
+    pub extra: Vec<Chat>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Content {
+    Text(String),
+
+    /// A translation key, whose `%s` placeholders are filled by `with`.
+    Translate {
+        key: String,
+        with: Vec<Chat>,
+    },
+
+    /// An entity's score in an objective, with `value` overriding it.
+    Score {
+        name: String,
+        objective: String,
+        value: Option<String>,
+    },
+
+    Selector(String),
+
+    /// The key bound to a control, like `key.jump`.
+    Keybind(String),
+}
+
+/// Formatting for a component, where `None` means it's inherited from the
+/// parent component.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Style {
+    pub color: Option<Color>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underlined: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub obfuscated: Option<bool>,
+    pub font: Option<Identifier>,
+
+    /// Text inserted into the chat box when the component is shift-clicked.
+    pub insertion: Option<String>,
+
+    pub click_event: Option<ClickEvent>,
+    pub hover_event: Option<HoverEvent>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Color {
+    Black,
+    DarkBlue,
+    DarkGreen,
+    DarkAqua,
+    DarkRed,
+    DarkPurple,
+    Gold,
+    Gray,
+    DarkGray,
+    Blue,
+    Green,
+    Aqua,
+    Red,
+    LightPurple,
+    Yellow,
+    White,
+
+    /// An arbitrary `0xRRGGBB` color, supported since 1.16.
+    Hex(u32),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ClickEvent {
+    OpenUrl(String),
+    RunCommand(String),
+    SuggestCommand(String),
+    ChangePage(u32),
+    CopyToClipboard(String),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum HoverEvent {
+    ShowText(Box<Chat>),
+
+    /// An item, as stringified NBT.
+    ShowItem(String),
+
+    /// An entity, as stringified NBT.
+    ShowEntity(String),
+}
+
+#[derive(Debug)]
+pub enum ChatError {
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl std::fmt::Display for ChatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ChatError::Json(err) => write!(f, "invalid chat JSON: {}", err),
+            ChatError::Invalid(s) => write!(f, "invalid chat component: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for ChatError {}
+
+impl From<serde_json::Error> for ChatError {
+    fn from(value: serde_json::Error) -> Self {
+        ChatError::Json(value)
+    }
+}
+
+/// Colors in the order of their legacy `§` codes, `0` through `f`.
+const LEGACY_COLORS: [(Color, &str); 16] = [
+    (Color::Black, "black"),
+    (Color::DarkBlue, "dark_blue"),
+    (Color::DarkGreen, "dark_green"),
+    (Color::DarkAqua, "dark_aqua"),
+    (Color::DarkRed, "dark_red"),
+    (Color::DarkPurple, "dark_purple"),
+    (Color::Gold, "gold"),
+    (Color::Gray, "gray"),
+    (Color::DarkGray, "dark_gray"),
+    (Color::Blue, "blue"),
+    (Color::Green, "green"),
+    (Color::Aqua, "aqua"),
+    (Color::Red, "red"),
+    (Color::LightPurple, "light_purple"),
+    (Color::Yellow, "yellow"),
+    (Color::White, "white"),
+];
+
+impl Color {
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(hex) = name.strip_prefix('#') {
+            return match u32::from_str_radix(hex, 16) {
+                Ok(rgb) if hex.len() == 6 => Some(Color::Hex(rgb)),
+                _ => None,
+            };
+        }
+
+        LEGACY_COLORS
+            .iter()
+            .find(|&&(_, n)| n == name)
+            .map(|&(color, _)| color)
+    }
+
+    /// Returns the name used for the color in JSON, like `dark_red` or
+    /// `#ff8000`.
+    pub fn name(self) -> String {
+        match self {
+            Color::Hex(rgb) => format!("#{:06x}", rgb),
+            color => LEGACY_COLORS
+                .iter()
+                .find(|&&(c, _)| c == color)
+                .map(|&(_, name)| name.to_owned())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn from_legacy_code(code: char) -> Option<Self> {
+        let index = code.to_digit(16)?;
+        Some(LEGACY_COLORS[index as usize].0)
+    }
+
+    /// Returns the `§` code for the color, if it's one of the 16 named ones.
+    pub fn legacy_code(self) -> Option<char> {
+        let index = LEGACY_COLORS.iter().position(|&(c, _)| c == self)?;
+        std::char::from_digit(index as u32, 16)
+    }
+}
+
+impl Style {
+    pub fn is_empty(&self) -> bool {
+        *self == Style::default()
+    }
+}
+
+impl Chat {
+    pub fn new(content: Content) -> Self {
+        Self {
+            content,
+            style: Style::default(),
+            extra: Vec::new(),
+        }
+    }
+
+    pub fn text<S: Into<String>>(text: S) -> Self {
+        Self::new(Content::Text(text.into()))
+    }
+
+    pub fn translate<S: Into<String>>(key: S, with: Vec<Chat>) -> Self {
+        Self::new(Content::Translate {
+            key: key.into(),
+            with,
+        })
+    }
+
+    pub fn score<S: Into<String>, T: Into<String>>(name: S, objective: T) -> Self {
+        Self::new(Content::Score {
+            name: name.into(),
+            objective: objective.into(),
+            value: None,
+        })
+    }
+
+    pub fn selector<S: Into<String>>(selector: S) -> Self {
+        Self::new(Content::Selector(selector.into()))
+    }
+
+    pub fn keybind<S: Into<String>>(key: S) -> Self {
+        Self::new(Content::Keybind(key.into()))
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.style.color = Some(color);
+        self
+    }
+
+    pub fn bold(mut self, bold: bool) -> Self {
+        self.style.bold = Some(bold);
+        self
+    }
+
+    pub fn italic(mut self, italic: bool) -> Self {
+        self.style.italic = Some(italic);
+        self
+    }
+
+    pub fn underlined(mut self, underlined: bool) -> Self {
+        self.style.underlined = Some(underlined);
+        self
+    }
+
+    pub fn strikethrough(mut self, strikethrough: bool) -> Self {
+        self.style.strikethrough = Some(strikethrough);
+        self
+    }
+
+    pub fn obfuscated(mut self, obfuscated: bool) -> Self {
+        self.style.obfuscated = Some(obfuscated);
+        self
+    }
+
+    pub fn font(mut self, font: Identifier) -> Self {
+        self.style.font = Some(font);
+        self
+    }
+
+    pub fn insertion<S: Into<String>>(mut self, insertion: S) -> Self {
+        self.style.insertion = Some(insertion.into());
+        self
+    }
+
+    pub fn on_click(mut self, event: ClickEvent) -> Self {
+        self.style.click_event = Some(event);
+        self
+    }
+
+    pub fn on_hover(mut self, event: HoverEvent) -> Self {
+        self.style.hover_event = Some(event);
+        self
+    }
+
+    pub fn with_extra(mut self, extra: Chat) -> Self {
+        self.extra.push(extra);
+        self
+    }
+
+    /// Parses text using legacy `§` formatting codes into components.
+    ///
+    /// Like in vanilla, a color code also resets any formatting before it and
+    /// unknown codes are dropped.
+    pub fn from_legacy(s: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut style = Style::default();
+        let mut text = String::new();
+
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '§' {
+                text.push(c);
+                continue;
+            }
+
+            let code = match chars.next() {
+                Some(code) => code.to_ascii_lowercase(),
+                None => break,
+            };
+
+            if !text.is_empty() {
+                let mut part = Chat::text(std::mem::take(&mut text));
+                part.style = style.clone();
+                parts.push(part);
+            }
+
+            match code {
+                'k' => style.obfuscated = Some(true),
+                'l' => style.bold = Some(true),
+                'm' => style.strikethrough = Some(true),
+                'n' => style.underlined = Some(true),
+                'o' => style.italic = Some(true),
+                'r' => style = Style::default(),
+                code => {
+                    if let Some(color) = Color::from_legacy_code(code) {
+                        style = Style::default();
+                        style.color = Some(color);
+                    }
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            let mut part = Chat::text(text);
+            part.style = style;
+            parts.push(part);
+        }
+
+        if parts.len() == 1 {
+            return parts.remove(0);
+        }
+
+        Self {
+            extra: parts,
+            ..Chat::text("")
+        }
+    }
+
+    /// Renders the component without any formatting, for logs.
+    ///
+    /// Since the server has no translations, translation keys are used as
+    /// their own format strings, which is also what vanilla falls back to.
+    pub fn to_plain_text(&self) -> String {
+        let mut out = String::new();
+        self.write_plain_text(&mut out);
+        out
+    }
+
+    fn write_plain_text(&self, out: &mut String) {
+        match &self.content {
+            Content::Text(text) => out.push_str(text),
+            Content::Translate { key, with } => write_translation(key, with, out),
+            Content::Score { value, .. } => out.push_str(value.as_deref().unwrap_or("")),
+            Content::Selector(selector) => out.push_str(selector),
+            Content::Keybind(key) => out.push_str(key),
+        }
+
+        for extra in &self.extra {
+            extra.write_plain_text(out);
+        }
+    }
+
+    /// Parses a component from its JSON form.
+    pub fn from_json_str(s: &str) -> Result<Self, ChatError> {
+        Self::from_json(&serde_json::from_str(s)?)
+    }
+
+    pub fn to_json_string(&self) -> String {
+        self.to_json().to_string()
+    }
+
+    /// Converts a JSON value into a component, accepting the shorthands
+    /// vanilla does: plain strings, numbers and booleans are text, and arrays
+    /// are their first element with the rest as its `extra`.
+    pub fn from_json(value: &Value) -> Result<Self, ChatError> {
+        match value {
+            Value::String(s) => Ok(Chat::text(s.clone())),
+            Value::Number(n) => Ok(Chat::text(n.to_string())),
+            Value::Bool(b) => Ok(Chat::text(b.to_string())),
+
+            Value::Array(values) => {
+                let mut values = values.iter().map(Chat::from_json);
+                let mut chat = values
+                    .next()
+                    .ok_or_else(|| ChatError::Invalid("empty array".into()))??;
+                for extra in values {
+                    chat.extra.push(extra?);
+                }
+                Ok(chat)
+            }
+
+            Value::Object(object) => Self::from_json_object(object),
+
+            Value::Null => Err(ChatError::Invalid("null".into())),
+        }
+    }
+
+    fn from_json_object(object: &Map<String, Value>) -> Result<Self, ChatError> {
+        let content = if let Some(text) = object.get("text") {
+            Content::Text(match text {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+        } else if let Some(key) = object.get("translate") {
+            Content::Translate {
+                key: json_str(key, "translate")?.to_owned(),
+                with: match object.get("with") {
+                    Some(with) => json_components(with, "with")?,
+                    None => Vec::new(),
+                },
+            }
+        } else if let Some(score) = object.get("score") {
+            let score = score
+                .as_object()
+                .ok_or_else(|| ChatError::Invalid("`score` must be an object".into()))?;
+
+            let field = |name| -> Result<&Value, ChatError> {
+                score
+                    .get(name)
+                    .ok_or_else(|| ChatError::Invalid(format!("`score` is missing `{}`", name)))
+            };
+
+            Content::Score {
+                name: json_str(field("name")?, "name")?.to_owned(),
+                objective: json_str(field("objective")?, "objective")?.to_owned(),
+                value: match score.get("value") {
+                    Some(value) => Some(json_str(value, "value")?.to_owned()),
+                    None => None,
+                },
+            }
+        } else if let Some(selector) = object.get("selector") {
+            Content::Selector(json_str(selector, "selector")?.to_owned())
+        } else if let Some(key) = object.get("keybind") {
+            Content::Keybind(json_str(key, "keybind")?.to_owned())
+        } else {
+            return Err(ChatError::Invalid("component has no content".into()));
+        };
+
+        let flag = |name| -> Result<Option<bool>, ChatError> {
+            match object.get(name) {
+                None => Ok(None),
+                Some(Value::Bool(b)) => Ok(Some(*b)),
+                Some(_) => Err(ChatError::Invalid(format!("`{}` must be a boolean", name))),
+            }
+        };
+
+        let style =
+            Style {
+                color: match object.get("color") {
+                    Some(color) => {
+                        let name = json_str(color, "color")?;
+                        Some(Color::from_name(name).ok_or_else(|| {
+                            ChatError::Invalid(format!("unknown color {:?}", name))
+                        })?)
+                    }
+                    None => None,
+                },
+                bold: flag("bold")?,
+                italic: flag("italic")?,
+                underlined: flag("underlined")?,
+                strikethrough: flag("strikethrough")?,
+                obfuscated: flag("obfuscated")?,
+                font: match object.get("font") {
+                    Some(font) => Some(
+                        Identifier::parse(json_str(font, "font")?)
+                            .map_err(|err| ChatError::Invalid(err.to_string()))?,
+                    ),
+                    None => None,
+                },
+                insertion: match object.get("insertion") {
+                    Some(insertion) => Some(json_str(insertion, "insertion")?.to_owned()),
+                    None => None,
+                },
+                click_event: match object.get("clickEvent") {
+                    Some(event) => Some(ClickEvent::from_json(event)?),
+                    None => None,
+                },
+                hover_event: match object.get("hoverEvent") {
+                    Some(event) => Some(HoverEvent::from_json(event)?),
+                    None => None,
+                },
+            };
+
+        let extra = match object.get("extra") {
+            Some(extra) => json_components(extra, "extra")?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            content,
+            style,
+            extra,
+        })
+    }
+
+    /// Converts the component into its JSON form, which is just a string for
+    /// unstyled text without children.
+    pub fn to_json(&self) -> Value {
+        if let Content::Text(text) = &self.content {
+            if self.style.is_empty() && self.extra.is_empty() {
+                return Value::String(text.clone());
+            }
+        }
+
+        let mut object = Map::new();
+
+        match &self.content {
+            Content::Text(text) => {
+                object.insert("text".into(), text.clone().into());
+            }
+
+            Content::Translate { key, with } => {
+                object.insert("translate".into(), key.clone().into());
+                if !with.is_empty() {
+                    let with = with.iter().map(Chat::to_json).collect();
+                    object.insert("with".into(), Value::Array(with));
+                }
+            }
+
+            Content::Score {
+                name,
+                objective,
+                value,
+            } => {
+                let mut score = Map::new();
+                score.insert("name".into(), name.clone().into());
+                score.insert("objective".into(), objective.clone().into());
+                if let Some(value) = value {
+                    score.insert("value".into(), value.clone().into());
+                }
+                object.insert("score".into(), Value::Object(score));
+            }
+
+            Content::Selector(selector) => {
+                object.insert("selector".into(), selector.clone().into());
+            }
+
+            Content::Keybind(key) => {
+                object.insert("keybind".into(), key.clone().into());
+            }
+        }
+
+        let style = &self.style;
+
+        if let Some(color) = style.color {
+            object.insert("color".into(), color.name().into());
+        }
+
+        for &(name, flag) in &[
+            ("bold", style.bold),
+            ("italic", style.italic),
+            ("underlined", style.underlined),
+            ("strikethrough", style.strikethrough),
+            ("obfuscated", style.obfuscated),
+        ] {
+            if let Some(flag) = flag {
+                object.insert(name.into(), flag.into());
+            }
+        }
+
+        if let Some(font) = &style.font {
+            object.insert("font".into(), font.as_str().into());
+        }
+
+        if let Some(insertion) = &style.insertion {
+            object.insert("insertion".into(), insertion.clone().into());
+        }
+
+        if let Some(event) = &style.click_event {
+            object.insert("clickEvent".into(), event.to_json());
+        }
+
+        if let Some(event) = &style.hover_event {
+            object.insert("hoverEvent".into(), event.to_json());
+        }
+
+        if !self.extra.is_empty() {
+            let extra = self.extra.iter().map(Chat::to_json).collect();
+            object.insert("extra".into(), Value::Array(extra));
+        }
+
+        Value::Object(object)
+    }
+}
+
+impl ClickEvent {
+    fn from_json(value: &Value) -> Result<Self, ChatError> {
+        let (action, value) = json_event(value, "clickEvent", "value")?;
+        let value = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+
+        Ok(match action {
+            "open_url" => ClickEvent::OpenUrl(value),
+            "run_command" => ClickEvent::RunCommand(value),
+            "suggest_command" => ClickEvent::SuggestCommand(value),
+            "change_page" => ClickEvent::ChangePage(
+                value
+                    .parse()
+                    .map_err(|_| ChatError::Invalid(format!("invalid page {:?}", value)))?,
+            ),
+            "copy_to_clipboard" => ClickEvent::CopyToClipboard(value),
+            action => {
+                return Err(ChatError::Invalid(format!(
+                    "unknown click action {:?}",
+                    action
+                )))
+            }
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let (action, value) = match self {
+            ClickEvent::OpenUrl(url) => ("open_url", url.clone()),
+            ClickEvent::RunCommand(command) => ("run_command", command.clone()),
+            ClickEvent::SuggestCommand(command) => ("suggest_command", command.clone()),
+            ClickEvent::ChangePage(page) => ("change_page", page.to_string()),
+            ClickEvent::CopyToClipboard(text) => ("copy_to_clipboard", text.clone()),
+        };
+
+        let mut object = Map::new();
+        object.insert("action".into(), action.into());
+        object.insert("value".into(), value.into());
+        Value::Object(object)
+    }
+}
+
+impl HoverEvent {
+    // Since 1.16 hover events carry `contents` instead of `value`, but
+    // `value` is still understood, so that's what we send to support both.
+    // Items and entities in `contents` are objects, which we turn into the
+    // stringified NBT `value` holds.
+    fn from_json(event: &Value) -> Result<Self, ChatError> {
+        if let Ok((action, value)) = json_event(event, "hoverEvent", "value") {
+            return Ok(match action {
+                "show_text" => HoverEvent::ShowText(Box::new(Chat::from_json(value)?)),
+                "show_item" => HoverEvent::ShowItem(json_str(value, "value")?.to_owned()),
+                "show_entity" => HoverEvent::ShowEntity(json_str(value, "value")?.to_owned()),
+                action => return Err(unknown_hover_action(action)),
+            });
+        }
+
+        let (action, contents) = json_event(event, "hoverEvent", "contents")?;
+        Ok(match action {
+            "show_text" => HoverEvent::ShowText(Box::new(Chat::from_json(contents)?)),
+            "show_item" => HoverEvent::ShowItem(item_contents(contents)?.to_string()),
+            "show_entity" => HoverEvent::ShowEntity(entity_contents(contents)?.to_string()),
+            action => return Err(unknown_hover_action(action)),
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let (action, value) = match self {
+            HoverEvent::ShowText(text) => ("show_text", text.to_json()),
+            HoverEvent::ShowItem(item) => ("show_item", item.clone().into()),
+            HoverEvent::ShowEntity(entity) => ("show_entity", entity.clone().into()),
+        };
+
+        let mut object = Map::new();
+        object.insert("action".into(), action.into());
+        object.insert("value".into(), value);
+        Value::Object(object)
+    }
+}
+
+fn unknown_hover_action(action: &str) -> ChatError {
+    ChatError::Invalid(format!("unknown hover action {:?}", action))
+}
+
+/// Converts the `contents` of a `show_item` event, either an item id or an
+/// object with `id` and optionally `count` and `tag`, to an item's NBT.
+fn item_contents(contents: &Value) -> Result<Tag, ChatError> {
+    if let Value::String(id) = contents {
+        return Ok(Tag::Compound(
+            vec![
+                ("id".to_owned(), Tag::String(id.clone())),
+                ("Count".to_owned(), Tag::Byte(1)),
+            ]
+            .into_iter()
+            .collect(),
+        ));
+    }
+
+    let mut item = Compound::new();
+    let id = contents
+        .get("id")
+        .ok_or_else(|| ChatError::Invalid("`show_item` needs an `id`".into()))?;
+    item.insert("id".into(), Tag::String(json_str(id, "id")?.to_owned()));
+
+    let count = match contents.get("count") {
+        Some(count) => count
+            .as_i64()
+            .and_then(|count| i8::try_from(count).ok())
+            .ok_or_else(|| ChatError::Invalid(format!("invalid item count {}", count)))?,
+        None => 1,
+    };
+    item.insert("Count".into(), Tag::Byte(count));
+
+    if let Some(tag) = contents.get("tag") {
+        let tag = nbt::snbt::parse(json_str(tag, "tag")?)
+            .map_err(|err| ChatError::Invalid(format!("invalid item tag: {}", err)))?;
+        if tag.as_compound().is_none() {
+            return Err(ChatError::Invalid("`tag` must be a compound".into()));
+        }
+        item.insert("tag".into(), tag);
+    }
+
+    Ok(Tag::Compound(item))
+}
+
+/// Converts the `contents` of a `show_entity` event, an object with `type`,
+/// `id` and optionally `name`, to the NBT older versions expect.
+fn entity_contents(contents: &Value) -> Result<Tag, ChatError> {
+    let missing = || ChatError::Invalid("`show_entity` needs `type` and `id`".into());
+
+    let mut entity = Compound::new();
+    let kind = contents.get("type").ok_or_else(missing)?;
+    entity.insert(
+        "type".into(),
+        Tag::String(json_str(kind, "type")?.to_owned()),
+    );
+
+    // The UUID is a string, or since 1.16 also four ints.
+    let id = match contents.get("id").ok_or_else(missing)? {
+        Value::String(id) => id.clone(),
+        Value::Array(ints) if ints.len() == 4 => {
+            let mut uuid = 0u128;
+            for int in ints {
+                let int = int
+                    .as_i64()
+                    .and_then(|int| i32::try_from(int).ok())
+                    .ok_or_else(|| ChatError::Invalid(format!("invalid UUID part {}", int)))?;
+                uuid = uuid << 32 | u128::from(int as u32);
+            }
+
+            let hex = format!("{:032x}", uuid);
+            format!(
+                "{}-{}-{}-{}-{}",
+                &hex[..8],
+                &hex[8..12],
+                &hex[12..16],
+                &hex[16..20],
+                &hex[20..]
+            )
+        }
+        _ => return Err(ChatError::Invalid("`id` must be a UUID".into())),
+    };
+    entity.insert("id".into(), Tag::String(id));
+
+    if let Some(name) = contents.get("name") {
+        let name = Chat::from_json(name)?.to_json_string();
+        entity.insert("name".into(), Tag::String(name));
+    }
+
+    Ok(Tag::Compound(entity))
+}
+
+fn json_str<'a>(value: &'a Value, name: &str) -> Result<&'a str, ChatError> {
+    value
+        .as_str()
+        .ok_or_else(|| ChatError::Invalid(format!("`{}` must be a string", name)))
+}
+
+fn json_components(value: &Value, name: &str) -> Result<Vec<Chat>, ChatError> {
+    value
+        .as_array()
+        .ok_or_else(|| ChatError::Invalid(format!("`{}` must be an array", name)))?
+        .iter()
+        .map(Chat::from_json)
+        .collect()
+}
+
+fn json_event<'a>(
+    value: &'a Value,
+    name: &str,
+    value_key: &str,
+) -> Result<(&'a str, &'a Value), ChatError> {
+    let missing = || ChatError::Invalid(format!("`{}` needs `action` and `{}`", name, value_key));
+
+    let action = value.get("action").ok_or_else(missing)?;
+    let value = value.get(value_key).ok_or_else(missing)?;
+
+    Ok((json_str(action, "action")?, value))
+}
+
+/// Fills a format string's `%s`, `%1$s` and `%%` placeholders with `args`.
+fn write_translation(format: &str, args: &[Chat], out: &mut String) {
+    let mut next_arg = 0;
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        let mut digits = String::new();
+        while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+            digits.push(d);
+            chars.next();
+        }
+
+        let index = if digits.is_empty() {
+            None
+        } else if chars.peek() == Some(&'$') {
+            chars.next();
+            digits.parse::<usize>().ok().and_then(|n| n.checked_sub(1))
+        } else {
+            out.push('%');
+            out.push_str(&digits);
+            continue;
+        };
+
+        match chars.next() {
+            Some('%') if digits.is_empty() => out.push('%'),
+            Some('s') => {
+                let index = index.unwrap_or_else(|| {
+                    next_arg += 1;
+                    next_arg - 1
+                });
+
+                if let Some(arg) = args.get(index) {
+                    arg.write_plain_text(out);
+                }
+            }
+            other => {
+                out.push('%');
+                out.push_str(&digits);
+                out.extend(other);
+            }
+        }
+    }
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Content::Text(String::new())
+    }
+}
+
+impl From<&str> for Chat {
+    fn from(text: &str) -> Self {
+        Chat::text(text)
+    }
+}
+
+impl From<String> for Chat {
+    fn from(text: String) -> Self {
+        Chat::text(text)
+    }
+}
+
+impl std::fmt::Display for Chat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.to_plain_text())
+    }
+}
+
+impl Serialize for Chat {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_json_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Chat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ChatVisitor;
+
+        impl<'de> Visitor<'de> for ChatVisitor {
+            type Value = Chat;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a string holding a JSON text component")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Chat::from_json_str(value).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_string(ChatVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate::coder::{de::Deserializer, ser::Serializer};
+
+    #[test]
+    fn test_to_json() {
+        let chat = Chat::translate(
+            "chat.type.text",
+            vec![
+                Chat::selector("@p").on_click(ClickEvent::SuggestCommand("/msg Steve ".into())),
+                Chat::text("hi").color(Color::Hex(0xff8000)),
+            ],
+        )
+        .bold(true)
+        .on_hover(HoverEvent::ShowText(Box::new(Chat::keybind("key.jump"))))
+        .with_extra(Chat::score("Steve", "kills").color(Color::DarkRed));
+
+        let expected = json!({
+            "translate": "chat.type.text",
+            "with": [
+                {
+                    "selector": "@p",
+                    "clickEvent": { "action": "suggest_command", "value": "/msg Steve " },
+                },
+                { "text": "hi", "color": "#ff8000" },
+            ],
+            "bold": true,
+            "hoverEvent": { "action": "show_text", "value": { "keybind": "key.jump" } },
+            "extra": [
+                { "score": { "name": "Steve", "objective": "kills" }, "color": "dark_red" },
+            ],
+        });
+
+        assert_eq!(chat.to_json(), expected);
+        assert_eq!(Chat::from_json(&expected).unwrap(), chat);
+        assert_eq!(Chat::text("plain").to_json(), json!("plain"));
+    }
+
+    #[test]
+    fn test_from_json_shorthands() {
+        assert_eq!(Chat::from_json_str("\"hi\"").unwrap(), Chat::text("hi"));
+        assert_eq!(Chat::from_json_str("42").unwrap(), Chat::text("42"));
+        assert_eq!(
+            Chat::from_json_str(r#"["a", {"text": "b", "italic": false}]"#).unwrap(),
+            Chat::text("a").with_extra(Chat::text("b").italic(false))
+        );
+        assert_eq!(
+            Chat::from_json_str(
+                r#"{"text": "", "hoverEvent": {"action": "show_text", "contents": "x"}}"#
+            )
+            .unwrap(),
+            Chat::text("").on_hover(HoverEvent::ShowText(Box::new(Chat::text("x"))))
+        );
+
+        for invalid in &[
+            "[]",
+            "null",
+            "{}",
+            r#"{"text": "", "color": "pink"}"#,
+            r#"{"text": "", "bold": "yes"}"#,
+            r#"{"text": "", "clickEvent": {"action": "explode", "value": ""}}"#,
+            r#"{"translate": "x", "with": {}}"#,
+            "{",
+        ] {
+            assert!(Chat::from_json_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_hover_contents() {
+        let hover = |event: Value| {
+            let chat = Chat::from_json(&json!({ "text": "", "hoverEvent": event }))?;
+            Ok::<_, ChatError>(chat.style.hover_event.unwrap())
+        };
+
+        assert_eq!(
+            hover(json!({
+                "action": "show_item",
+                "contents": { "id": "minecraft:stone", "count": 3, "tag": "{Damage: 2}" },
+            }))
+            .unwrap(),
+            HoverEvent::ShowItem(r#"{Count:3b,id:"minecraft:stone",tag:{Damage:2}}"#.into())
+        );
+        assert_eq!(
+            hover(json!({ "action": "show_item", "contents": "minecraft:stone" })).unwrap(),
+            HoverEvent::ShowItem(r#"{Count:1b,id:"minecraft:stone"}"#.into())
+        );
+        assert_eq!(
+            hover(json!({
+                "action": "show_entity",
+                "contents": {
+                    "type": "minecraft:pig",
+                    "id": [1, 2, 3, -1],
+                    "name": "Bob",
+                },
+            }))
+            .unwrap(),
+            HoverEvent::ShowEntity(
+                r#"{id:"00000001-0000-0002-0000-0003ffffffff",name:'"Bob"',type:"minecraft:pig"}"#
+                    .into()
+            )
+        );
+
+        for invalid in &[
+            json!({ "action": "show_item", "contents": { "count": 1 } }),
+            json!({ "action": "show_item", "contents": { "id": "minecraft:stone", "count": 300 } }),
+            json!({ "action": "show_item", "contents": { "id": "minecraft:stone", "tag": "[]" } }),
+            json!({ "action": "show_entity", "contents": { "type": "minecraft:pig", "id": [1] } }),
+            json!({ "action": "show_entity", "contents": { "type": "minecraft:pig" } }),
+        ] {
+            assert!(hover(invalid.clone()).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_from_legacy() {
+        assert_eq!(Chat::from_legacy("hello"), Chat::text("hello"));
+
+        assert_eq!(
+            Chat::from_legacy("§lA §cB§oC§rD§"),
+            Chat::text("")
+                .with_extra(Chat::text("A ").bold(true))
+                .with_extra(Chat::text("B").color(Color::Red))
+                .with_extra(Chat::text("C").color(Color::Red).italic(true))
+                .with_extra(Chat::text("D"))
+        );
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let chat = Chat::translate(
+            "%s says %%%2$s%% to %1$s",
+            vec![Chat::text("Alex"), Chat::text("hi").bold(true)],
+        )
+        .with_extra(Chat::text("!"));
+
+        assert_eq!(chat.to_plain_text(), "Alex says %hi% to Alex!");
+        assert_eq!(chat.to_string(), chat.to_plain_text());
+        assert_eq!(Chat::translate("100%", vec![]).to_plain_text(), "100%");
+    }
+
+    #[test]
+    fn test_coder() {
+        let chat = Chat::text("hi").color(Color::Gold);
+
+        let mut buf = Vec::new();
+        chat.serialize(&mut Serializer::new(&mut buf)).unwrap();
+
+        let mut expected = Vec::new();
+        r#"{"color":"gold","text":"hi"}"#.serialize(&mut Serializer::new(&mut expected)).unwrap();
+        assert_eq!(buf, expected);
+
+        let deserialized = Chat::deserialize(&mut Deserializer::new(&buf[..])).unwrap();
+        assert_eq!(deserialized, chat);
+    }
+
+    proptest! {
+        #[test]
+        fn test_legacy_plain_text(s in "([a-z ]|§[0-9a-fk-or]){0,32}") {
+            let stripped: String = s.split('§').enumerate().map(|(i, part)| {
+                if i == 0 { part } else { &part[1..] }
+            }).collect();
+
+            prop_assert_eq!(Chat::from_legacy(&s).to_plain_text(), stripped);
+        }
+
+        #[test]
+        fn test_json_roundtrip(text in ".*", code in 0..16u32, bold: Option<bool>, rgb in 0..0x100_0000u32) {
+            let color = Color::from_legacy_code(std::char::from_digit(code, 16).unwrap()).unwrap();
+            let mut chat = Chat::text(text.clone()).color(color).with_extra(Chat::text(text).color(Color::Hex(rgb)));
+            chat.style.bold = bold;
+
+            prop_assert_eq!(Chat::from_json_str(&chat.to_json_string()).unwrap(), chat);
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Position, WorldHeight};
+
+/// Blocks along each side of a chunk, and of a chunk section.
+pub const CHUNK_SIZE: i32 = 16;
+
+/// Chunks along each side of a region file.
+pub const REGION_SIZE: i32 = 32;
+
+/// The coordinates of a chunk column, i.e. block coordinates divided by 16.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Debug)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+/// The coordinates of a 16x16x16 chunk section, i.e. block coordinates
+/// divided by 16.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Debug)]
+pub struct SectionPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// The coordinates of a region file, i.e. chunk coordinates divided by 32.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Debug)]
+pub struct RegionPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+/// A side of a block, in the order the protocol numbers them.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum BlockFace {
+    Down,
+    Up,
+    North,
+    South,
+    West,
+    East,
+}
+
+/// An axis-aligned box of blocks, including both corners.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct Aabb {
+    min: Position,
+    max: Position,
+}
+
+impl Position {
+    pub fn chunk(self) -> ChunkPos {
+        ChunkPos {
+            x: self.x >> 4,
+            z: self.z >> 4,
+        }
+    }
+
+    pub fn section(self) -> SectionPos {
+        SectionPos {
+            x: self.x >> 4,
+            y: self.y >> 4,
+            z: self.z >> 4,
+        }
+    }
+
+    /// Returns the index of the block within its section, in the `y`, `z`,
+    /// `x` order sections store their blocks in.
+    pub fn section_index(self) -> usize {
+        let x = (self.x & lsb!(4)) as usize;
+        let y = (self.y & lsb!(4)) as usize;
+        let z = (self.z & lsb!(4)) as usize;
+
+        (y << 8) | (z << 4) | x
+    }
+
+    /// Returns the position next to this one on the given side, or `None`
+    /// if it doesn't fit in an `i32`.
+    pub fn offset(self, face: BlockFace) -> Option<Self> {
+        let (dx, dy, dz) = face.offset();
+
+        Some(Self {
+            x: self.x.checked_add(dx)?,
+            y: self.y.checked_add(dy)?,
+            z: self.z.checked_add(dz)?,
+        })
+    }
+
+    /// Returns the positions sharing a face with this one, which are six
+    /// unless it's at the very edge of the `i32` range.
+    pub fn neighbors(self) -> impl Iterator<Item = Position> {
+        BlockFace::ALL
+            .iter()
+            .filter_map(move |&face| self.offset(face))
+    }
+
+    pub fn manhattan_distance(self, other: Self) -> u64 {
+        let dx = (i64::from(self.x) - i64::from(other.x)).unsigned_abs();
+        let dy = (i64::from(self.y) - i64::from(other.y)).unsigned_abs();
+        let dz = (i64::from(self.z) - i64::from(other.z)).unsigned_abs();
+
+        dx + dy + dz
+    }
+
+    pub fn distance_squared(self, other: Self) -> i64 {
+        let dx = i64::from(self.x) - i64::from(other.x);
+        let dy = i64::from(self.y) - i64::from(other.y);
+        let dz = i64::from(self.z) - i64::from(other.z);
+
+        dx * dx + dy * dy + dz * dz
+    }
+
+    pub fn distance(self, other: Self) -> f64 {
+        (self.distance_squared(other) as f64).sqrt()
+    }
+}
+
+impl ChunkPos {
+    pub fn region(self) -> RegionPos {
+        RegionPos {
+            x: self.x >> 5,
+            z: self.z >> 5,
+        }
+    }
+
+    /// Returns the index of the chunk within its region file's header.
+    pub fn region_index(self) -> usize {
+        let x = (self.x & lsb!(5)) as usize;
+        let z = (self.z & lsb!(5)) as usize;
+
+        (z << 5) | x
+    }
+
+    pub fn section(self, y: i32) -> SectionPos {
+        SectionPos {
+            x: self.x,
+            y,
+            z: self.z,
+        }
+    }
+
+    /// Returns the block at the given coordinates relative to the chunk's
+    /// north-west corner, or `None` if it doesn't fit in an `i32`.
+    pub fn block(self, x: i32, y: i32, z: i32) -> Option<Position> {
+        Some(Position {
+            x: self.x.checked_mul(CHUNK_SIZE)?.checked_add(x)?,
+            y,
+            z: self.z.checked_mul(CHUNK_SIZE)?.checked_add(z)?,
+        })
+    }
+
+    pub fn contains(self, position: Position) -> bool {
+        position.chunk() == self
+    }
+}
+
+impl SectionPos {
+    pub fn chunk(self) -> ChunkPos {
+        ChunkPos {
+            x: self.x,
+            z: self.z,
+        }
+    }
+
+    /// Returns where the section is within its chunk column, counting from
+    /// the bottom of the world, or `None` if it lies outside of `height`.
+    pub fn index_in(self, height: WorldHeight) -> Option<usize> {
+        let bottom = height.min_y() >> 4;
+        let sections = height.height() / CHUNK_SIZE;
+
+        match self.y - bottom {
+            index if (0..sections).contains(&index) => Some(index as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns the block at `index` within the section, see
+    /// `Position::section_index`, or `None` if it doesn't fit in an `i32`.
+    pub fn block(self, index: usize) -> Option<Position> {
+        let index = index as i32;
+        let corner = |n: i32, offset: i32| n.checked_mul(CHUNK_SIZE)?.checked_add(offset);
+
+        Some(Position {
+            x: corner(self.x, index & lsb!(4))?,
+            y: corner(self.y, (index >> 8) & lsb!(4))?,
+            z: corner(self.z, (index >> 4) & lsb!(4))?,
+        })
+    }
+
+    /// Returns every block in the section as an `Aabb`, or `None` if they
+    /// don't fit in an `i32`.
+    pub fn bounds(self) -> Option<Aabb> {
+        Some(Aabb::new(self.block(0)?, self.block(0xfff)?))
+    }
+
+    pub fn contains(self, position: Position) -> bool {
+        position.section() == self
+    }
+}
+
+impl RegionPos {
+    /// Returns the chunk at the given coordinates relative to the region's
+    /// north-west corner, or `None` if it doesn't fit in an `i32`.
+    pub fn chunk(self, x: i32, z: i32) -> Option<ChunkPos> {
+        Some(ChunkPos {
+            x: self.x.checked_mul(REGION_SIZE)?.checked_add(x)?,
+            z: self.z.checked_mul(REGION_SIZE)?.checked_add(z)?,
+        })
+    }
+
+    /// Returns the name vanilla gives this region's file.
+    pub fn file_name(self) -> String {
+        format!("r.{}.{}.mca", self.x, self.z)
+    }
+
+    pub fn contains(self, chunk: ChunkPos) -> bool {
+        chunk.region() == self
+    }
+}
+
+impl BlockFace {
+    pub const ALL: [BlockFace; 6] = [
+        BlockFace::Down,
+        BlockFace::Up,
+        BlockFace::North,
+        BlockFace::South,
+        BlockFace::West,
+        BlockFace::East,
+    ];
+
+    /// Returns the direction the face points in as `(dx, dy, dz)`.
+    pub fn offset(self) -> (i32, i32, i32) {
+        match self {
+            BlockFace::Down => (0, -1, 0),
+            BlockFace::Up => (0, 1, 0),
+            BlockFace::North => (0, 0, -1),
+            BlockFace::South => (0, 0, 1),
+            BlockFace::West => (-1, 0, 0),
+            BlockFace::East => (1, 0, 0),
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            BlockFace::Down => BlockFace::Up,
+            BlockFace::Up => BlockFace::Down,
+            BlockFace::North => BlockFace::South,
+            BlockFace::South => BlockFace::North,
+            BlockFace::West => BlockFace::East,
+            BlockFace::East => BlockFace::West,
+        }
+    }
+}
+
+impl Aabb {
+    /// Creates the box spanning two opposite corners, given in any order.
+    pub fn new(a: Position, b: Position) -> Self {
+        Self {
+            min: Position {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+                z: a.z.min(b.z),
+            },
+            max: Position {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+                z: a.z.max(b.z),
+            },
+        }
+    }
+
+    pub fn min(self) -> Position {
+        self.min
+    }
+
+    pub fn max(self) -> Position {
+        self.max
+    }
+
+    pub fn contains(self, position: Position) -> bool {
+        (self.min.x..=self.max.x).contains(&position.x)
+            && (self.min.y..=self.max.y).contains(&position.y)
+            && (self.min.z..=self.max.z).contains(&position.z)
+    }
+
+    pub fn contains_box(self, other: Self) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+            && self.min.z <= other.max.z
+            && other.min.z <= self.max.z
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    prop_compose! {
+        fn position()(x in Position::MIN_XZ..=Position::MAX_XZ, y in Position::MIN_Y..=Position::MAX_Y, z in Position::MIN_XZ..=Position::MAX_XZ) -> Position {
+            Position { x, y, z }
+        }
+    }
+
+    fn face() -> impl Strategy<Value = BlockFace> {
+        (0..BlockFace::ALL.len()).prop_map(|i| BlockFace::ALL[i])
+    }
+
+    #[test]
+    fn test_known_positions() {
+        let position = Position {
+            x: -1,
+            y: -64,
+            z: 33,
+        };
+
+        assert_eq!(position.chunk(), ChunkPos { x: -1, z: 2 });
+        assert_eq!(position.section(), SectionPos { x: -1, y: -4, z: 2 });
+        assert_eq!(position.section_index(), 0x01f);
+        assert_eq!(position.chunk().region(), RegionPos { x: -1, z: 0 });
+        assert_eq!(position.chunk().region().file_name(), "r.-1.0.mca");
+        assert_eq!(position.chunk().region_index(), 2 * 32 + 31);
+        assert_eq!(position.section().index_in(WorldHeight::OVERWORLD), Some(0));
+        assert_eq!(position.section().index_in(WorldHeight::LEGACY), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let edge = Position {
+            x: i32::MAX,
+            y: 0,
+            z: i32::MIN,
+        };
+        assert_eq!(edge.offset(BlockFace::East), None);
+        assert_eq!(edge.offset(BlockFace::North), None);
+        assert_eq!(edge.neighbors().count(), 4);
+
+        let far = ChunkPos {
+            x: i32::MAX >> 4,
+            z: 0,
+        };
+        assert_eq!(far.block(15, 0, 0).map(|p| p.x), Some(i32::MAX));
+        assert_eq!(ChunkPos { x: far.x + 1, z: 0 }.block(0, 0, 0), None);
+        assert_eq!(far.section(0).bounds().map(|b| b.max().x), Some(i32::MAX));
+        assert_eq!(far.section(i32::MIN).block(0), None);
+        assert_eq!(RegionPos { x: 0, z: i32::MIN }.chunk(0, 0), None);
+    }
+
+    #[test]
+    fn test_face_discriminants() {
+        assert_eq!(crate::coder::duplicate_discriminant::<BlockFace>(), None);
+    }
+
+    proptest! {
+        #[test]
+        fn test_section_roundtrip(position in position()) {
+            let section = position.section();
+            prop_assert_eq!(section.block(position.section_index()), Some(position));
+            prop_assert!(section.contains(position));
+            prop_assert!(section.bounds().unwrap().contains(position));
+            prop_assert_eq!(section.chunk(), position.chunk());
+        }
+
+        #[test]
+        fn test_chunk_roundtrip(position in position()) {
+            let chunk = position.chunk();
+            let (x, z) = (position.x & lsb!(4), position.z & lsb!(4));
+            prop_assert_eq!(chunk.block(x, position.y, z), Some(position));
+            prop_assert!(chunk.contains(position));
+            prop_assert_eq!(chunk.section(position.y >> 4), position.section());
+        }
+
+        #[test]
+        fn test_region_roundtrip(x: i32, z: i32) {
+            let chunk = ChunkPos { x: x >> 5, z: z >> 5 };
+            let region = chunk.region();
+            let index = chunk.region_index() as i32;
+            prop_assert_eq!(region.chunk(index & lsb!(5), index >> 5), Some(chunk));
+            prop_assert!(region.contains(chunk));
+        }
+
+        #[test]
+        fn test_offset(position in position(), face in face()) {
+            let neighbor = position.offset(face).unwrap();
+            prop_assert_eq!(neighbor.offset(face.opposite()), Some(position));
+            prop_assert_eq!(position.manhattan_distance(neighbor), 1);
+            prop_assert_eq!(position.neighbors().filter(|&p| p == neighbor).count(), 1);
+        }
+
+        #[test]
+        fn test_distances(a in position(), b in position()) {
+            let euclidean = a.distance(b);
+            prop_assert!(euclidean <= a.manhattan_distance(b) as f64);
+            prop_assert_eq!(a.distance_squared(b), b.distance_squared(a));
+            prop_assert_eq!(a.manhattan_distance(a), 0);
+        }
+
+        #[test]
+        fn test_aabb(a in position(), b in position(), c in position()) {
+            let aabb = Aabb::new(a, b);
+            prop_assert_eq!(aabb, Aabb::new(b, a));
+            prop_assert!(aabb.contains(a) && aabb.contains(b));
+            prop_assert!(aabb.contains_box(Aabb::new(aabb.min(), aabb.max())));
+            prop_assert_eq!(aabb.contains(c), aabb.intersects(Aabb::new(c, c)));
+            prop_assert!(Aabb::new(a, c).intersects(Aabb::new(c, b)));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::convert::TryFrom;
+
+use serde::{
+    de::{self, Deserialize, Deserializer},
+    ser::{Serialize, Serializer},
+};
+
+/// How a player interacts with the world, sent and saved as its id.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub enum GameMode {
+    #[default]
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 4] = [
+        GameMode::Survival,
+        GameMode::Creative,
+        GameMode::Adventure,
+        GameMode::Spectator,
+    ];
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        Self::ALL.get(usize::try_from(id).ok()?).copied()
+    }
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the name used by commands and `server.properties`.
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative",
+            GameMode::Adventure => "adventure",
+            GameMode::Spectator => "spectator",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug, Default)]
+pub enum Difficulty {
+    Peaceful,
+    #[default]
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Peaceful,
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+    ];
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        Self::ALL.get(usize::try_from(id).ok()?).copied()
+    }
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Peaceful => "peaceful",
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+}
+
+macro_rules! id_serde_impl {
+    ($ty:ident, $what:literal) => {
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.id().serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let id = u8::deserialize(deserializer)?;
+                Self::from_id(id.into())
+                    .ok_or_else(|| de::Error::custom(format!("invalid {} {}", $what, id)))
+            }
+        }
+    };
+}
+
+id_serde_impl!(GameMode, "game mode");
+id_serde_impl!(Difficulty, "difficulty");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coder::{de::Deserializer, ser::Serializer};
+
+    #[test]
+    fn test_ids() {
+        for &mode in &GameMode::ALL {
+            assert_eq!(GameMode::from_id(mode.id().into()), Some(mode));
+        }
+        for &difficulty in &Difficulty::ALL {
+            assert_eq!(
+                Difficulty::from_id(difficulty.id().into()),
+                Some(difficulty)
+            );
+        }
+
+        assert_eq!(GameMode::from_id(3), Some(GameMode::Spectator));
+        assert_eq!(GameMode::from_id(-1), None);
+        assert_eq!(Difficulty::from_id(4), None);
+        assert_eq!(Difficulty::Hard.name(), "hard");
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let mut buf = Vec::new();
+        (GameMode::Adventure, Difficulty::Peaceful)
+            .serialize(&mut Serializer::new(&mut buf))
+            .unwrap();
+        assert_eq!(buf, [2, 0]);
+
+        let mut de = Deserializer::new(&[3, 4][..]);
+        assert_eq!(GameMode::deserialize(&mut de).unwrap(), GameMode::Spectator);
+        assert!(GameMode::deserialize(&mut de).is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    str::FromStr,
+    sync::{OnceLock, RwLock},
+};
+
+use serde::{
+    de::{self, Deserialize, Deserializer, Visitor},
+    ser::{Serialize, Serializer},
+};
+
+/// Namespace used when an identifier doesn't specify one.
+pub const DEFAULT_NAMESPACE: &str = "minecraft";
+
+/// Identifiers that show up often enough in packets that we never want to
+/// allocate for them.
+const COMMON: &[&str] = &[
+    "minecraft:brand",
+    "minecraft:register",
+    "minecraft:unregister",
+    "minecraft:overworld",
+    "minecraft:the_nether",
+    "minecraft:the_end",
+    "minecraft:air",
+    "minecraft:stone",
+    "minecraft:grass_block",
+    "minecraft:dirt",
+    "minecraft:bedrock",
+    "minecraft:water",
+    "minecraft:lava",
+    "minecraft:player",
+    "minecraft:plains",
+];
+
+/// A namespaced identifier (also called a resource location), like
+/// `minecraft:stone`.
+#[derive(Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Identifier {
+    full: Cow<'static, str>,
+    colon: usize,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum IdentifierError {
+    InvalidNamespace(String),
+    InvalidPath(String),
+}
+
+impl std::fmt::Display for IdentifierError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            IdentifierError::InvalidNamespace(s) => write!(
+                f,
+                "invalid identifier {:?}: namespaces may only contain [a-z0-9_.-]",
+                s
+            ),
+
+            IdentifierError::InvalidPath(s) => write!(
+                f,
+                "invalid identifier {:?}: paths may only contain [a-z0-9_.-/]",
+                s
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IdentifierError {}
+
+fn interned() -> &'static RwLock<HashSet<&'static str>> {
+    static INTERNED: OnceLock<RwLock<HashSet<&'static str>>> = OnceLock::new();
+    INTERNED.get_or_init(|| RwLock::new(COMMON.iter().copied().collect()))
+}
+
+fn lookup(s: &str) -> Option<&'static str> {
+    let interned = interned().read().unwrap_or_else(|err| err.into_inner());
+    interned.get(s).copied()
+}
+
+fn is_namespace_char(c: char) -> bool {
+    matches!(c, 'a'..='z' | '0'..='9' | '_' | '.' | '-')
+}
+
+fn is_path_char(c: char) -> bool {
+    is_namespace_char(c) || c == '/'
+}
+
+impl Identifier {
+    /// Creates the identifier `namespace:path`.
+    pub fn new(namespace: &str, path: &str) -> Result<Self, IdentifierError> {
+        Self::parse_owned(format!("{}:{}", namespace, path))
+    }
+
+    /// Parses an identifier, using the `minecraft` namespace if `s` doesn't
+    /// have one.
+    pub fn parse(s: &str) -> Result<Self, IdentifierError> {
+        match lookup(s) {
+            Some(interned) => Self::from_static(interned),
+            None => Self::parse_owned(s.to_owned()),
+        }
+    }
+
+    /// Parses an identifier like `parse` does, reusing the allocation of `s`
+    /// if it isn't an interned value.
+    pub fn parse_owned(s: String) -> Result<Self, IdentifierError> {
+        if let Some(interned) = lookup(&s) {
+            return Self::from_static(interned);
+        }
+
+        let s = match s.find(':') {
+            Some(0) => format!("{}{}", DEFAULT_NAMESPACE, s),
+            Some(_) => s,
+            None => format!("{}:{}", DEFAULT_NAMESPACE, s),
+        };
+
+        // Adding the default namespace might have turned it into a common
+        // value after all.
+        if let Some(interned) = lookup(&s) {
+            return Self::from_static(interned);
+        }
+
+        Self::validate(Cow::Owned(s))
+    }
+
+    /// Creates an identifier borrowing a static string, without allocating.
+    pub fn from_static(s: &'static str) -> Result<Self, IdentifierError> {
+        if s.starts_with(':') || !s.contains(':') {
+            return Self::parse_owned(s.to_owned());
+        }
+
+        Self::validate(Cow::Borrowed(s))
+    }
+
+    /// Parses an identifier and remembers it, so that parsing or deserializing
+    /// it again doesn't allocate.
+    ///
+    /// Interned values are never freed, so this should only be used for
+    /// identifiers the server itself knows about, never ones sent by clients.
+    pub fn intern(s: &str) -> Result<Self, IdentifierError> {
+        let full = match Self::parse(s)?.full {
+            Cow::Borrowed(full) => return Self::from_static(full),
+            Cow::Owned(full) => full,
+        };
+
+        let mut interned = interned().write().unwrap_or_else(|err| err.into_inner());
+
+        // Someone else might have interned it since we looked it up.
+        let full = match interned.get(&*full) {
+            Some(&full) => full,
+            None => {
+                let full: &'static str = Box::leak(full.into_boxed_str());
+                interned.insert(full);
+                full
+            }
+        };
+
+        Self::from_static(full)
+    }
+
+    fn validate(full: Cow<'static, str>) -> Result<Self, IdentifierError> {
+        let colon = match full.find(':') {
+            Some(colon) => colon,
+            None => return Err(IdentifierError::InvalidPath(full.into_owned())),
+        };
+
+        if !full[..colon].chars().all(is_namespace_char) {
+            return Err(IdentifierError::InvalidNamespace(full.into_owned()));
+        }
+
+        if !full[colon + 1..].chars().all(is_path_char) {
+            return Err(IdentifierError::InvalidPath(full.into_owned()));
+        }
+
+        Ok(Self { full, colon })
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.full[..self.colon]
+    }
+
+    pub fn path(&self) -> &str {
+        &self.full[self.colon + 1..]
+    }
+
+    /// Returns the identifier as `namespace:path`.
+    pub fn as_str(&self) -> &str {
+        &self.full
+    }
+
+    /// Returns whether the identifier is in the `minecraft` namespace.
+    pub fn is_vanilla(&self) -> bool {
+        self.namespace() == DEFAULT_NAMESPACE
+    }
+}
+
+impl FromStr for Identifier {
+    type Err = IdentifierError;
+
+    fn from_str(s: &str) -> Result<Self, IdentifierError> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for Identifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.full)
+    }
+}
+
+impl std::fmt::Debug for Identifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Identifier({:?})", &*self.full)
+    }
+}
+
+impl Serialize for Identifier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.full)
+    }
+}
+
+impl<'de> Deserialize<'de> for Identifier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IdentifierVisitor;
+
+        impl<'de> Visitor<'de> for IdentifierVisitor {
+            type Value = Identifier;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a namespaced identifier")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Identifier::parse(value).map_err(E::custom)
+            }
+
+            fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+                Identifier::parse_owned(value).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_string(IdentifierVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::coder::{de::Deserializer, ser::Serializer};
+
+    fn is_borrowed(id: &Identifier) -> bool {
+        matches!(id.full, Cow::Borrowed(_))
+    }
+
+    #[test]
+    fn test_parse() {
+        let id = Identifier::parse("oak_stairs").unwrap();
+        assert_eq!(id.namespace(), "minecraft");
+        assert_eq!(id.path(), "oak_stairs");
+        assert_eq!(id.as_str(), "minecraft:oak_stairs");
+        assert!(id.is_vanilla());
+
+        assert_eq!(Identifier::parse(":oak_stairs").unwrap(), id);
+        assert_eq!(Identifier::new("minecraft", "oak_stairs").unwrap(), id);
+
+        let id: Identifier = "my_plugin:sounds/boom.1".parse().unwrap();
+        assert_eq!(id.namespace(), "my_plugin");
+        assert_eq!(id.path(), "sounds/boom.1");
+        assert!(!id.is_vanilla());
+
+        assert_eq!(
+            Identifier::parse("My_Plugin:x"),
+            Err(IdentifierError::InvalidNamespace("My_Plugin:x".into()))
+        );
+        assert_eq!(
+            Identifier::parse("a/b:c"),
+            Err(IdentifierError::InvalidNamespace("a/b:c".into()))
+        );
+        assert_eq!(
+            Identifier::parse("minecraft:a:b"),
+            Err(IdentifierError::InvalidPath("minecraft:a:b".into()))
+        );
+        assert_eq!(
+            Identifier::parse("stone block"),
+            Err(IdentifierError::InvalidPath("minecraft:stone block".into()))
+        );
+    }
+
+    #[test]
+    fn test_interning() {
+        assert!(is_borrowed(&Identifier::parse("minecraft:brand").unwrap()));
+        assert!(is_borrowed(&Identifier::parse("brand").unwrap()));
+        assert!(is_borrowed(
+            &Identifier::from_static("my_plugin:x").unwrap()
+        ));
+        assert!(!is_borrowed(
+            &Identifier::parse("test:not_interned").unwrap()
+        ));
+
+        let id = Identifier::intern("test:interned").unwrap();
+        assert!(is_borrowed(&id));
+        assert!(is_borrowed(&Identifier::parse("test:interned").unwrap()));
+        assert!(is_borrowed(
+            &Identifier::parse_owned("test:interned".into()).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_validates() {
+        let mut buf = Vec::new();
+        "Not Valid!"
+            .serialize(&mut Serializer::new(&mut buf))
+            .unwrap();
+
+        let result = Identifier::deserialize(&mut Deserializer::new(&buf[..]));
+        assert!(result.is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_roundtrip(s in "[a-z0-9_.-]{1,16}:[a-z0-9_./-]{0,32}") {
+            let id = Identifier::parse(&s).unwrap();
+            prop_assert_eq!(id.as_str(), &s[..]);
+
+            let mut buf = Vec::new();
+            id.serialize(&mut Serializer::new(&mut buf)).unwrap();
+
+            let mut expected = Vec::new();
+            s.serialize(&mut Serializer::new(&mut expected)).unwrap();
+            prop_assert_eq!(&buf, &expected);
+
+            let deserialized = Identifier::deserialize(&mut Deserializer::new(&buf[..])).unwrap();
+            prop_assert_eq!(deserialized, id);
+        }
+    }
+}
@@ -0,0 +1,793 @@
+use std::{collections::BTreeMap, marker::PhantomData};
+
+use serde::{
+    de::{self, Deserialize, Deserializer, SeqAccess, Visitor},
+    ser::{self, Serialize, SerializeTuple, Serializer},
+    Deserialize as DeriveDeserialize, Serialize as DeriveSerialize,
+};
+
+use super::{
+    super::nbt::{Compound, NetworkTag, Tag},
+    BlockFace, Chat, Position, Slot, Uuid, VarInt,
+};
+
+/// The index that ends the list, which therefore can't hold an entry itself.
+const TERMINATOR: u8 = 0xff;
+
+/// Rotations of an armor stand's parts, in degrees.
+#[derive(Clone, Copy, PartialEq, Debug, Default, DeriveSerialize, DeriveDeserialize)]
+pub struct Rotation {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct VillagerData {
+    pub kind: i32,
+    pub profession: i32,
+    pub level: i32,
+}
+
+/// The pose of an entity, which decides its hitbox.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug, DeriveSerialize, DeriveDeserialize)]
+pub enum Pose {
+    #[default]
+    Standing,
+    FallFlying,
+    Sleeping,
+    Swimming,
+    SpinAttack,
+    Sneaking,
+    Dying,
+}
+
+/// A particle, along with the data its type needs.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Particle {
+    pub id: i32,
+    pub data: ParticleData,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ParticleData {
+    None,
+    /// The block state of `block` and `falling_dust` particles.
+    Block(i32),
+    Dust {
+        red: f32,
+        green: f32,
+        blue: f32,
+        scale: f32,
+    },
+    Item(Slot),
+}
+
+impl Particle {
+    // The only particles that carry data, as numbered since 1.14.
+    pub const BLOCK: i32 = 3;
+    pub const DUST: i32 = 14;
+    pub const FALLING_DUST: i32 = 23;
+    pub const ITEM: i32 = 32;
+
+    pub fn new(id: i32) -> Self {
+        Self {
+            id,
+            data: ParticleData::None,
+        }
+    }
+}
+
+/// A single metadata value, tagged with its type.
+///
+/// Types are numbered as from 1.14 onwards, which added `VillagerData`,
+/// `OptVarInt` and `Pose`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum MetadataValue {
+    Byte(i8),
+    VarInt(i32),
+    Float(f32),
+    String(String),
+    Chat(Chat),
+    OptChat(Option<Chat>),
+    Slot(Slot),
+    Boolean(bool),
+    Rotation(Rotation),
+    Position(Position),
+    OptPosition(Option<Position>),
+    Direction(BlockFace),
+    OptUuid(Option<Uuid>),
+    /// A block state id, where 0 (air) means there is none.
+    OptBlockState(Option<i32>),
+    Nbt(Compound),
+    Particle(Particle),
+    VillagerData(VillagerData),
+    OptVarInt(Option<i32>),
+    Pose(Pose),
+}
+
+impl MetadataValue {
+    pub fn type_id(&self) -> i32 {
+        match self {
+            MetadataValue::Byte(_) => 0,
+            MetadataValue::VarInt(_) => 1,
+            MetadataValue::Float(_) => 2,
+            MetadataValue::String(_) => 3,
+            MetadataValue::Chat(_) => 4,
+            MetadataValue::OptChat(_) => 5,
+            MetadataValue::Slot(_) => 6,
+            MetadataValue::Boolean(_) => 7,
+            MetadataValue::Rotation(_) => 8,
+            MetadataValue::Position(_) => 9,
+            MetadataValue::OptPosition(_) => 10,
+            MetadataValue::Direction(_) => 11,
+            MetadataValue::OptUuid(_) => 12,
+            MetadataValue::OptBlockState(_) => 13,
+            MetadataValue::Nbt(_) => 14,
+            MetadataValue::Particle(_) => 15,
+            MetadataValue::VillagerData(_) => 16,
+            MetadataValue::OptVarInt(_) => 17,
+            MetadataValue::Pose(_) => 18,
+        }
+    }
+
+    /// Returns how many elements `serialize_payload` writes.
+    fn element_count(&self) -> usize {
+        match self {
+            MetadataValue::OptChat(chat) => 1 + chat.is_some() as usize,
+            MetadataValue::OptPosition(position) => 1 + position.is_some() as usize,
+            MetadataValue::OptUuid(uuid) => 1 + uuid.is_some() as usize,
+            MetadataValue::Particle(particle) => match particle.data {
+                ParticleData::None => 1,
+                _ => 2,
+            },
+            _ => 1,
+        }
+    }
+
+    fn serialize_payload<S: SerializeTuple>(&self, seq: &mut S) -> Result<(), S::Error> {
+        fn optional<T: Serialize, S: SerializeTuple>(
+            seq: &mut S,
+            value: &Option<T>,
+        ) -> Result<(), S::Error> {
+            seq.serialize_element(&value.is_some())?;
+            match value {
+                Some(value) => seq.serialize_element(value),
+                None => Ok(()),
+            }
+        }
+
+        match self {
+            MetadataValue::Byte(n) => seq.serialize_element(n),
+            MetadataValue::VarInt(n) => seq.serialize_element(&VarInt(*n)),
+            MetadataValue::Float(n) => seq.serialize_element(n),
+            MetadataValue::String(s) => seq.serialize_element(s),
+            MetadataValue::Chat(chat) => seq.serialize_element(chat),
+            MetadataValue::OptChat(chat) => optional(seq, chat),
+            MetadataValue::Slot(slot) => seq.serialize_element(slot),
+            MetadataValue::Boolean(b) => seq.serialize_element(b),
+            MetadataValue::Rotation(rotation) => seq.serialize_element(rotation),
+            MetadataValue::Position(position) => seq.serialize_element(position),
+            MetadataValue::OptPosition(position) => optional(seq, position),
+            MetadataValue::Direction(face) => seq.serialize_element(face),
+            MetadataValue::OptUuid(uuid) => optional(seq, uuid),
+            MetadataValue::OptBlockState(state) => {
+                seq.serialize_element(&VarInt(state.unwrap_or(0)))
+            }
+            MetadataValue::Nbt(compound) => {
+                seq.serialize_element(&NetworkTag(Some(Tag::Compound(compound.clone()))))
+            }
+            MetadataValue::Particle(particle) => {
+                seq.serialize_element(&VarInt(particle.id))?;
+                match &particle.data {
+                    ParticleData::None => Ok(()),
+                    ParticleData::Block(state) => seq.serialize_element(&VarInt(*state)),
+                    ParticleData::Dust {
+                        red,
+                        green,
+                        blue,
+                        scale,
+                    } => seq.serialize_element(&(red, green, blue, scale)),
+                    ParticleData::Item(slot) => seq.serialize_element(slot),
+                }
+            }
+            MetadataValue::VillagerData(data) => seq.serialize_element(&(
+                VarInt(data.kind),
+                VarInt(data.profession),
+                VarInt(data.level),
+            )),
+            MetadataValue::OptVarInt(n) => {
+                let n = n
+                    .map_or(Some(0), |n| n.checked_add(1))
+                    .ok_or_else(|| ser::Error::custom("optional VarInt out of range"))?;
+                seq.serialize_element(&VarInt(n))
+            }
+            MetadataValue::Pose(pose) => seq.serialize_element(pose),
+        }
+    }
+
+    fn deserialize_payload<'de, A: SeqAccess<'de>>(
+        type_id: i32,
+        seq: &mut A,
+    ) -> Result<Self, A::Error> {
+        fn optional<'de, T: Deserialize<'de>, A: SeqAccess<'de>>(
+            seq: &mut A,
+        ) -> Result<Option<T>, A::Error> {
+            if next(seq)? {
+                next(seq).map(Some)
+            } else {
+                Ok(None)
+            }
+        }
+
+        let value = match type_id {
+            0 => MetadataValue::Byte(next(seq)?),
+            1 => MetadataValue::VarInt(next::<VarInt, _>(seq)?.0),
+            2 => MetadataValue::Float(next(seq)?),
+            3 => MetadataValue::String(next(seq)?),
+            4 => MetadataValue::Chat(next(seq)?),
+            5 => MetadataValue::OptChat(optional(seq)?),
+            6 => MetadataValue::Slot(next(seq)?),
+            7 => MetadataValue::Boolean(next(seq)?),
+            8 => MetadataValue::Rotation(next(seq)?),
+            9 => MetadataValue::Position(next(seq)?),
+            10 => MetadataValue::OptPosition(optional(seq)?),
+            11 => MetadataValue::Direction(next(seq)?),
+            12 => MetadataValue::OptUuid(optional(seq)?),
+            13 => {
+                let VarInt(state) = next(seq)?;
+                MetadataValue::OptBlockState((state != 0).then_some(state))
+            }
+            14 => match next(seq)? {
+                NetworkTag(Some(Tag::Compound(compound))) => MetadataValue::Nbt(compound),
+                NetworkTag(None) => MetadataValue::Nbt(Compound::new()),
+                NetworkTag(Some(_)) => {
+                    return Err(de::Error::custom(
+                        "expected a compound tag in entity metadata",
+                    ))
+                }
+            },
+            15 => {
+                let VarInt(id) = next(seq)?;
+                let data = match id {
+                    Particle::BLOCK | Particle::FALLING_DUST => {
+                        ParticleData::Block(next::<VarInt, _>(seq)?.0)
+                    }
+                    Particle::DUST => {
+                        let (red, green, blue, scale) = next(seq)?;
+                        ParticleData::Dust {
+                            red,
+                            green,
+                            blue,
+                            scale,
+                        }
+                    }
+                    Particle::ITEM => ParticleData::Item(next(seq)?),
+                    _ => ParticleData::None,
+                };
+                MetadataValue::Particle(Particle { id, data })
+            }
+            16 => {
+                let (VarInt(kind), VarInt(profession), VarInt(level)) = next(seq)?;
+                MetadataValue::VillagerData(VillagerData {
+                    kind,
+                    profession,
+                    level,
+                })
+            }
+            17 => {
+                let VarInt(n) = next(seq)?;
+                let n = (n != 0)
+                    .then(|| {
+                        n.checked_sub(1)
+                            .ok_or_else(|| de::Error::custom("optional VarInt out of range"))
+                    })
+                    .transpose()?;
+                MetadataValue::OptVarInt(n)
+            }
+            18 => MetadataValue::Pose(next(seq)?),
+            _ => {
+                return Err(de::Error::custom(format!(
+                    "unknown entity metadata type {}",
+                    type_id
+                )))
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+fn next<'de, T: Deserialize<'de>, A: SeqAccess<'de>>(seq: &mut A) -> Result<T, A::Error> {
+    seq.next_element()?
+        .ok_or_else(|| de::Error::custom("entity metadata ended early"))
+}
+
+/// Types that can be stored in entity metadata, so that fields can be typed.
+pub trait MetadataType: Sized {
+    fn into_value(self) -> MetadataValue;
+    fn from_value(value: &MetadataValue) -> Option<Self>;
+}
+
+macro_rules! metadata_type_impl {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl MetadataType for $ty {
+                fn into_value(self) -> MetadataValue {
+                    MetadataValue::$variant(self)
+                }
+
+                fn from_value(value: &MetadataValue) -> Option<Self> {
+                    match value {
+                        MetadataValue::$variant(value) => Some(value.clone()),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+// Block states are `Option<i32>` as well, so they can only be set with
+// `EntityMetadata::set_value`.
+metadata_type_impl! {
+    i8 => Byte,
+    i32 => VarInt,
+    f32 => Float,
+    String => String,
+    Chat => Chat,
+    Option<Chat> => OptChat,
+    Slot => Slot,
+    bool => Boolean,
+    Rotation => Rotation,
+    Position => Position,
+    Option<Position> => OptPosition,
+    BlockFace => Direction,
+    Option<Uuid> => OptUuid,
+    Compound => Nbt,
+    Particle => Particle,
+    VillagerData => VillagerData,
+    Option<i32> => OptVarInt,
+    Pose => Pose,
+}
+
+/// A metadata field of some entity type, at a fixed index.
+pub struct Field<T> {
+    index: u8,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Field<T> {
+    pub const fn new(index: u8) -> Self {
+        Self {
+            index,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+}
+
+impl<T> Clone for Field<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Field<T> {}
+
+impl<T> std::fmt::Debug for Field<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Field({})", self.index)
+    }
+}
+
+/// A bit of a byte field.
+#[derive(Clone, Copy, Debug)]
+pub struct Flag {
+    pub field: Field<i8>,
+    pub mask: u8,
+}
+
+impl Flag {
+    pub const fn new(index: u8, mask: u8) -> Self {
+        Self {
+            field: Field::new(index),
+            mask,
+        }
+    }
+}
+
+/// Field definitions for each entity type, as of 1.14. Every type has the
+/// fields of the ones it extends, which come first.
+pub mod fields {
+    use super::{
+        super::{Chat, Position, Slot},
+        Field, Flag, Pose, Rotation, VillagerData,
+    };
+    use crate::nbt::Compound;
+
+    pub mod entity {
+        use super::*;
+
+        pub const FLAGS: Field<i8> = Field::new(0);
+        pub const ON_FIRE: Flag = Flag::new(0, 0x01);
+        pub const SNEAKING: Flag = Flag::new(0, 0x02);
+        pub const SPRINTING: Flag = Flag::new(0, 0x08);
+        pub const SWIMMING: Flag = Flag::new(0, 0x10);
+        pub const INVISIBLE: Flag = Flag::new(0, 0x20);
+        pub const GLOWING: Flag = Flag::new(0, 0x40);
+        pub const FLYING_WITH_ELYTRA: Flag = Flag::new(0, 0x80);
+        pub const AIR: Field<i32> = Field::new(1);
+        pub const CUSTOM_NAME: Field<Option<Chat>> = Field::new(2);
+        pub const CUSTOM_NAME_VISIBLE: Field<bool> = Field::new(3);
+        pub const SILENT: Field<bool> = Field::new(4);
+        pub const NO_GRAVITY: Field<bool> = Field::new(5);
+        pub const POSE: Field<Pose> = Field::new(6);
+    }
+
+    pub mod item {
+        use super::*;
+
+        pub const ITEM: Field<Slot> = Field::new(7);
+    }
+
+    pub mod living {
+        use super::*;
+
+        pub const HAND_STATES: Field<i8> = Field::new(7);
+        pub const HAND_ACTIVE: Flag = Flag::new(7, 0x01);
+        pub const OFFHAND_ACTIVE: Flag = Flag::new(7, 0x02);
+        pub const HEALTH: Field<f32> = Field::new(8);
+        pub const POTION_EFFECT_COLOR: Field<i32> = Field::new(9);
+        pub const POTION_EFFECT_AMBIENT: Field<bool> = Field::new(10);
+        pub const ARROWS: Field<i32> = Field::new(11);
+        pub const BED_POSITION: Field<Option<Position>> = Field::new(12);
+    }
+
+    pub mod player {
+        use super::*;
+
+        pub const ADDITIONAL_HEARTS: Field<f32> = Field::new(13);
+        pub const SCORE: Field<i32> = Field::new(14);
+        pub const SKIN_PARTS: Field<i8> = Field::new(15);
+        pub const CAPE: Flag = Flag::new(15, 0x01);
+        pub const JACKET: Flag = Flag::new(15, 0x02);
+        pub const LEFT_SLEEVE: Flag = Flag::new(15, 0x04);
+        pub const RIGHT_SLEEVE: Flag = Flag::new(15, 0x08);
+        pub const LEFT_PANTS_LEG: Flag = Flag::new(15, 0x10);
+        pub const RIGHT_PANTS_LEG: Flag = Flag::new(15, 0x20);
+        pub const HAT: Flag = Flag::new(15, 0x40);
+        pub const MAIN_HAND: Field<i8> = Field::new(16);
+        pub const LEFT_SHOULDER: Field<Compound> = Field::new(17);
+        pub const RIGHT_SHOULDER: Field<Compound> = Field::new(18);
+    }
+
+    pub mod armor_stand {
+        use super::*;
+
+        pub const FLAGS: Field<i8> = Field::new(13);
+        pub const SMALL: Flag = Flag::new(13, 0x01);
+        pub const HAS_ARMS: Flag = Flag::new(13, 0x04);
+        pub const NO_BASEPLATE: Flag = Flag::new(13, 0x08);
+        pub const MARKER: Flag = Flag::new(13, 0x10);
+        pub const HEAD_ROTATION: Field<Rotation> = Field::new(14);
+        pub const BODY_ROTATION: Field<Rotation> = Field::new(15);
+        pub const LEFT_ARM_ROTATION: Field<Rotation> = Field::new(16);
+        pub const RIGHT_ARM_ROTATION: Field<Rotation> = Field::new(17);
+        pub const LEFT_LEG_ROTATION: Field<Rotation> = Field::new(18);
+        pub const RIGHT_LEG_ROTATION: Field<Rotation> = Field::new(19);
+    }
+
+    pub mod mob {
+        use super::*;
+
+        pub const FLAGS: Field<i8> = Field::new(13);
+        pub const NO_AI: Flag = Flag::new(13, 0x01);
+        pub const LEFT_HANDED: Flag = Flag::new(13, 0x02);
+    }
+
+    pub mod ageable {
+        use super::*;
+
+        pub const BABY: Field<bool> = Field::new(14);
+    }
+
+    pub mod villager {
+        use super::*;
+
+        pub const HEAD_SHAKE_TIMER: Field<i32> = Field::new(15);
+        pub const VILLAGER_DATA: Field<VillagerData> = Field::new(16);
+    }
+}
+
+/// The metadata of an entity, as sent when spawning it or when some of it
+/// changes.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct EntityMetadata {
+    entries: BTreeMap<u8, MetadataValue>,
+}
+
+impl EntityMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get_value(&self, index: u8) -> Option<&MetadataValue> {
+        self.entries.get(&index)
+    }
+
+    pub fn set_value(&mut self, index: u8, value: MetadataValue) {
+        self.entries.insert(index, value);
+    }
+
+    pub fn remove(&mut self, index: u8) -> Option<MetadataValue> {
+        self.entries.remove(&index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &MetadataValue)> {
+        self.entries.iter().map(|(&index, value)| (index, value))
+    }
+
+    /// Returns the value of a field, or `None` if it's missing or holds
+    /// another type.
+    pub fn get<T: MetadataType>(&self, field: Field<T>) -> Option<T> {
+        self.get_value(field.index).and_then(T::from_value)
+    }
+
+    pub fn set<T: MetadataType>(&mut self, field: Field<T>, value: T) {
+        self.set_value(field.index, value.into_value());
+    }
+
+    pub fn flag(&self, flag: Flag) -> bool {
+        self.get(flag.field)
+            .is_some_and(|bits| bits as u8 & flag.mask != 0)
+    }
+
+    pub fn set_flag(&mut self, flag: Flag, value: bool) {
+        let bits = self.get(flag.field).unwrap_or(0) as u8;
+        let bits = if value {
+            bits | flag.mask
+        } else {
+            bits & !flag.mask
+        };
+
+        self.set(flag.field, bits as i8);
+    }
+}
+
+impl Serialize for EntityMetadata {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = self
+            .entries
+            .values()
+            .map(|value| 2 + value.element_count())
+            .sum::<usize>();
+        let mut seq = serializer.serialize_tuple(len + 1)?;
+
+        for (&index, value) in &self.entries {
+            if index == TERMINATOR {
+                return Err(ser::Error::custom("entity metadata index 255 is reserved"));
+            }
+
+            seq.serialize_element(&index)?;
+            seq.serialize_element(&VarInt(value.type_id()))?;
+            value.serialize_payload(&mut seq)?;
+        }
+
+        seq.serialize_element(&TERMINATOR)?;
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for EntityMetadata {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MetadataVisitor;
+
+        impl<'de> Visitor<'de> for MetadataVisitor {
+            type Value = EntityMetadata;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "entity metadata")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut metadata = EntityMetadata::new();
+
+                loop {
+                    let index: u8 = next(&mut seq)?;
+                    if index == TERMINATOR {
+                        return Ok(metadata);
+                    }
+
+                    let VarInt(type_id) = next(&mut seq)?;
+                    let value = MetadataValue::deserialize_payload(type_id, &mut seq)?;
+                    metadata.set_value(index, value);
+                }
+            }
+        }
+
+        // The list is only as long as it takes to reach the terminator.
+        deserializer.deserialize_tuple(usize::MAX, MetadataVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{super::WorldHeight, fields::*, *};
+    use crate::coder::{de, error::Result, ser};
+
+    fn encode(metadata: &EntityMetadata) -> Vec<u8> {
+        let mut buf = Vec::new();
+        metadata
+            .serialize(&mut ser::Serializer::new(&mut buf))
+            .unwrap();
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Result<EntityMetadata> {
+        EntityMetadata::deserialize(&mut de::Deserializer::new(buf))
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut metadata = EntityMetadata::new();
+        assert!(!metadata.flag(entity::ON_FIRE));
+
+        metadata.set_flag(entity::ON_FIRE, true);
+        metadata.set_flag(entity::SNEAKING, true);
+        metadata.set_flag(entity::FLYING_WITH_ELYTRA, true);
+        metadata.set_flag(entity::ON_FIRE, false);
+        assert!(metadata.flag(entity::SNEAKING) && !metadata.flag(entity::ON_FIRE));
+        assert_eq!(metadata.get(entity::FLAGS), Some(0x82_u8 as i8));
+
+        metadata.set(living::HEALTH, 20.0);
+        metadata.set(entity::CUSTOM_NAME, Some(Chat::text("Steve")));
+        assert_eq!(metadata.get(living::HEALTH), Some(20.0));
+        assert_eq!(
+            metadata.get(entity::CUSTOM_NAME),
+            Some(Some(Chat::text("Steve")))
+        );
+
+        // Fields of other entity types may share the index.
+        assert_eq!(metadata.get(living::ARROWS), None);
+        metadata.set(player::MAIN_HAND, 1);
+        assert_eq!(metadata.get(armor_stand::FLAGS), None);
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(encode(&EntityMetadata::new()), [0xff]);
+
+        let mut metadata = EntityMetadata::new();
+        metadata.set_flag(entity::SNEAKING, true);
+        metadata.set(entity::AIR, 300);
+        metadata.set(entity::CUSTOM_NAME, None);
+        metadata.set(entity::POSE, Pose::Sneaking);
+        metadata.set_value(13, MetadataValue::OptBlockState(None));
+        metadata.set_value(20, MetadataValue::OptVarInt(Some(0)));
+
+        assert_eq!(
+            encode(&metadata),
+            [
+                0x00, 0x00, 0x02, // sneaking
+                0x01, 0x01, 0xac, 0x02, // 300 air
+                0x02, 0x05, 0x00, // no custom name
+                0x06, 0x12, 0x05, // sneaking pose
+                0x0d, 0x0d, 0x00, // no block state
+                0x14, 0x11, 0x01, // entity 0
+                0xff,
+            ]
+        );
+
+        let mut invalid = EntityMetadata::new();
+        invalid.set_value(TERMINATOR, MetadataValue::Boolean(true));
+        assert!(invalid
+            .serialize(&mut ser::Serializer::new(&mut Vec::new()))
+            .is_err());
+
+        assert!(decode(&[0x00, 0x13, 0x00, 0xff]).is_err());
+        assert!(decode(&[0x00, 0x00, 0x00]).is_err());
+
+        // Optional VarInts are shifted by one, which mustn't overflow.
+        let mut overflow = EntityMetadata::new();
+        overflow.set_value(0, MetadataValue::OptVarInt(Some(i32::MAX)));
+        assert!(overflow
+            .serialize(&mut ser::Serializer::new(&mut Vec::new()))
+            .is_err());
+        assert!(decode(&[0x00, 0x11, 0x80, 0x80, 0x80, 0x80, 0x08, 0xff]).is_err());
+        assert_eq!(
+            decode(&[0x00, 0x11, 0xff, 0xff, 0xff, 0xff, 0x07, 0xff])
+                .unwrap()
+                .get_value(0),
+            Some(&MetadataValue::OptVarInt(Some(i32::MAX - 1)))
+        );
+    }
+
+    #[test]
+    fn test_pose_discriminants() {
+        assert_eq!(crate::coder::duplicate_discriminant::<Pose>(), None);
+    }
+
+    #[test]
+    fn test_roundtrip_all_types() {
+        let mut shoulder = Compound::new();
+        shoulder.insert("id".to_owned(), Tag::String("minecraft:parrot".to_owned()));
+
+        let values = vec![
+            MetadataValue::Byte(-3),
+            MetadataValue::VarInt(1 << 20),
+            MetadataValue::Float(0.5),
+            MetadataValue::String("hello".to_owned()),
+            MetadataValue::Chat(Chat::text("hi").bold(true)),
+            MetadataValue::OptChat(Some(Chat::text("name"))),
+            MetadataValue::Slot(Slot::from(super::super::ItemStack::new(1, 3).unwrap())),
+            MetadataValue::Boolean(true),
+            MetadataValue::Rotation(Rotation {
+                x: 1.0,
+                y: -90.0,
+                z: 45.5,
+            }),
+            MetadataValue::Position(Position::new(10, 64, -10, WorldHeight::OVERWORLD).unwrap()),
+            MetadataValue::OptPosition(None),
+            MetadataValue::Direction(BlockFace::West),
+            MetadataValue::OptUuid(Some(Uuid(0x069a79f4_44e9_4726_a5be_fca90e38aaf5))),
+            MetadataValue::OptBlockState(Some(1)),
+            MetadataValue::Nbt(shoulder),
+            MetadataValue::Nbt(Compound::new()),
+            MetadataValue::Particle(Particle::new(0)),
+            MetadataValue::Particle(Particle {
+                id: Particle::DUST,
+                data: ParticleData::Dust {
+                    red: 1.0,
+                    green: 0.0,
+                    blue: 0.5,
+                    scale: 2.0,
+                },
+            }),
+            MetadataValue::Particle(Particle {
+                id: Particle::ITEM,
+                data: ParticleData::Item(Slot::empty()),
+            }),
+            MetadataValue::VillagerData(VillagerData {
+                kind: 2,
+                profession: 5,
+                level: 1,
+            }),
+            MetadataValue::OptVarInt(None),
+            MetadataValue::Pose(Pose::Dying),
+        ];
+
+        let mut metadata = EntityMetadata::new();
+        for (index, value) in values.into_iter().enumerate() {
+            metadata.set_value(index as u8, value);
+        }
+
+        assert_eq!(decode(&encode(&metadata)).unwrap(), metadata);
+    }
+
+    proptest! {
+        #[test]
+        fn test_roundtrip(entries in prop::collection::btree_map(0..255u8, (any::<i8>(), 0..i32::MAX, any::<bool>()), 0..16)) {
+            let mut metadata = EntityMetadata::new();
+            for (index, (byte, varint, boolean)) in entries {
+                let value = match index % 3 {
+                    0 => MetadataValue::Byte(byte),
+                    1 => MetadataValue::OptVarInt(Some(varint).filter(|_| boolean)),
+                    _ => MetadataValue::Boolean(boolean),
+                };
+                metadata.set_value(index, value);
+            }
+
+            prop_assert_eq!(decode(&encode(&metadata)).unwrap(), metadata);
+        }
+    }
+}
@@ -1,5 +1,26 @@
-mod position;
-mod varint;
+mod bits;
+mod chat;
+mod coords;
+mod game;
+mod identifier;
+mod metadata;
+pub(crate) mod position;
+pub(crate) mod slot;
+mod units;
+mod uuid;
+pub(crate) mod varint;
 
-pub use position::Position;
+pub use bits::{BitSet, PackedArray, PackedArrayError, Packing};
+pub use chat::{Chat, ChatError, ClickEvent, Color, Content, HoverEvent, Style};
+pub use coords::{Aabb, BlockFace, ChunkPos, RegionPos, SectionPos, CHUNK_SIZE, REGION_SIZE};
+pub use game::{Difficulty, GameMode};
+pub use identifier::{Identifier, IdentifierError};
+pub use metadata::{
+    fields, EntityMetadata, Field, Flag, MetadataType, MetadataValue, Particle, ParticleData, Pose,
+    Rotation, VillagerData,
+};
+pub use position::{Position, PositionError, PositionLayout, WorldHeight};
+pub use slot::{Enchantment, ItemStack, LegacySlot, Slot, SlotError, SlotFormat};
+pub use units::{Angle, FixedPoint, Velocity};
+pub use uuid::{ParseUuidError, Uuid};
 pub use varint::{VarInt, VarLong};
@@ -1,30 +1,216 @@
+use std::convert::TryFrom;
+
 use serde::{
     de::{self, Deserialize, Deserializer, Visitor},
-    ser::{Serialize, Serializer},
+    ser::{self, Serialize, Serializer},
 };
 
-#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Debug)]
+/// Newtype struct name our own coder recognizes, so that it can pack the
+/// position in the layout the connection it's writing to expects.
+pub(crate) const POSITION_TOKEN: &str = "$mcserver::Position";
+
+/// A block position.
+///
+/// On the wire `x` and `z` get 26 bits and `y` gets 12, so use `Position::new`
+/// or `Position::check` to make sure a position can actually be sent and lies
+/// within its dimension, instead of building one by hand.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Debug)]
 pub struct Position {
     pub x: i32, // 26 bits
-    pub y: i16, // 12 bits
+    pub y: i32, // 12 bits
     pub z: i32, // 26 bits
 }
 
-impl Into<u64> for Position {
-    fn into(self) -> u64 {
+/// The range of `y` values blocks can be at in a dimension.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct WorldHeight {
+    min_y: i32,
+    height: i32,
+}
+
+impl WorldHeight {
+    /// The world height of every dimension before 1.18.
+    pub const LEGACY: Self = Self {
+        min_y: 0,
+        height: 256,
+    };
+
+    /// The world height of the overworld since 1.18.
+    pub const OVERWORLD: Self = Self {
+        min_y: -64,
+        height: 384,
+    };
+
+    /// Lowest and highest `y` a dimension type may allow, which are the same
+    /// limits vanilla enforces on datapacks.
+    const MIN_Y: i32 = -2032;
+    const MAX_Y: i32 = 2031;
+
+    /// Creates a world height of `height` blocks starting at `min_y`, which
+    /// like in datapacks must both be multiples of 16.
+    pub fn new(min_y: i32, height: i32) -> Result<Self, PositionError> {
+        let valid = min_y % 16 == 0
+            && height % 16 == 0
+            && height > 0
+            && min_y >= Self::MIN_Y
+            && min_y
+                .checked_add(height - 1)
+                .is_some_and(|max_y| max_y <= Self::MAX_Y);
+
+        if valid {
+            Ok(Self { min_y, height })
+        } else {
+            Err(PositionError::InvalidWorldHeight { min_y, height })
+        }
+    }
+
+    pub fn min_y(self) -> i32 {
+        self.min_y
+    }
+
+    pub fn max_y(self) -> i32 {
+        self.min_y + self.height - 1
+    }
+
+    pub fn height(self) -> i32 {
+        self.height
+    }
+
+    pub fn contains(self, y: i32) -> bool {
+        (self.min_y..=self.max_y()).contains(&y)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum PositionError {
+    /// A coordinate lies outside of `min..=max`, because it doesn't fit in the
+    /// bits the protocol gives it or because it's outside the world height.
+    OutOfBounds {
+        axis: char,
+        value: i32,
+        min: i32,
+        max: i32,
+    },
+
+    InvalidWorldHeight {
+        min_y: i32,
+        height: i32,
+    },
+}
+
+impl std::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PositionError::OutOfBounds {
+                axis,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "{} coordinate {} is out of bounds, expected {}..={}",
+                axis, value, min, max
+            ),
+
+            PositionError::InvalidWorldHeight { min_y, height } => write!(
+                f,
+                "invalid world height of {} blocks starting at y {}",
+                height, min_y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+/// How the coordinates of a `Position` are packed into 64 bits.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub enum PositionLayout {
+    /// `x(26) | y(12) | z(26)`, used before 1.14.
+    #[default]
+    Xyz,
+
+    /// `x(26) | z(26) | y(12)`, used since 1.14.
+    Xzy,
+}
+
+impl PositionLayout {
+    /// Returns the layout clients speaking `protocol_version` expect.
+    pub fn for_protocol(protocol_version: i32) -> Self {
+        // 1.14 was protocol version 477.
+        if protocol_version >= 477 {
+            PositionLayout::Xzy
+        } else {
+            PositionLayout::Xyz
+        }
+    }
+}
+
+macro_rules! check_bounds {
+    ($axis:literal: $value:expr, $min:expr, $max:expr) => {{
+        let (value, min, max) = ($value, $min, $max);
+        if !(min..=max).contains(&value) {
+            return Err(PositionError::OutOfBounds {
+                axis: $axis,
+                value,
+                min,
+                max,
+            });
+        }
+    }};
+}
+
+impl Position {
+    pub const MIN_XZ: i32 = -(1 << 25);
+    pub const MAX_XZ: i32 = lsb!(25);
+    pub const MIN_Y: i32 = -(1 << 11);
+    pub const MAX_Y: i32 = lsb!(11);
+
+    /// Creates a position, making sure it can be sent over the wire and that
+    /// `y` lies within `height`.
+    pub fn new(x: i32, y: i32, z: i32, height: WorldHeight) -> Result<Self, PositionError> {
+        Self { x, y, z }.check(height)
+    }
+
+    /// Makes sure the position can be sent over the wire and that `y` lies
+    /// within `height`.
+    pub fn check(self, height: WorldHeight) -> Result<Self, PositionError> {
+        self.check_packable()?;
+        check_bounds!('y': self.y, height.min_y(), height.max_y());
+        Ok(self)
+    }
+
+    fn check_packable(self) -> Result<Self, PositionError> {
+        check_bounds!('x': self.x, Self::MIN_XZ, Self::MAX_XZ);
+        check_bounds!('y': self.y, Self::MIN_Y, Self::MAX_Y);
+        check_bounds!('z': self.z, Self::MIN_XZ, Self::MAX_XZ);
+        Ok(self)
+    }
+
+    /// Packs the position into 64 bits, failing if any coordinate doesn't fit
+    /// in the bits the protocol gives it.
+    pub fn pack(self, layout: PositionLayout) -> Result<u64, PositionError> {
+        self.check_packable()?;
+
         let x: u64 = self.x as u64 & lsb!(26);
         let y: u64 = self.y as u64 & lsb!(12);
         let z: u64 = self.z as u64 & lsb!(26);
 
-        (x << (26 + 12)) | (y << 26) | z
+        Ok(match layout {
+            PositionLayout::Xyz => (x << (26 + 12)) | (y << 26) | z,
+            PositionLayout::Xzy => (x << (26 + 12)) | (z << 12) | y,
+        })
     }
-}
 
-impl From<u64> for Position {
-    fn from(n: u64) -> Self {
-        let z = (n & lsb!(26)) as u32;
-        let y = ((n >> 26) & lsb!(12)) as u16;
-        let x = ((n >> (26 + 12)) & lsb!(26)) as u32;
+    pub fn unpack(n: u64, layout: PositionLayout) -> Self {
+        let (x, y, z) = match layout {
+            PositionLayout::Xyz => (n >> (26 + 12), n >> 26, n),
+            PositionLayout::Xzy => (n >> (26 + 12), n, n >> 12),
+        };
+
+        let x = (x & lsb!(26)) as u32;
+        let y = (y & lsb!(12)) as u16;
+        let z = (z & lsb!(26)) as u32;
 
         macro_rules! uN_to_iN {
             ($x:ident: $N:literal; $from_ty:ty => $to_ty:ty) => {
@@ -35,19 +221,34 @@ impl From<u64> for Position {
                         0
                     }
             };
-        };
+        }
 
         let x = uN_to_iN!(x: 26; u32 => i32);
-        let y = uN_to_iN!(y: 12; u16 => i16);
+        let y = uN_to_iN!(y: 12; u16 => i32);
         let z = uN_to_iN!(z: 26; u32 => i32);
 
         Self { x, y, z }
     }
 }
 
+impl TryFrom<Position> for u64 {
+    type Error = PositionError;
+
+    fn try_from(pos: Position) -> Result<u64, PositionError> {
+        pos.pack(PositionLayout::Xyz)
+    }
+}
+
+impl From<u64> for Position {
+    fn from(n: u64) -> Self {
+        Position::unpack(n, PositionLayout::Xyz)
+    }
+}
+
 impl Serialize for Position {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        serializer.serialize_u64(self.clone().into())
+        let packed = u64::try_from(*self).map_err(ser::Error::custom)?;
+        serializer.serialize_newtype_struct(POSITION_TOKEN, &packed)
     }
 }
 
@@ -55,7 +256,7 @@ impl<'de> Deserialize<'de> for Position {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         struct PositionVisitor;
 
-        impl Visitor<'_> for PositionVisitor {
+        impl<'de> Visitor<'de> for PositionVisitor {
             type Value = Position;
 
             fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -65,8 +266,91 @@ impl<'de> Deserialize<'de> for Position {
             fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
                 Ok(Position::from(value))
             }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                deserializer.deserialize_u64(self)
+            }
         }
 
-        deserializer.deserialize_u64(PositionVisitor)
+        deserializer.deserialize_newtype_struct(POSITION_TOKEN, PositionVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_world_height() {
+        assert_eq!(WorldHeight::new(-64, 384), Ok(WorldHeight::OVERWORLD));
+        assert_eq!(WorldHeight::new(0, 256), Ok(WorldHeight::LEGACY));
+        assert!(WorldHeight::new(-2032, 4064).is_ok());
+
+        for &(min_y, height) in &[(-2048, 16), (-2032, 4080), (0, 0), (8, 256), (0, 24)] {
+            assert_eq!(
+                WorldHeight::new(min_y, height),
+                Err(PositionError::InvalidWorldHeight { min_y, height })
+            );
+        }
+
+        assert_eq!(WorldHeight::OVERWORLD.min_y(), -64);
+        assert_eq!(WorldHeight::OVERWORLD.max_y(), 319);
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        assert_eq!(
+            Position::new(0, -65, 0, WorldHeight::OVERWORLD),
+            Err(PositionError::OutOfBounds {
+                axis: 'y',
+                value: -65,
+                min: -64,
+                max: 319,
+            })
+        );
+
+        assert_eq!(
+            Position::new(1 << 25, 0, 0, WorldHeight::OVERWORLD),
+            Err(PositionError::OutOfBounds {
+                axis: 'x',
+                value: 1 << 25,
+                min: Position::MIN_XZ,
+                max: Position::MAX_XZ,
+            })
+        );
+
+        let position = Position {
+            x: 0,
+            y: 2048,
+            z: 0,
+        };
+        assert!(position.pack(PositionLayout::Xzy).is_err());
+        assert!(u64::try_from(position).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_check(x: i32, y in -4096..4096, z: i32) {
+            let height = WorldHeight::OVERWORLD;
+            let in_bounds = (Position::MIN_XZ..=Position::MAX_XZ).contains(&x)
+                && (Position::MIN_XZ..=Position::MAX_XZ).contains(&z)
+                && height.contains(y);
+
+            prop_assert_eq!(Position::new(x, y, z, height).is_ok(), in_bounds);
+        }
+
+        #[test]
+        fn test_pack(x in Position::MIN_XZ..=Position::MAX_XZ, y in Position::MIN_Y..=Position::MAX_Y, z in Position::MIN_XZ..=Position::MAX_XZ) {
+            let position = Position { x, y, z };
+
+            for &layout in &[PositionLayout::Xyz, PositionLayout::Xzy] {
+                prop_assert_eq!(Position::unpack(position.pack(layout).unwrap(), layout), position);
+            }
+        }
     }
 }
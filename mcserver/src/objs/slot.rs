@@ -0,0 +1,574 @@
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+};
+
+use serde::{
+    de::{Deserialize, Deserializer},
+    ser::{self, Serialize, Serializer},
+};
+
+use super::{Chat, Identifier, VarInt};
+use crate::{
+    coder::{
+        de::read_raw,
+        error::{Error, Result},
+    },
+    nbt::{self, Compound, Limits, Tag},
+};
+
+/// How item stacks are encoded on the wire.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Debug)]
+pub enum SlotFormat {
+    /// `id: i16` (-1 if empty), then `count: i8`, `damage: i16` and the NBT,
+    /// used before 1.13.
+    Legacy,
+
+    /// `present: bool`, then `id: VarInt`, `count: i8` and the NBT, used from
+    /// 1.13.2 through 1.20.1.
+    #[default]
+    Modern,
+}
+
+impl SlotFormat {
+    /// Returns the format clients speaking `protocol_version` expect, or
+    /// `None` for 1.13 and 1.13.1, which sent ids as shorts without a damage
+    /// value and aren't supported.
+    pub fn for_protocol(protocol_version: i32) -> Option<Self> {
+        // 1.13 was protocol version 393, and 1.13.2 was 404.
+        match protocol_version {
+            i32::MIN..=392 => Some(SlotFormat::Legacy),
+            393..=403 => None,
+            _ => Some(SlotFormat::Modern),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SlotError {
+    /// A stack holds a number of items outside of `1..=ItemStack::MAX_COUNT`.
+    InvalidCount(i8),
+    /// An item id doesn't fit in the legacy format's short.
+    InvalidLegacyItem(i32),
+    /// A `Damage` tag doesn't fit in the legacy format's short.
+    InvalidLegacyDamage(i64),
+    /// The stack's `display` tag isn't a compound, so it can't hold a name
+    /// or lore.
+    InvalidDisplay,
+}
+
+impl std::fmt::Display for SlotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SlotError::InvalidCount(count) => write!(
+                f,
+                "invalid item count {}, expected 1..={}",
+                count,
+                ItemStack::MAX_COUNT
+            ),
+            SlotError::InvalidLegacyItem(item) => {
+                write!(f, "item id {} doesn't fit in a legacy slot", item)
+            }
+            SlotError::InvalidLegacyDamage(damage) => {
+                write!(f, "damage {} doesn't fit in a legacy slot", damage)
+            }
+            SlotError::InvalidDisplay => write!(f, "the item's display tag isn't a compound"),
+        }
+    }
+}
+
+impl std::error::Error for SlotError {}
+
+/// A stack of items.
+///
+/// Damage, names, lore and enchantments all live in the stack's NBT, which is
+/// where 1.13 and later keep them; the legacy format's damage field is
+/// converted to and from the `Damage` tag.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ItemStack {
+    /// The item's protocol id.
+    pub item: i32,
+    count: i8,
+    pub nbt: Compound,
+}
+
+/// The contents of an inventory slot, which may be empty.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Slot(pub Option<ItemStack>);
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Enchantment {
+    pub id: Identifier,
+    pub level: i16,
+}
+
+impl ItemStack {
+    /// Most items a stack can hold, which is also the most vanilla clients
+    /// accept.
+    pub const MAX_COUNT: i8 = 64;
+
+    pub fn new(item: i32, count: i8) -> Result<Self, SlotError> {
+        let mut stack = Self {
+            item,
+            count: 1,
+            nbt: Compound::new(),
+        };
+
+        stack.set_count(count)?;
+        Ok(stack)
+    }
+
+    pub fn count(&self) -> i8 {
+        self.count
+    }
+
+    pub fn set_count(&mut self, count: i8) -> Result<(), SlotError> {
+        if (1..=Self::MAX_COUNT).contains(&count) {
+            self.count = count;
+            Ok(())
+        } else {
+            Err(SlotError::InvalidCount(count))
+        }
+    }
+
+    fn display(&self) -> Option<&Compound> {
+        self.nbt.get("display").and_then(Tag::as_compound)
+    }
+
+    /// Returns the `display` compound, adding it if it's missing.
+    fn display_mut(&mut self) -> Result<&mut Compound, SlotError> {
+        self.nbt
+            .entry("display".to_owned())
+            .or_insert_with(|| Tag::Compound(Compound::new()))
+            .as_compound_mut()
+            .ok_or(SlotError::InvalidDisplay)
+    }
+
+    /// Returns the custom name of the stack, if it has one.
+    pub fn display_name(&self) -> Option<Chat> {
+        self.display()?.get("Name")?.as_str().map(parse_text)
+    }
+
+    pub fn set_display_name(&mut self, name: Option<Chat>) -> Result<(), SlotError> {
+        let display = self.display_mut()?;
+        match name {
+            Some(name) => {
+                let name = Tag::String(name.to_json_string());
+                display.insert("Name".to_owned(), name);
+            }
+            None => {
+                display.remove("Name");
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn lore(&self) -> Vec<Chat> {
+        match self.display().and_then(|display| display.get("Lore")) {
+            Some(Tag::List(lines)) => lines
+                .iter()
+                .filter_map(Tag::as_str)
+                .map(parse_text)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn set_lore(&mut self, lore: Vec<Chat>) -> Result<(), SlotError> {
+        let display = self.display_mut()?;
+        if lore.is_empty() {
+            display.remove("Lore");
+            return Ok(());
+        }
+
+        let lines = lore
+            .iter()
+            .map(|line| Tag::String(line.to_json_string()))
+            .collect();
+
+        display.insert("Lore".to_owned(), Tag::List(lines));
+        Ok(())
+    }
+
+    /// Returns the stack's enchantments, skipping any that are malformed.
+    pub fn enchantments(&self) -> Vec<Enchantment> {
+        let enchantments = match self.nbt.get("Enchantments") {
+            Some(Tag::List(enchantments)) => enchantments,
+            _ => return Vec::new(),
+        };
+
+        enchantments
+            .iter()
+            .filter_map(|enchantment| {
+                let enchantment = enchantment.as_compound()?;
+
+                Some(Enchantment {
+                    id: Identifier::parse(enchantment.get("id")?.as_str()?).ok()?,
+                    level: i16::try_from(enchantment.get("lvl")?.as_i64()?).ok()?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn set_enchantments(&mut self, enchantments: &[Enchantment]) {
+        if enchantments.is_empty() {
+            self.nbt.remove("Enchantments");
+            return;
+        }
+
+        let enchantments = enchantments
+            .iter()
+            .map(|enchantment| {
+                let mut compound = Compound::new();
+                compound.insert("id".to_owned(), Tag::String(enchantment.id.to_string()));
+                compound.insert("lvl".to_owned(), Tag::Short(enchantment.level));
+                Tag::Compound(compound)
+            })
+            .collect();
+
+        self.nbt
+            .insert("Enchantments".to_owned(), Tag::List(enchantments));
+    }
+
+    /// Returns how much durability the stack has lost, or 0 if its `Damage`
+    /// tag is malformed.
+    pub fn damage(&self) -> i32 {
+        self.nbt
+            .get("Damage")
+            .and_then(Tag::as_i64)
+            .and_then(|damage| i32::try_from(damage).ok())
+            .unwrap_or(0)
+    }
+
+    pub fn set_damage(&mut self, damage: i32) {
+        if damage == 0 {
+            self.nbt.remove("Damage");
+        } else {
+            self.nbt.insert("Damage".to_owned(), Tag::Int(damage));
+        }
+    }
+}
+
+/// Parses text stored in item NBT, which is JSON since 1.13 and plain text
+/// with `§` codes before.
+fn parse_text(s: &str) -> Chat {
+    Chat::from_json_str(s).unwrap_or_else(|_| Chat::from_legacy(s))
+}
+
+fn write_nbt<W: Write>(w: &mut W, nbt: &Compound) -> Result<()> {
+    if nbt.is_empty() {
+        nbt::write_end(w)?;
+    } else {
+        nbt::write_named(w, "", &Tag::Compound(nbt.clone()))?;
+    }
+
+    Ok(())
+}
+
+fn read_nbt<R: Read>(r: &mut R) -> Result<Compound> {
+    match nbt::read_named(r, Limits::NETWORK)? {
+        Some((_, Tag::Compound(nbt))) => Ok(nbt),
+        Some(_) => Err(Error::Custom("item NBT must be a compound".to_owned())),
+        None => Ok(Compound::new()),
+    }
+}
+
+impl Slot {
+    pub fn empty() -> Self {
+        Slot(None)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W, format: SlotFormat) -> Result<()> {
+        let stack = match (&self.0, format) {
+            (None, SlotFormat::Legacy) => return Ok(w.write_all(&(-1i16).to_be_bytes())?),
+            (None, SlotFormat::Modern) => return Ok(w.write_all(&[0])?),
+            (Some(stack), _) => stack,
+        };
+
+        match format {
+            SlotFormat::Legacy => {
+                let mut nbt = stack.nbt.clone();
+                let damage = nbt
+                    .remove("Damage")
+                    .and_then(|damage| damage.as_i64())
+                    .unwrap_or(0);
+
+                let item = i16::try_from(stack.item)
+                    .map_err(|_| SlotError::InvalidLegacyItem(stack.item))?;
+                let damage =
+                    i16::try_from(damage).map_err(|_| SlotError::InvalidLegacyDamage(damage))?;
+
+                w.write_all(&item.to_be_bytes())?;
+                w.write_all(&[stack.count as u8])?;
+                w.write_all(&damage.to_be_bytes())?;
+                write_nbt(w, &nbt)
+            }
+
+            SlotFormat::Modern => {
+                w.write_all(&[1])?;
+                VarInt(stack.item).write_to(w)?;
+                w.write_all(&[stack.count as u8])?;
+                write_nbt(w, &stack.nbt)
+            }
+        }
+    }
+
+    pub fn read_from<R: Read>(r: &mut R, format: SlotFormat) -> Result<Self> {
+        let item = match format {
+            SlotFormat::Legacy => {
+                let mut buf = [0; 2];
+                r.read_exact(&mut buf)?;
+                match i16::from_be_bytes(buf) {
+                    -1 => return Ok(Slot(None)),
+                    item => i32::from(item),
+                }
+            }
+
+            SlotFormat::Modern => {
+                let mut buf = [0; 1];
+                r.read_exact(&mut buf)?;
+                match buf[0] {
+                    0 => return Ok(Slot(None)),
+                    1 => VarInt::read_from(r)?.0,
+                    n => return Err(Error::InvalidBooleanValue(n)),
+                }
+            }
+        };
+
+        let mut buf = [0; 1];
+        r.read_exact(&mut buf)?;
+        let mut stack = ItemStack::new(item, buf[0] as i8)?;
+
+        if format == SlotFormat::Legacy {
+            let mut buf = [0; 2];
+            r.read_exact(&mut buf)?;
+            stack.nbt = read_nbt(r)?;
+            stack.set_damage(i16::from_be_bytes(buf).into());
+        } else {
+            stack.nbt = read_nbt(r)?;
+        }
+
+        Ok(Slot(Some(stack)))
+    }
+}
+
+impl From<ItemStack> for Slot {
+    fn from(stack: ItemStack) -> Self {
+        Slot(Some(stack))
+    }
+}
+
+impl From<Option<ItemStack>> for Slot {
+    fn from(stack: Option<ItemStack>) -> Self {
+        Slot(stack)
+    }
+}
+
+/// A slot in the format used before 1.13, for connections that expect it.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct LegacySlot(pub Slot);
+
+fn serialize_slot<S: Serializer>(
+    slot: &Slot,
+    format: SlotFormat,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut buf = Vec::new();
+    slot.write_to(&mut buf, format)
+        .map_err(ser::Error::custom)?;
+    serializer.serialize_bytes(&buf)
+}
+
+impl Serialize for Slot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_slot(self, SlotFormat::Modern, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Slot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        read_raw(deserializer, "an encoded Slot", |mut r| {
+            Slot::read_from(&mut r, SlotFormat::Modern)
+        })
+    }
+}
+
+impl Serialize for LegacySlot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_slot(&self.0, SlotFormat::Legacy, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LegacySlot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        read_raw(deserializer, "an encoded legacy Slot", |mut r| {
+            Slot::read_from(&mut r, SlotFormat::Legacy).map(LegacySlot)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::coder::{de, ser};
+
+    fn sword() -> ItemStack {
+        let mut sword = ItemStack::new(603, 1).unwrap();
+        sword.set_damage(12);
+        sword
+            .set_display_name(Some(Chat::text("Excalibur")))
+            .unwrap();
+        sword
+            .set_lore(vec![Chat::text("Pulled from a stone"), Chat::text("Sharp")])
+            .unwrap();
+        sword.set_enchantments(&[Enchantment {
+            id: Identifier::parse("sharpness").unwrap(),
+            level: 5,
+        }]);
+        sword
+    }
+
+    fn encode(slot: &Slot, format: SlotFormat) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut serializer = ser::Serializer::new(&mut buf);
+        match format {
+            SlotFormat::Legacy => LegacySlot(slot.clone()).serialize(&mut serializer),
+            SlotFormat::Modern => slot.serialize(&mut serializer),
+        }
+        .unwrap();
+        buf
+    }
+
+    fn decode(buf: &[u8], format: SlotFormat) -> Result<Slot> {
+        let mut deserializer = de::Deserializer::new(buf);
+        match format {
+            SlotFormat::Legacy => LegacySlot::deserialize(&mut deserializer).map(|slot| slot.0),
+            SlotFormat::Modern => Slot::deserialize(&mut deserializer),
+        }
+    }
+
+    #[test]
+    fn test_accessors() {
+        let sword = sword();
+
+        assert_eq!(sword.damage(), 12);
+        assert_eq!(sword.display_name(), Some(Chat::text("Excalibur")));
+        assert_eq!(sword.lore().len(), 2);
+        assert_eq!(sword.enchantments()[0].id.as_str(), "minecraft:sharpness");
+        assert_eq!(sword.enchantments()[0].level, 5);
+
+        let mut plain = ItemStack::new(1, 1).unwrap();
+        assert_eq!(plain.display_name(), None);
+        assert!(plain.lore().is_empty() && plain.enchantments().is_empty());
+
+        // Names from before 1.13 are plain text.
+        plain.nbt = nbt::snbt::parse("{display: {Name: '§cHot'}}")
+            .unwrap()
+            .as_compound()
+            .unwrap()
+            .clone();
+        assert_eq!(
+            plain.display_name(),
+            Some(Chat::text("Hot").color(super::super::Color::Red))
+        );
+
+        // Malformed tags are skipped when reading and rejected when writing.
+        let mut odd = ItemStack::new(1, 1).unwrap();
+        odd.nbt = nbt::snbt::parse(
+            "{display: 1, Damage: 3000000000L, Enchantments: [{id: 'sharpness', lvl: 40000}]}",
+        )
+        .unwrap()
+        .as_compound()
+        .unwrap()
+        .clone();
+        assert_eq!(odd.display_name(), None);
+        assert_eq!(odd.set_display_name(None), Err(SlotError::InvalidDisplay));
+        assert_eq!(odd.set_lore(Vec::new()), Err(SlotError::InvalidDisplay));
+        assert!(odd.enchantments().is_empty());
+        assert_eq!(odd.damage(), 0);
+    }
+
+    #[test]
+    fn test_counts() {
+        assert_eq!(ItemStack::new(1, 0), Err(SlotError::InvalidCount(0)));
+        assert_eq!(ItemStack::new(1, 65), Err(SlotError::InvalidCount(65)));
+        assert!(ItemStack::new(1, 64).is_ok());
+
+        assert!(decode(&[1, 1, 0, 0], SlotFormat::Modern).is_err());
+        assert!(decode(&[0x00, 0x01, 0xff, 0x00, 0x00, 0x00], SlotFormat::Legacy).is_err());
+    }
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(encode(&Slot::empty(), SlotFormat::Modern), [0x00]);
+        assert_eq!(encode(&Slot::empty(), SlotFormat::Legacy), [0xff, 0xff]);
+
+        let stone = Slot::from(ItemStack::new(1, 64).unwrap());
+        assert_eq!(encode(&stone, SlotFormat::Modern), [0x01, 0x01, 0x40, 0x00]);
+
+        let mut wool = ItemStack::new(35, 3).unwrap();
+        wool.set_damage(14);
+        assert_eq!(
+            encode(&wool.into(), SlotFormat::Legacy),
+            [0x00, 0x23, 0x03, 0x00, 0x0e, 0x00]
+        );
+
+        for &format in &[SlotFormat::Modern, SlotFormat::Legacy] {
+            let sword = Slot::from(sword());
+            assert_eq!(decode(&encode(&sword, format), format).unwrap(), sword);
+        }
+
+        // Slots are read directly, leaving whatever follows them.
+        let mut buf = encode(&stone, SlotFormat::Modern);
+        buf.push(0x2a);
+        let (slot, next): (Slot, u8) =
+            Deserialize::deserialize(&mut de::Deserializer::new(&buf[..])).unwrap();
+        assert_eq!((slot, next), (stone, 0x2a));
+
+        // Modern item ids may not fit in a short.
+        let big = Slot::from(ItemStack::new(40_000, 1).unwrap());
+        assert_eq!(
+            encode(&big, SlotFormat::Modern)[..4],
+            [0x01, 0xc0, 0xb8, 0x02]
+        );
+        assert!(big.write_to(&mut Vec::new(), SlotFormat::Legacy).is_err());
+
+        let mut worn = ItemStack::new(1, 1).unwrap();
+        worn.set_damage(40_000);
+        let err = Slot::from(worn)
+            .write_to(&mut Vec::new(), SlotFormat::Legacy)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            SlotError::InvalidLegacyDamage(40_000).to_string()
+        );
+    }
+
+    #[test]
+    fn test_for_protocol() {
+        assert_eq!(SlotFormat::for_protocol(340), Some(SlotFormat::Legacy));
+        assert_eq!(SlotFormat::for_protocol(393), None);
+        assert_eq!(SlotFormat::for_protocol(401), None);
+        assert_eq!(SlotFormat::for_protocol(404), Some(SlotFormat::Modern));
+    }
+
+    proptest! {
+        #[test]
+        fn test_roundtrip(item in 0..i16::MAX as i32, count in 1..=64i8, damage: i16, present: bool) {
+            let mut stack = ItemStack::new(item, count).unwrap();
+            stack.set_damage(damage.into());
+            let slot = Slot(Some(stack).filter(|_| present));
+
+            for &format in &[SlotFormat::Modern, SlotFormat::Legacy] {
+                prop_assert_eq!(&decode(&encode(&slot, format), format).unwrap(), &slot);
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+/// A rotation in steps of 1/256 of a full turn, as used for entity yaw, pitch
+/// and head rotation.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default, Serialize, Deserialize)]
+pub struct Angle(pub u8);
+
+/// An entity's velocity, in 1/8000 of a block per tick along each axis.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default, Serialize, Deserialize)]
+pub struct Velocity {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+/// A coordinate in 1/32 of a block, as used by entity packets before 1.9.
+#[derive(
+    Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Debug, Default, Serialize, Deserialize,
+)]
+pub struct FixedPoint(pub i32);
+
+impl Angle {
+    /// Converts an angle in degrees, wrapping it into a single turn.
+    ///
+    /// Like vanilla this rounds down, so e.g. 359.9° becomes 255 rather than
+    /// wrapping around to 0.
+    pub fn from_degrees(degrees: f32) -> Self {
+        Angle((degrees * 256.0 / 360.0).floor() as i32 as u8)
+    }
+
+    /// Returns the angle in degrees, within `0.0..360.0`.
+    pub fn to_degrees(self) -> f32 {
+        f32::from(self.0) * 360.0 / 256.0
+    }
+}
+
+impl Velocity {
+    /// Fastest speed along an axis that can be sent, in blocks per tick.
+    ///
+    /// Vanilla clamps to this before converting, even though the wire format
+    /// could fit slightly more.
+    pub const MAX_BLOCKS_PER_TICK: f64 = 3.9;
+
+    /// Converts a velocity in blocks per tick, clamping every axis to
+    /// `MAX_BLOCKS_PER_TICK` and rounding towards zero like vanilla.
+    pub fn from_blocks_per_tick(x: f64, y: f64, z: f64) -> Self {
+        let convert = |v: f64| {
+            let max = Self::MAX_BLOCKS_PER_TICK;
+            (v.clamp(-max, max) * 8000.0) as i16
+        };
+
+        Self {
+            x: convert(x),
+            y: convert(y),
+            z: convert(z),
+        }
+    }
+
+    /// Returns the velocity along each axis in blocks per tick.
+    pub fn to_blocks_per_tick(self) -> (f64, f64, f64) {
+        let convert = |v: i16| f64::from(v) / 8000.0;
+
+        (convert(self.x), convert(self.y), convert(self.z))
+    }
+}
+
+impl FixedPoint {
+    /// Number of bits used for the fractional part.
+    pub const FRACTION_BITS: u32 = 5;
+
+    /// Converts a coordinate in blocks, rounding down like vanilla did.
+    ///
+    /// Coordinates too big to be represented saturate, which only happens
+    /// far beyond the world border.
+    pub fn from_blocks(blocks: f64) -> Self {
+        FixedPoint((blocks * f64::from(1 << Self::FRACTION_BITS)).floor() as i32)
+    }
+
+    pub fn to_blocks(self) -> f64 {
+        f64::from(self.0) / f64::from(1 << Self::FRACTION_BITS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::coder::ser::Serializer;
+
+    #[test]
+    fn test_angle() {
+        assert_eq!(Angle::from_degrees(0.0), Angle(0));
+        assert_eq!(Angle::from_degrees(90.0), Angle(64));
+        assert_eq!(Angle::from_degrees(-90.0), Angle(192));
+        assert_eq!(Angle::from_degrees(359.9), Angle(255));
+        assert_eq!(Angle::from_degrees(360.0), Angle(0));
+        assert_eq!(Angle::from_degrees(-0.1), Angle(255));
+        assert_eq!(Angle(128).to_degrees(), 180.0);
+    }
+
+    #[test]
+    fn test_velocity() {
+        assert_eq!(
+            Velocity::from_blocks_per_tick(0.5, -10.0, -0.00001),
+            Velocity {
+                x: 4000,
+                y: -31200,
+                z: 0,
+            }
+        );
+        assert_eq!(
+            Velocity {
+                x: 8000,
+                y: -4000,
+                z: 1
+            }
+            .to_blocks_per_tick(),
+            (1.0, -0.5, 0.000125)
+        );
+    }
+
+    #[test]
+    fn test_fixed_point() {
+        assert_eq!(FixedPoint::from_blocks(1.5), FixedPoint(48));
+        assert_eq!(FixedPoint::from_blocks(-0.01), FixedPoint(-1));
+        assert_eq!(FixedPoint::from_blocks(1e300), FixedPoint(i32::MAX));
+        assert_eq!(FixedPoint(-16).to_blocks(), -0.5);
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let mut buf = Vec::new();
+        let mut serializer = Serializer::new(&mut buf);
+
+        (
+            Angle(0x80),
+            Velocity {
+                x: 1,
+                y: -1,
+                z: 0x1234,
+            },
+            FixedPoint(-2),
+        )
+            .serialize(&mut serializer)
+            .unwrap();
+
+        assert_eq!(
+            buf,
+            [0x80, 0x00, 0x01, 0xff, 0xff, 0x12, 0x34, 0xff, 0xff, 0xff, 0xfe]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_angle_roundtrip(n: u8) {
+            prop_assert_eq!(Angle::from_degrees(Angle(n).to_degrees()), Angle(n));
+        }
+
+        #[test]
+        fn test_angle_precision(degrees in 0.0f32..360.0) {
+            let error = degrees - Angle::from_degrees(degrees).to_degrees();
+            prop_assert!((0.0..360.0 / 256.0).contains(&error));
+        }
+
+        #[test]
+        fn test_velocity_precision(x: i16) {
+            // Converting back truncates, so it may land one step closer to zero.
+            let max = (Velocity::MAX_BLOCKS_PER_TICK * 8000.0) as i16;
+            let x = x.clamp(-max, max);
+
+            let (blocks, _, _) = Velocity { x, y: 0, z: 0 }.to_blocks_per_tick();
+            let converted = Velocity::from_blocks_per_tick(blocks, 0.0, 0.0).x;
+            prop_assert!(converted == x || converted == x - x.signum());
+        }
+
+        #[test]
+        fn test_fixed_point_roundtrip(n: i32) {
+            prop_assert_eq!(FixedPoint::from_blocks(FixedPoint(n).to_blocks()), FixedPoint(n));
+        }
+
+        #[test]
+        fn test_fixed_point_precision(blocks in -1e6f64..1e6) {
+            let error = blocks - FixedPoint::from_blocks(blocks).to_blocks();
+            prop_assert!((0.0..1.0 / 32.0).contains(&error));
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::str::FromStr;
+
+use serde::{
+    de::{self, Deserialize, Deserializer, Visitor},
+    ser::{Serialize, Serializer},
+};
+
+/// A UUID, which the protocol sends as 128 bits.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Default)]
+pub struct Uuid(pub u128);
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseUuidError(pub String);
+
+impl std::fmt::Display for ParseUuidError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid UUID {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseUuidError {}
+
+impl Uuid {
+    pub const NIL: Self = Uuid(0);
+
+    /// Creates a UUID from the four ints NBT stores it as since 1.16, most
+    /// significant first.
+    pub fn from_int_array(ints: [i32; 4]) -> Self {
+        Uuid(
+            ints.iter()
+                .fold(0, |uuid, &n| (uuid << 32) | u128::from(n as u32)),
+        )
+    }
+
+    pub fn to_int_array(self) -> [i32; 4] {
+        let mut ints = [0; 4];
+        for (i, n) in ints.iter_mut().enumerate() {
+            *n = (self.0 >> (96 - 32 * i)) as i32;
+        }
+        ints
+    }
+
+    /// Creates a UUID from the two longs NBT stored it as before 1.16.
+    pub fn from_most_least(most: i64, least: i64) -> Self {
+        Uuid((u128::from(most as u64) << 64) | u128::from(least as u64))
+    }
+
+    pub fn most_significant(self) -> i64 {
+        (self.0 >> 64) as i64
+    }
+
+    pub fn least_significant(self) -> i64 {
+        self.0 as i64
+    }
+}
+
+/// Formats the UUID with hyphens, like `069a79f4-44e9-4726-a5be-fca90e38aaf5`.
+impl std::fmt::Display for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let hex = format!("{:032x}", self.0);
+
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+impl std::fmt::Debug for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Uuid({})", self)
+    }
+}
+
+/// Parses a UUID with or without hyphens, as Mojang's API leaves them out.
+impl FromStr for Uuid {
+    type Err = ParseUuidError;
+
+    fn from_str(s: &str) -> Result<Self, ParseUuidError> {
+        let err = || ParseUuidError(s.to_owned());
+
+        let hex: String = match s.len() {
+            32 => s.to_owned(),
+            36 if [8, 13, 18, 23].iter().all(|&i| s.as_bytes()[i] == b'-') => {
+                s.split('-').collect()
+            }
+            _ => return Err(err()),
+        };
+
+        if hex.len() != 32 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(err());
+        }
+
+        u128::from_str_radix(&hex, 16).map(Uuid).map_err(|_| err())
+    }
+}
+
+impl Serialize for Uuid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u128(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Uuid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UuidVisitor;
+
+        impl<'de> Visitor<'de> for UuidVisitor {
+            type Value = Uuid;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a 128 bit UUID")
+            }
+
+            fn visit_u128<E: de::Error>(self, value: u128) -> Result<Self::Value, E> {
+                Ok(Uuid(value))
+            }
+        }
+
+        deserializer.deserialize_u128(UuidVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let uuid: Uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5".parse().unwrap();
+        assert_eq!(uuid, Uuid(0x069a79f4_44e9_4726_a5be_fca90e38aaf5));
+        assert_eq!("069a79f444e94726a5befca90e38aaf5".parse(), Ok(uuid));
+        assert_eq!(uuid.to_string(), "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+
+        for invalid in &[
+            "",
+            "069a79f4-44e9-4726-a5be-fca90e38aaf",
+            "069a79f444e94726a5befca90e38aafg",
+            "069a79f4-44e94-726-a5be-fca90e38aaf5",
+            "+69a79f444e94726a5befca90e38aaf5",
+        ] {
+            assert!(invalid.parse::<Uuid>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_nbt_forms() {
+        let uuid = Uuid(0x069a79f4_44e9_4726_a5be_fca90e38aaf5);
+        assert_eq!(
+            uuid.to_int_array(),
+            [0x069a79f4, 0x44e94726, 0xa5befca9_u32 as i32, 0x0e38aaf5]
+        );
+        assert_eq!(uuid.most_significant(), 0x069a79f4_44e94726);
+    }
+
+    proptest! {
+        #[test]
+        fn test_roundtrip(n: u128) {
+            let uuid = Uuid(n);
+            prop_assert_eq!(uuid.to_string().parse(), Ok(uuid));
+            prop_assert_eq!(Uuid::from_int_array(uuid.to_int_array()), uuid);
+            prop_assert_eq!(Uuid::from_most_least(uuid.most_significant(), uuid.least_significant()), uuid);
+        }
+    }
+}
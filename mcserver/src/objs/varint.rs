@@ -1,37 +1,91 @@
+use std::io::{self, Read, Write};
+
 use serde::{
     de::{self, Deserialize, Deserializer, Visitor},
     ser::{Serialize, Serializer},
 };
 
+use crate::coder::error::{Error, Result};
+
 macro_rules! coder_varint_impl {
-    ($($ty:ident: $inner_ty:ty),*) => {
+    ($($ty:ident: $inner_ty:ty as $unsigned_ty:ty => $token:ident),*) => {
         $(
             #[derive(Default, Eq, PartialEq, Debug, Clone, Copy)]
             pub struct $ty(pub $inner_ty);
 
-            impl Serialize for $ty {
-                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-                    const MAX_BYTE_SIZE: usize = 1 + std::mem::size_of::<$ty>() * 8 / 7;
+            /// Newtype struct name our own deserializer recognizes, so that it
+            /// can read the value directly instead of byte by byte.
+            pub(crate) const $token: &str = concat!("$mcserver::", stringify!($ty));
+
+            impl $ty {
+                /// Maximum amount of bytes a value can take up on the wire.
+                pub const MAX_BYTE_SIZE: usize = 1 + std::mem::size_of::<$inner_ty>() * 8 / 7;
+
+                fn encode(self) -> smallvec::SmallVec<[u8; <$ty>::MAX_BYTE_SIZE]> {
+                    let mut buf = smallvec::SmallVec::new();
 
-                    if self.0 == 0 {
-                        return serializer.serialize_bytes(&[0u8] as &[_]);
+                    // Negative values are encoded through their two's
+                    // complement representation, like vanilla does, so that
+                    // shifting them right eventually reaches zero.
+                    let mut rest = self.0 as $unsigned_ty;
+
+                    while rest > lsb!(7) {
+                        buf.push((rest & lsb!(7)) as u8 | 0b10_00_00_00);
+                        rest >>= 7;
                     }
 
-                    let mut buf = smallvec::SmallVec::<[u8; MAX_BYTE_SIZE]>::new();
+                    buf.push(rest as u8);
+
+                    buf
+                }
 
-                    for i in 0..MAX_BYTE_SIZE {
-                        let rest = self.0 >> (7 * i);
-                        if rest == 0 {
+                /// Decodes a value from the bytes yielded by `next_byte`,
+                /// rejecting encodings that are longer than `MAX_BYTE_SIZE` or
+                /// longer than they need to be.
+                fn decode<E>(
+                    mut next_byte: impl FnMut() -> Result<u8, E>,
+                    humongous: impl FnOnce() -> E,
+                ) -> Result<Self, E> {
+                    let mut result: $inner_ty = 0;
+
+                    for i in 0..Self::MAX_BYTE_SIZE {
+                        let byte = next_byte()?;
+
+                        // A zero byte after the first one only adds zeros,
+                        // which means the encoding is over-long.
+                        if i != 0 && byte == 0 {
                             break;
                         }
 
-                        let lower = (rest & lsb!(7)) as u8;
-                        buf.push(lower | 0b10_00_00_00);
+                        result |= ((byte & 0b01_11_11_11) as $inner_ty) << (7 * i);
+
+                        if byte & 0b10_00_00_00 == 0 {
+                            return Ok(Self(result));
+                        }
                     }
 
-                    *buf.last_mut().unwrap() ^= 0b10_00_00_00;
+                    Err(humongous())
+                }
+
+                pub fn write_to<W: Write>(self, w: &mut W) -> io::Result<()> {
+                    w.write_all(&self.encode())
+                }
+
+                pub fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+                    Self::decode(
+                        || {
+                            let mut buf = [0; 1];
+                            r.read_exact(&mut buf)?;
+                            Ok(buf[0])
+                        },
+                        || Error::HumongousVarInt,
+                    )
+                }
+            }
 
-                    serializer.serialize_bytes(&buf)
+            impl Serialize for $ty {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_bytes(&self.encode())
                 }
             }
 
@@ -46,28 +100,39 @@ macro_rules! coder_varint_impl {
                             write!(f, "A sequence of VarInt-encoded bytes")
                         }
 
-                        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
-                            let mut result = <$ty>::default();
-
-                            for i in 0.. {
-                                let byte: u8 = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(i + 1, &self))?;
+                        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                            use std::convert::TryFrom;
 
-                                result.0 |= ((byte & 0b01_11_11_11) as $inner_ty).checked_shl(7 * i as u32).ok_or_else(|| de::Error::invalid_length(i + 1, &self))?;
+                            <$inner_ty>::try_from(value)
+                                .map($ty)
+                                .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+                        }
 
-                                if byte & 0b10_00_00_00 == 0 {
-                                    break;
-                                }
-                            }
+                        fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+                            deserializer.deserialize_seq(self)
+                        }
 
-                            return Ok(result);
+                        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                            let mut i = 0;
+
+                            <$ty>::decode(
+                                || {
+                                    i += 1;
+                                    seq.next_element()?.ok_or_else(|| de::Error::invalid_length(i, &self))
+                                },
+                                || de::Error::custom(Error::HumongousVarInt),
+                            )
                         }
                     }
 
-                    deserializer.deserialize_seq(VarIntVisitor)
+                    deserializer.deserialize_newtype_struct($token, VarIntVisitor)
                 }
             }
         )*
     }
 }
 
-coder_varint_impl!(VarInt: i32, VarLong: i64);
+coder_varint_impl!(
+    VarInt: i32 as u32 => VARINT_TOKEN,
+    VarLong: i64 as u64 => VARLONG_TOKEN
+);
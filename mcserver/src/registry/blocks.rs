@@ -0,0 +1,461 @@
+use super::super::objs::Identifier;
+
+/// A property of a block and the values it can take, in the order vanilla
+/// numbers them.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Property {
+    pub name: &'static str,
+    pub values: &'static [&'static str],
+}
+
+/// A block, whose states take up consecutive ids starting from
+/// `first_state`, with the last property changing fastest.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Block {
+    name: &'static str,
+    first_state: u32,
+    default_state: u32,
+    properties: &'static [Property],
+}
+
+impl Block {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn properties(&self) -> &'static [Property] {
+        self.properties
+    }
+
+    pub fn state_count(&self) -> u32 {
+        self.properties
+            .iter()
+            .map(|property| property.values.len() as u32)
+            .product()
+    }
+
+    pub fn default_state(&'static self) -> BlockState {
+        BlockState {
+            block: self,
+            id: self.default_state,
+        }
+    }
+
+    pub fn states(&'static self) -> impl Iterator<Item = BlockState> {
+        (self.first_state..self.first_state + self.state_count())
+            .map(move |id| BlockState { block: self, id })
+    }
+
+    fn property(&self, name: &str) -> Option<(usize, &'static Property)> {
+        self.properties
+            .iter()
+            .enumerate()
+            .find(|(_, property)| property.name == name)
+    }
+
+    /// Returns how many states apart two consecutive values of a property
+    /// are.
+    fn stride(&self, index: usize) -> u32 {
+        self.properties[index + 1..]
+            .iter()
+            .map(|property| property.values.len() as u32)
+            .product()
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum BlockStateError {
+    Syntax(String),
+    UnknownBlock(String),
+    UnknownProperty {
+        block: &'static str,
+        property: String,
+    },
+    InvalidValue {
+        property: &'static str,
+        value: String,
+    },
+}
+
+impl std::fmt::Display for BlockStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BlockStateError::Syntax(s) => write!(f, "invalid block state {:?}", s),
+            BlockStateError::UnknownBlock(name) => write!(f, "unknown block {}", name),
+            BlockStateError::UnknownProperty { block, property } => {
+                write!(f, "{} has no property {}", block, property)
+            }
+            BlockStateError::InvalidValue { property, value } => {
+                write!(f, "{} can't be {:?}", property, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockStateError {}
+
+/// A state of a block, i.e. the block with a value for each of its
+/// properties.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockState {
+    block: &'static Block,
+    id: u32,
+}
+
+// Blocks are compared by address, as each is only ever in one registry.
+impl PartialEq for BlockState {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.block, other.block) && self.id == other.id
+    }
+}
+
+impl Eq for BlockState {}
+
+impl std::hash::Hash for BlockState {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl BlockState {
+    /// Returns the global id of this state, as sent in packets and chunks.
+    pub fn id(self) -> u32 {
+        self.id
+    }
+
+    pub fn block(self) -> &'static Block {
+        self.block
+    }
+
+    pub fn is_default(self) -> bool {
+        self.id == self.block.default_state
+    }
+
+    fn value_index(self, index: usize) -> usize {
+        let property = &self.block.properties[index];
+        ((self.id - self.block.first_state) / self.block.stride(index)) as usize
+            % property.values.len()
+    }
+
+    /// Returns the value of a property, or `None` if the block doesn't have
+    /// it.
+    pub fn get(self, property: &str) -> Option<&'static str> {
+        let (index, property) = self.block.property(property)?;
+        Some(property.values[self.value_index(index)])
+    }
+
+    pub fn properties(self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        self.block
+            .properties
+            .iter()
+            .enumerate()
+            .map(move |(index, property)| (property.name, property.values[self.value_index(index)]))
+    }
+
+    /// Returns this state with a property changed to another value.
+    pub fn with(self, property: &str, value: &str) -> Result<Self, BlockStateError> {
+        let (index, definition) =
+            self.block
+                .property(property)
+                .ok_or_else(|| BlockStateError::UnknownProperty {
+                    block: self.block.name,
+                    property: property.to_owned(),
+                })?;
+
+        let position = definition
+            .values
+            .iter()
+            .position(|&v| v == value)
+            .ok_or_else(|| BlockStateError::InvalidValue {
+                property: definition.name,
+                value: value.to_owned(),
+            })?;
+
+        Ok(self.with_value_index(index, position))
+    }
+
+    /// Returns this state with a property changed to its next value, wrapping
+    /// around like the debug stick does, or `None` if the block doesn't have
+    /// it.
+    pub fn cycle(self, property: &str) -> Option<Self> {
+        let (index, definition) = self.block.property(property)?;
+        let next = (self.value_index(index) + 1) % definition.values.len();
+        Some(self.with_value_index(index, next))
+    }
+
+    fn with_value_index(self, index: usize, position: usize) -> Self {
+        let stride = self.block.stride(index);
+        let current = self.value_index(index) as u32;
+
+        Self {
+            block: self.block,
+            id: self.id - current * stride + position as u32 * stride,
+        }
+    }
+}
+
+/// Formats the state like commands take it, e.g.
+/// `minecraft:oak_stairs[facing=north,half=top,shape=straight,waterlogged=false]`.
+impl std::fmt::Display for BlockState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.block.name)?;
+
+        for (i, (name, value)) in self.properties().enumerate() {
+            let separator = if i == 0 { '[' } else { ',' };
+            write!(f, "{}{}={}", separator, name, value)?;
+        }
+
+        if !self.block.properties.is_empty() {
+            f.write_str("]")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Every block and block state of a protocol version.
+#[derive(Debug)]
+pub struct BlockRegistry {
+    protocol_version: i32,
+    /// Ordered by their first state.
+    blocks: &'static [Block],
+    /// Indices of `blocks`, ordered by name.
+    by_name: &'static [u16],
+}
+
+include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
+
+impl BlockRegistry {
+    /// Returns the registry for a protocol version, if its report was
+    /// included.
+    pub fn for_protocol(protocol_version: i32) -> Option<&'static Self> {
+        REGISTRIES
+            .iter()
+            .find(|registry| registry.protocol_version == protocol_version)
+    }
+
+    /// Returns the protocol versions that have a registry.
+    pub fn protocol_versions() -> impl Iterator<Item = i32> {
+        REGISTRIES.iter().map(|registry| registry.protocol_version)
+    }
+
+    pub fn protocol_version(&self) -> i32 {
+        self.protocol_version
+    }
+
+    pub fn blocks(&'static self) -> &'static [Block] {
+        self.blocks
+    }
+
+    /// Returns how many state ids there are, which is one more than the
+    /// highest.
+    pub fn state_count(&self) -> u32 {
+        self.blocks
+            .last()
+            .map_or(0, |block| block.first_state + block.state_count())
+    }
+
+    /// Returns how many bits a state id takes when chunks use the global
+    /// palette.
+    pub fn bits_per_state(&self) -> u8 {
+        (32 - self.state_count().saturating_sub(1).leading_zeros()) as u8
+    }
+
+    pub fn state(&'static self, id: u32) -> Option<BlockState> {
+        let index = match self
+            .blocks
+            .binary_search_by_key(&id, |block| block.first_state)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+
+        let block = &self.blocks[index];
+        if id - block.first_state < block.state_count() {
+            Some(BlockState { block, id })
+        } else {
+            None
+        }
+    }
+
+    /// Looks up a block by name, where the namespace defaults to `minecraft`.
+    pub fn block(&'static self, name: &str) -> Option<&'static Block> {
+        let name = Identifier::parse(name).ok()?;
+
+        self.by_name
+            .binary_search_by_key(&name.as_str(), |&index| {
+                self.blocks[usize::from(index)].name
+            })
+            .ok()
+            .map(|index| &self.blocks[usize::from(self.by_name[index])])
+    }
+
+    /// Parses a state like commands take it, where properties that aren't
+    /// given keep their default value.
+    pub fn parse(&'static self, s: &str) -> Result<BlockState, BlockStateError> {
+        let syntax = || BlockStateError::Syntax(s.to_owned());
+
+        let (name, properties) = match s.find('[') {
+            Some(start) => {
+                let properties = s[start + 1..].strip_suffix(']').ok_or_else(syntax)?;
+                (&s[..start], Some(properties))
+            }
+            None => (s, None),
+        };
+
+        let block = self
+            .block(name)
+            .ok_or_else(|| BlockStateError::UnknownBlock(name.to_owned()))?;
+        let mut state = block.default_state();
+
+        let properties = properties.filter(|properties| !properties.trim().is_empty());
+        for property in properties.iter().flat_map(|p| p.split(',')) {
+            let mut parts = property.splitn(2, '=');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => return Err(syntax()),
+            };
+
+            state = state.with(key, value)?;
+        }
+
+        Ok(state)
+    }
+
+    /// Returns the blocks of the 1.13.2 registry as if they were those of
+    /// another protocol version, to test encodings whose report isn't
+    /// included.
+    #[cfg(test)]
+    pub(crate) fn for_test(protocol_version: i32) -> &'static Self {
+        let registry = Self::for_protocol(404).unwrap();
+        if protocol_version == registry.protocol_version {
+            return registry;
+        }
+
+        Box::leak(Box::new(Self {
+            protocol_version,
+            blocks: registry.blocks,
+            by_name: registry.by_name,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> &'static BlockRegistry {
+        BlockRegistry::for_protocol(404).unwrap()
+    }
+
+    #[test]
+    fn test_lookups() {
+        let registry = registry();
+        assert!(BlockRegistry::for_protocol(1).is_none());
+
+        assert_eq!(registry.state(0).unwrap().to_string(), "minecraft:air");
+        assert_eq!(
+            registry.state(9).unwrap().to_string(),
+            "minecraft:grass_block[snowy=false]"
+        );
+        assert!(registry.state(9).unwrap().is_default());
+        assert_eq!(registry.state(35).unwrap().get("level"), Some("1"));
+        assert_eq!(
+            registry.state(100).unwrap().to_string(),
+            "minecraft:stripped_acacia_log[axis=y]"
+        );
+
+        let stairs = registry.block("oak_stairs").unwrap();
+        assert_eq!(stairs.state_count(), 80);
+        assert_eq!(stairs.default_state().id(), 1660);
+        assert_eq!(registry.state(1649).unwrap().block(), stairs);
+        assert_eq!(
+            registry.state(1729).unwrap().block().name(),
+            "minecraft:chest"
+        );
+        assert_eq!(
+            registry.state(8598).unwrap().to_string(),
+            "minecraft:structure_block[mode=data]"
+        );
+        assert_eq!(registry.state(8599), None);
+        assert_eq!(registry.state_count(), 8599);
+        assert_eq!(registry.bits_per_state(), 14);
+
+        assert!(registry.block("minecraft:stone").is_some());
+        assert!(registry.block("stone_bricks").is_some());
+        assert!(registry.block("marble").is_none());
+        assert!(registry.block("Not An Id").is_none());
+    }
+
+    #[test]
+    fn test_parse() {
+        let registry = registry();
+
+        let state = registry
+            .parse("minecraft:oak_stairs[facing=north,half=top]")
+            .unwrap();
+        assert_eq!(state.id(), 1650);
+        assert_eq!(state.get("shape"), Some("straight"));
+        assert_eq!(state.get("waterlogged"), Some("false"));
+        assert_eq!(state.get("snowy"), None);
+
+        assert_eq!(registry.parse("stone").unwrap().id(), 1);
+        assert_eq!(registry.parse("stone[]").unwrap().id(), 1);
+        assert_eq!(registry.parse("water[ level = 15 ]").unwrap().id(), 49);
+
+        assert_eq!(
+            registry.parse("oak_stairs[facing=up]"),
+            Err(BlockStateError::InvalidValue {
+                property: "facing",
+                value: "up".to_owned()
+            })
+        );
+        assert_eq!(
+            registry.parse("stone[snowy=true]"),
+            Err(BlockStateError::UnknownProperty {
+                block: "minecraft:stone",
+                property: "snowy".to_owned()
+            })
+        );
+        assert_eq!(
+            registry.parse("marble"),
+            Err(BlockStateError::UnknownBlock("marble".to_owned()))
+        );
+        assert!(registry.parse("oak_stairs[facing").is_err());
+        assert!(registry.parse("oak_stairs[facing]").is_err());
+    }
+
+    #[test]
+    fn test_property_changes() {
+        let registry = registry();
+        let stairs = registry.block("oak_stairs").unwrap().default_state();
+
+        let east = stairs.with("facing", "east").unwrap();
+        assert_eq!(east.get("facing"), Some("east"));
+        assert_eq!(east.get("half"), Some("bottom"));
+        assert_eq!(east.with("facing", "north").unwrap(), stairs);
+
+        let mut state = stairs;
+        for &facing in &["south", "west", "east", "north"] {
+            state = state.cycle("facing").unwrap();
+            assert_eq!(state.get("facing"), Some(facing));
+        }
+        assert_eq!(state, stairs);
+        assert_eq!(stairs.cycle("level"), None);
+    }
+
+    #[test]
+    fn test_every_state() {
+        for version in BlockRegistry::protocol_versions() {
+            let registry = BlockRegistry::for_protocol(version).unwrap();
+
+            for block in registry.blocks() {
+                for state in block.states() {
+                    assert_eq!(registry.state(state.id()), Some(state));
+                    assert_eq!(registry.parse(&state.to_string()), Ok(state));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use super::super::objs::Identifier;
+
+/// A registry mapping the names of things like items or sounds to the ids
+/// packets refer to them by.
+#[derive(Debug)]
+pub struct Registry {
+    name: &'static str,
+    default: Option<&'static str>,
+    /// Ordered by id.
+    entries: &'static [(&'static str, i32)],
+    /// Indices of `entries`, ordered by name.
+    by_name: &'static [u16],
+}
+
+impl Registry {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the entry vanilla falls back to for unknown names, if any.
+    pub fn default(&self) -> Option<Identifier> {
+        self.default.map(Self::identifier)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Names come from vanilla's reports, so they're always valid.
+    fn identifier(name: &'static str) -> Identifier {
+        Identifier::from_static(name).expect("invalid identifier in a data report")
+    }
+
+    pub fn id(&self, name: &Identifier) -> Option<i32> {
+        self.by_name
+            .binary_search_by_key(&name.as_str(), |&index| self.entries[usize::from(index)].0)
+            .ok()
+            .map(|index| self.entries[usize::from(self.by_name[index])].1)
+    }
+
+    pub fn get(&self, id: i32) -> Option<Identifier> {
+        self.entries
+            .binary_search_by_key(&id, |&(_, id)| id)
+            .ok()
+            .map(|index| Self::identifier(self.entries[index].0))
+    }
+
+    /// Returns every entry, ordered by id.
+    pub fn iter(&self) -> impl Iterator<Item = (Identifier, i32)> + '_ {
+        self.entries
+            .iter()
+            .map(|&(name, id)| (Self::identifier(name), id))
+    }
+}
+
+/// The registries of a protocol version.
+#[derive(Debug)]
+pub struct Registries {
+    protocol_version: i32,
+    /// Ordered by name.
+    registries: &'static [Registry],
+}
+
+include!(concat!(env!("OUT_DIR"), "/registries.rs"));
+
+impl Registries {
+    /// Returns the registries of a protocol version, if its report was
+    /// included.
+    pub fn for_protocol(protocol_version: i32) -> Option<&'static Self> {
+        REGISTRIES
+            .iter()
+            .find(|registries| registries.protocol_version == protocol_version)
+    }
+
+    pub fn protocol_version(&self) -> i32 {
+        self.protocol_version
+    }
+
+    pub fn get(&self, name: &Identifier) -> Option<&'static Registry> {
+        self.registries
+            .binary_search_by_key(&name.as_str(), |registry| registry.name)
+            .ok()
+            .map(|index| &self.registries[index])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'static Registry> {
+        self.registries.iter()
+    }
+
+    // The build script makes sure these are in every report.
+    fn required(&self, name: &'static str) -> &'static Registry {
+        self.get(&Identifier::from_static(name).unwrap())
+            .expect("the build script checks for required registries")
+    }
+
+    pub fn items(&self) -> &'static Registry {
+        self.required("minecraft:item")
+    }
+
+    pub fn entity_types(&self) -> &'static Registry {
+        self.required("minecraft:entity_type")
+    }
+
+    pub fn biomes(&self) -> &'static Registry {
+        self.required("minecraft:biome")
+    }
+
+    pub fn sounds(&self) -> &'static Registry {
+        self.required("minecraft:sound_event")
+    }
+
+    pub fn particles(&self) -> &'static Registry {
+        self.required("minecraft:particle_type")
+    }
+
+    pub fn enchantments(&self) -> &'static Registry {
+        self.required("minecraft:enchantment")
+    }
+
+    pub fn mob_effects(&self) -> &'static Registry {
+        self.required("minecraft:mob_effect")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> Identifier {
+        Identifier::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_lookups() {
+        let registries = Registries::for_protocol(404).unwrap();
+        assert!(Registries::for_protocol(1).is_none());
+
+        let items = registries.items();
+        assert_eq!(items.id(&id("stone")), Some(1));
+        assert_eq!(items.get(1), Some(id("minecraft:stone")));
+        assert_eq!(items.id(&id("mod:stone")), None);
+        assert_eq!(items.get(-1), None);
+        assert_eq!(items.default(), Some(id("air")));
+
+        assert_eq!(registries.entity_types().id(&id("creeper")), Some(10));
+        assert_eq!(registries.biomes().get(1), Some(id("plains")));
+        assert_eq!(registries.sounds().id(&id("ambient.cave")), Some(0));
+        assert_eq!(registries.particles().id(&id("dust")), Some(11));
+        assert_eq!(registries.enchantments().id(&id("sharpness")), Some(11));
+
+        // The full report, not just the first entries of each registry.
+        assert_eq!(items.id(&id("diamond_sword")), Some(493));
+        assert_eq!(items.get(789), Some(id("heart_of_the_sea")));
+        assert_eq!(items.get(790), None);
+        assert_eq!(registries.entity_types().id(&id("trident")), Some(94));
+        assert_eq!(registries.biomes().id(&id("the_void")), Some(127));
+        assert_eq!(registries.biomes().get(128), None);
+        assert_eq!(registries.sounds().id(&id("weather.rain.above")), Some(661));
+        assert_eq!(registries.particles().id(&id("dolphin")), Some(49));
+        assert_eq!(registries.mob_effects().id(&id("dolphins_grace")), Some(30));
+
+        // Mob effects start at 1.
+        assert_eq!(registries.mob_effects().id(&id("speed")), Some(1));
+        assert_eq!(registries.mob_effects().get(0), None);
+
+        assert!(registries.get(&id("minecraft:fluid")).is_none());
+        assert_eq!(
+            registries.get(&id("item")).map(Registry::name),
+            Some("minecraft:item")
+        );
+    }
+
+    #[test]
+    fn test_every_entry() {
+        for registries in REGISTRIES {
+            for registry in registries.iter() {
+                for (name, entry) in registry.iter() {
+                    assert_eq!(registry.id(&name), Some(entry));
+                    assert_eq!(registry.get(entry), Some(name));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! Registries mapping names to the numeric ids packets use, which change
+//! between protocol versions.
+//!
+//! They are generated at build time from the vanilla data reports in `data/`,
+//! one directory per protocol version.
+
+mod blocks;
+mod ids;
+
+pub use blocks::{Block, BlockRegistry, BlockState, BlockStateError, Property};
+pub use ids::{Registries, Registry};
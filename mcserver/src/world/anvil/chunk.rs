@@ -0,0 +1,1004 @@
+use std::convert::TryFrom;
+
+use super::{
+    super::{
+        super::{
+            nbt::{Compound, Tag},
+            objs::{ChunkPos, Identifier, PackedArray, Packing, WorldHeight, CHUNK_SIZE},
+            registry::{BlockRegistry, BlockState, BlockStateError, Registry},
+        },
+        ChunkColumn, ChunkLight, LightArray, Palette, PalettedContainer, LIGHT_ARRAY_SIZE,
+    },
+    tags::{get, get_compound, get_int, get_list, get_long_array, get_string},
+    AnvilError, Result,
+};
+
+/// The data version of 1.13, the first with block states instead of numeric
+/// ids.
+const FLATTENING: i32 = 1519;
+
+/// Biomes became 3D in 19w36a, ahead of 1.15.
+const BIOMES_3D: i32 = 2203;
+
+/// Block states are padded since 20w17a, ahead of 1.16.
+const PADDED: i32 = 2529;
+
+/// Light is kept apart from blocks and chunks end up with a `full` status
+/// since 18w43a, ahead of 1.14.
+const SEPARATE_LIGHT: i32 = 1901;
+
+/// The `Level` compound went away and biomes got palettes in 21w43a, ahead
+/// of 1.18.
+const FLAT_LAYOUT: i32 = 2844;
+
+/// The protocol and data versions of releases, to tell which chunks a block
+/// registry can decode.
+const RELEASES: &[(i32, i32)] = &[
+    (404, 1631),
+    (477, 1952),
+    (498, 1976),
+    (578, 2230),
+    (754, 2586),
+    (756, 2730),
+    (758, 2975),
+    (762, 3337),
+    (763, 3465),
+];
+
+/// Returns the newest data version whose blocks a registry for
+/// `protocol_version` knows, taking the latest release it's at least as
+/// new as.
+fn max_data_version(protocol_version: i32) -> Option<i32> {
+    RELEASES
+        .iter()
+        .rev()
+        .find(|&&(protocol, _)| protocol <= protocol_version)
+        .map(|&(_, data_version)| data_version)
+}
+
+/// A chunk loaded from a region file.
+#[derive(Clone, Debug)]
+pub struct AnvilChunk {
+    pub column: ChunkColumn,
+    pub light: ChunkLight,
+    /// Block entities as they're saved, with their position and id inside.
+    pub block_entities: Vec<Compound>,
+    pub data_version: i32,
+}
+
+fn get_light(compound: &Compound, name: &'static str) -> Result<Option<LightArray>> {
+    match compound.get(name) {
+        Some(Tag::ByteArray(values)) if values.len() == LIGHT_ARRAY_SIZE => {
+            let mut bytes = [0; LIGHT_ARRAY_SIZE];
+            for (byte, &value) in bytes.iter_mut().zip(values) {
+                *byte = value as u8;
+            }
+            Ok(Some(LightArray::from_bytes(bytes)))
+        }
+        Some(_) => Err(AnvilError::InvalidTag(name)),
+        None => Ok(None),
+    }
+}
+
+fn compounds<'a>(tags: &'a [Tag], name: &'static str) -> Result<Vec<&'a Compound>> {
+    tags.iter()
+        .map(|tag| match tag {
+            Tag::Compound(compound) => Ok(compound),
+            _ => Err(AnvilError::InvalidTag(name)),
+        })
+        .collect()
+}
+
+/// Returns the entries of a paletted array as indices into its palette,
+/// checking that they all lie within it.
+fn unpack(
+    len: usize,
+    palette_len: usize,
+    min_bits: u8,
+    packing: Packing,
+    data: Option<&[i64]>,
+    name: &'static str,
+) -> Result<Vec<usize>> {
+    let needed = (usize::BITS - palette_len.saturating_sub(1).leading_zeros()) as u8;
+    let bits = if palette_len <= 1 {
+        0
+    } else {
+        needed.max(min_bits)
+    };
+
+    let data = match (bits, data) {
+        (0, _) => return Ok(vec![0; len]),
+        (_, Some(data)) => data,
+        (_, None) => return Err(AnvilError::MissingTag(name)),
+    };
+
+    let array = PackedArray::from_longs(len, bits, packing, data.to_vec())
+        .map_err(|_| AnvilError::InvalidTag(name))?;
+
+    array
+        .iter()
+        .map(|entry| match entry as usize {
+            entry if entry < palette_len => Ok(entry),
+            _ => Err(AnvilError::InvalidTag(name)),
+        })
+        .collect()
+}
+
+fn block_state(registry: &'static BlockRegistry, entry: &Compound) -> Result<BlockState> {
+    let name = get_string(entry, "Name")?;
+    let block = registry
+        .block(name)
+        .ok_or_else(|| BlockStateError::UnknownBlock(name.to_owned()))?;
+
+    let properties = match entry.get("Properties") {
+        Some(Tag::Compound(properties)) => properties.iter(),
+        Some(_) => return Err(AnvilError::InvalidTag("Properties")),
+        None => Default::default(),
+    };
+
+    let mut state = block.default_state();
+    for (property, value) in properties {
+        match value {
+            Tag::String(value) => state = state.with(property, value)?,
+            _ => return Err(AnvilError::InvalidTag("Properties")),
+        }
+    }
+
+    Ok(state)
+}
+
+fn biome_id(biomes: &Registry, name: &str) -> Result<u32> {
+    Identifier::parse(name)
+        .ok()
+        .and_then(|id| biomes.id(&id))
+        .map(|id| id as u32)
+        .ok_or_else(|| AnvilError::UnknownBiome(name.to_owned()))
+}
+
+/// Decodes the chunk at `pos` as saved by vanilla since 1.13 into a column of
+/// `height` using the given registries. Chunks saved by a newer version than
+/// the block registry's are rejected, as their blocks may have changed.
+///
+/// Heightmaps aren't read, as they're recomputed while placing the blocks.
+pub fn decode_chunk(
+    nbt: &Compound,
+    pos: ChunkPos,
+    height: WorldHeight,
+    blocks: &'static BlockRegistry,
+    biomes: &'static Registry,
+) -> Result<AnvilChunk> {
+    let data_version = match nbt.get("DataVersion") {
+        Some(&Tag::Int(version)) => version,
+        Some(_) => return Err(AnvilError::InvalidTag("DataVersion")),
+        None => return Err(AnvilError::UnsupportedDataVersion(None)),
+    };
+
+    let max = max_data_version(blocks.protocol_version());
+    if data_version < FLATTENING || max.is_none_or(|max| data_version > max) {
+        return Err(AnvilError::UnsupportedDataVersion(Some(data_version)));
+    }
+
+    decode_layout(nbt, data_version, pos, height, blocks, biomes)
+}
+
+/// Decodes a chunk laid out as `data_version` saved it, whatever the block
+/// registry.
+fn decode_layout(
+    nbt: &Compound,
+    data_version: i32,
+    pos: ChunkPos,
+    height: WorldHeight,
+    blocks: &'static BlockRegistry,
+    biomes: &'static Registry,
+) -> Result<AnvilChunk> {
+    let flat = data_version >= FLAT_LAYOUT;
+    let level = if flat {
+        nbt
+    } else {
+        get_compound(nbt, "Level")?
+    };
+    let packing = if data_version >= PADDED {
+        Packing::Padded
+    } else {
+        Packing::Spanning
+    };
+
+    let found = ChunkPos {
+        x: get_int(level, "xPos")?,
+        z: get_int(level, "zPos")?,
+    };
+    if found != pos {
+        return Err(AnvilError::WrongPosition {
+            expected: pos,
+            found,
+        });
+    }
+
+    let mut column = ChunkColumn::new(pos, height, blocks, biomes);
+
+    let bottom = height.min_y() >> 4;
+    let section_count = (height.height() / CHUNK_SIZE) as usize;
+
+    let sections = get_list(level, if flat { "sections" } else { "Sections" })?;
+    let mut lights = Vec::new();
+
+    for section in compounds(sections, "sections")? {
+        let y = match get(section, "Y")? {
+            &Tag::Byte(y) => i32::from(y),
+            _ => return Err(AnvilError::InvalidTag("Y")),
+        };
+
+        // Light also covers the sections right outside the world.
+        if let Ok(index) = usize::try_from(y - bottom + 1) {
+            let block = get_light(section, "BlockLight")?;
+            let sky = get_light(section, "SkyLight")?;
+            lights.push((index, block, sky));
+        }
+
+        let index = match usize::try_from(y - bottom) {
+            Ok(index) if index < section_count => index,
+            _ => continue,
+        };
+
+        let (palette, data) = if flat {
+            match section.get("block_states") {
+                Some(Tag::Compound(states)) => (
+                    get_list(states, "palette")?,
+                    get_long_array(states, "data")?,
+                ),
+                Some(_) => return Err(AnvilError::InvalidTag("block_states")),
+                None => (&[][..], None),
+            }
+        } else {
+            (
+                get_list(section, "Palette")?,
+                get_long_array(section, "BlockStates")?,
+            )
+        };
+
+        let palette = compounds(palette, "palette")?
+            .into_iter()
+            .map(|entry| block_state(blocks, entry))
+            .collect::<Result<Vec<_>>>()?;
+
+        if !palette.is_empty() {
+            let entries = unpack(4096, palette.len(), 4, packing, data, "block states")?;
+            let min_y = (bottom + index as i32) * CHUNK_SIZE;
+
+            for (i, entry) in entries.into_iter().enumerate() {
+                let state = palette[entry];
+                if state.block().name() == "minecraft:air" {
+                    continue;
+                }
+
+                let (x, y, z) = (i & lsb!(4), i >> 8, (i >> 4) & lsb!(4));
+                let position = column.position(x as i32, min_y + y as i32, z as i32)?;
+                column.set_block(position, state)?;
+            }
+        }
+
+        if flat {
+            if let Some(Tag::Compound(section_biomes)) = section.get("biomes") {
+                let palette = get_list(section_biomes, "palette")?
+                    .iter()
+                    .map(|tag| match tag {
+                        Tag::String(name) => biome_id(biomes, name),
+                        _ => Err(AnvilError::InvalidTag("palette")),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let data = get_long_array(section_biomes, "data")?;
+
+                if !palette.is_empty() {
+                    let entries = unpack(64, palette.len(), 1, packing, data, "biomes")?;
+                    let min_y = (bottom + index as i32) * CHUNK_SIZE;
+
+                    for (i, entry) in entries.into_iter().enumerate() {
+                        let (x, y, z) = (i & lsb!(2), i >> 4, (i >> 2) & lsb!(2));
+                        let position =
+                            column.position(4 * x as i32, min_y + 4 * y as i32, 4 * z as i32)?;
+                        column.set_biome(position, palette[entry])?;
+                    }
+                }
+            }
+        }
+    }
+
+    if !flat {
+        decode_legacy_biomes(&mut column, level, data_version)?;
+    }
+
+    let has_sky = lights.iter().any(|(_, _, sky)| sky.is_some());
+    let mut light = ChunkLight::new(height, has_sky);
+    for (index, block, sky) in lights {
+        if index < light.len() {
+            light.set_block(index, block);
+            light.set_sky(index, sky);
+        }
+    }
+
+    let block_entities = get_list(
+        level,
+        if flat {
+            "block_entities"
+        } else {
+            "TileEntities"
+        },
+    )?;
+    let block_entities = compounds(block_entities, "block_entities")?
+        .into_iter()
+        .cloned()
+        .collect();
+
+    Ok(AnvilChunk {
+        column,
+        light,
+        block_entities,
+        data_version,
+    })
+}
+
+/// Reads the biomes of chunks from before 1.18, which are ids for every
+/// column or, since 1.15, for every 4x4x4 cube of the whole column.
+fn decode_legacy_biomes(
+    column: &mut ChunkColumn,
+    level: &Compound,
+    data_version: i32,
+) -> Result<()> {
+    let ids = match level.get("Biomes") {
+        Some(Tag::IntArray(ids)) => ids,
+        Some(_) => return Err(AnvilError::InvalidTag("Biomes")),
+        None => return Ok(()),
+    };
+
+    let height = column.height();
+    let cubes = (height.height() / 4) as usize;
+
+    if data_version < BIOMES_3D {
+        if ids.len() != 256 {
+            return Err(AnvilError::InvalidTag("Biomes"));
+        }
+
+        // Every cube takes the biome of its north-west column.
+        for i in 0..cubes * 16 {
+            let (x, y, z) = (4 * (i & lsb!(2)), 4 * (i >> 4), 4 * ((i >> 2) & lsb!(2)));
+            let position = column.position(x as i32, height.min_y() + y as i32, z as i32)?;
+            column.set_biome(position, ids[z * 16 + x] as u32)?;
+        }
+    } else {
+        if ids.len() != cubes * 16 {
+            return Err(AnvilError::InvalidTag("Biomes"));
+        }
+
+        for (i, &id) in ids.iter().enumerate() {
+            let (x, y, z) = (4 * (i & lsb!(2)), 4 * (i >> 4), 4 * ((i >> 2) & lsb!(2)));
+            let position = column.position(x as i32, height.min_y() + y as i32, z as i32)?;
+            column.set_biome(position, id as u32)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the values of a paletted container as a palette and the index
+/// of every entry in it.
+fn palette_of(container: &PalettedContainer) -> (Vec<u32>, Vec<u64>) {
+    let mut palette = match container.palette() {
+        Palette::Single(value) => vec![*value],
+        Palette::Indirect(values) => values.clone(),
+        Palette::Direct => Vec::new(),
+    };
+
+    let indices = container
+        .iter()
+        .map(|value| match palette.iter().position(|&v| v == value) {
+            Some(index) => index as u64,
+            None => {
+                palette.push(value);
+                palette.len() as u64 - 1
+            }
+        })
+        .collect();
+
+    (palette, indices)
+}
+
+/// Packs palette indices the way vanilla saves them, leaving out the data
+/// of single values where the layout allows.
+fn pack(
+    indices: Vec<u64>,
+    palette_len: usize,
+    min_bits: u8,
+    packing: Packing,
+    optional: bool,
+) -> Option<Tag> {
+    if optional && palette_len == 1 {
+        return None;
+    }
+
+    let needed = (usize::BITS - palette_len.saturating_sub(1).leading_zeros()) as u8;
+    let array = PackedArray::from_values(indices.len(), needed.max(min_bits), packing, indices)
+        .expect("indices lie within the palette");
+
+    Some(Tag::LongArray(
+        array.as_longs().iter().map(|&long| long as i64).collect(),
+    ))
+}
+
+fn light_tag(light: &LightArray) -> Tag {
+    Tag::ByteArray(light.as_bytes().iter().map(|&byte| byte as i8).collect())
+}
+
+/// Encodes a chunk the way vanilla saves it in `chunk.data_version`, which
+/// must be the version of the registries it uses.
+pub fn encode_chunk(chunk: &AnvilChunk, biomes: &Registry) -> Result<Compound> {
+    let data_version = chunk.data_version;
+    if data_version < FLATTENING {
+        return Err(AnvilError::UnsupportedDataVersion(Some(data_version)));
+    }
+
+    let flat = data_version >= FLAT_LAYOUT;
+    let packing = if data_version >= PADDED {
+        Packing::Padded
+    } else {
+        Packing::Spanning
+    };
+
+    let column = &chunk.column;
+    let light = &chunk.light;
+    let height = column.height();
+    let bottom = height.min_y() >> 4;
+    let registry = column.registry();
+
+    let biome_name = |id: u32| {
+        biomes
+            .get(id as i32)
+            .map(|name| Tag::String(name.to_string()))
+            .ok_or_else(|| AnvilError::UnknownBiome(id.to_string()))
+    };
+
+    let mut sections = Vec::new();
+    for light_index in 0..light.len() {
+        let mut section = Compound::new();
+
+        let in_world = light_index
+            .checked_sub(1)
+            .and_then(|index| column.sections().get(index));
+        let blocks = in_world.filter(|section| flat || !section.is_empty());
+
+        if let Some(blocks) = blocks {
+            let (palette, indices) = palette_of(blocks.blocks());
+            let palette = palette
+                .into_iter()
+                .map(|id| {
+                    let state = registry.state(id).unwrap();
+                    let mut entry = Compound::new();
+                    entry.insert("Name".into(), Tag::String(state.block().name().into()));
+
+                    let properties = state
+                        .properties()
+                        .map(|(name, value)| (name.to_owned(), Tag::String(value.to_owned())))
+                        .collect::<Compound>();
+                    if !properties.is_empty() {
+                        entry.insert("Properties".into(), Tag::Compound(properties));
+                    }
+
+                    Tag::Compound(entry)
+                })
+                .collect::<Vec<_>>();
+            let data = pack(indices, palette.len(), 4, packing, flat);
+
+            if flat {
+                let mut states = Compound::new();
+                states.insert("palette".into(), Tag::List(palette));
+                states.extend(data.map(|data| ("data".to_owned(), data)));
+                section.insert("block_states".into(), Tag::Compound(states));
+
+                let (palette, indices) = palette_of(blocks.biomes());
+                let palette = palette
+                    .into_iter()
+                    .map(biome_name)
+                    .collect::<Result<Vec<_>>>()?;
+                let data = pack(indices, palette.len(), 1, packing, true);
+
+                let mut section_biomes = Compound::new();
+                section_biomes.insert("palette".into(), Tag::List(palette));
+                section_biomes.extend(data.map(|data| ("data".to_owned(), data)));
+                section.insert("biomes".into(), Tag::Compound(section_biomes));
+            } else {
+                section.insert("Palette".into(), Tag::List(palette));
+                section.extend(data.map(|data| ("BlockStates".to_owned(), data)));
+            }
+        }
+
+        if data_version >= SEPARATE_LIGHT {
+            if let Some(block) = light.block(light_index) {
+                section.insert("BlockLight".into(), light_tag(block));
+            }
+            if let Some(sky) = light.sky(light_index) {
+                section.insert("SkyLight".into(), light_tag(sky));
+            }
+        } else if blocks.is_some() {
+            // Light was still part of sections, which had to have it.
+            let dark = LightArray::default();
+            let block = light.block(light_index).unwrap_or(&dark);
+            section.insert("BlockLight".into(), light_tag(block));
+            if light.has_sky() {
+                let sky = light.sky(light_index).unwrap_or(&dark);
+                section.insert("SkyLight".into(), light_tag(sky));
+            }
+        }
+
+        if !section.is_empty() {
+            let y = bottom - 1 + light_index as i32;
+            section.insert("Y".into(), Tag::Byte(y as i8));
+            sections.push(Tag::Compound(section));
+        }
+    }
+
+    let bits = (u32::BITS - (height.height() as u32).leading_zeros()) as u8;
+    let heightmaps = column
+        .heightmaps()
+        .iter()
+        .map(|heightmap| {
+            let heights = heightmap.heights().iter().map(|&height| height.into());
+            let array = PackedArray::from_values(256, bits, packing, heights)
+                .expect("heights are at most the height of the world");
+            let longs = array.as_longs().iter().map(|&long| long as i64).collect();
+
+            (heightmap.kind().name().to_owned(), Tag::LongArray(longs))
+        })
+        .collect();
+
+    let mut level = Compound::new();
+    level.insert("xPos".into(), Tag::Int(column.pos().x));
+    level.insert("zPos".into(), Tag::Int(column.pos().z));
+    level.insert("LastUpdate".into(), Tag::Long(0));
+    level.insert("InhabitedTime".into(), Tag::Long(0));
+    level.insert("Heightmaps".into(), Tag::Compound(heightmaps));
+
+    let status = if data_version >= SEPARATE_LIGHT {
+        "full"
+    } else {
+        "postprocessed"
+    };
+    level.insert("Status".into(), Tag::String(status.into()));
+    if data_version >= SEPARATE_LIGHT {
+        let lit = (0..light.len()).any(|i| light.block(i).is_some() || light.sky(i).is_some());
+        level.insert("isLightOn".into(), Tag::Byte(lit as i8));
+    }
+
+    let block_entities = chunk
+        .block_entities
+        .iter()
+        .cloned()
+        .map(Tag::Compound)
+        .collect();
+
+    if flat {
+        let mut nbt = level;
+        nbt.insert("DataVersion".into(), Tag::Int(data_version));
+        nbt.insert("yPos".into(), Tag::Int(bottom));
+        nbt.insert("sections".into(), Tag::List(sections));
+        nbt.insert("block_entities".into(), Tag::List(block_entities));
+        return Ok(nbt);
+    }
+
+    level.insert("Sections".into(), Tag::List(sections));
+    level.insert("TileEntities".into(), Tag::List(block_entities));
+    level.insert("Entities".into(), Tag::List(Vec::new()));
+    level.insert(
+        "Biomes".into(),
+        Tag::IntArray(legacy_biomes(column, data_version)?),
+    );
+
+    let mut nbt = Compound::new();
+    nbt.insert("DataVersion".into(), Tag::Int(data_version));
+    nbt.insert("Level".into(), Tag::Compound(level));
+    Ok(nbt)
+}
+
+/// Returns the biomes of a column as saved before 1.18, see
+/// `decode_legacy_biomes`.
+fn legacy_biomes(column: &ChunkColumn, data_version: i32) -> Result<Vec<i32>> {
+    let height = column.height();
+
+    if data_version < BIOMES_3D {
+        // Biomes were 2D, so take those at sea level.
+        let y = 64.max(height.min_y()).min(height.max_y());
+
+        (0..256)
+            .map(|i| {
+                let position = column.position(i & lsb!(4), y, i >> 4)?;
+                Ok(column.biome(position)? as i32)
+            })
+            .collect()
+    } else {
+        let cubes = (height.height() / 4) as usize;
+
+        (0..cubes * 16)
+            .map(|i| {
+                let (x, y, z) = (4 * (i & lsb!(2)), 4 * (i >> 4), 4 * ((i >> 2) & lsb!(2)));
+                let position = column.position(x as i32, height.min_y() + y as i32, z as i32)?;
+                Ok(column.biome(position)? as i32)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{
+        objs::Position,
+        world::{test_util::registries, ChunkError},
+    };
+
+    fn compound(entries: Vec<(&str, Tag)>) -> Compound {
+        entries
+            .into_iter()
+            .map(|(name, tag)| (name.to_owned(), tag))
+            .collect()
+    }
+
+    fn palette_entry(name: &str, properties: &[(&str, &str)]) -> Tag {
+        let properties = properties
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), Tag::String(value.to_owned())))
+            .collect();
+
+        Tag::Compound(compound(vec![
+            ("Name", Tag::String(name.to_owned())),
+            ("Properties", Tag::Compound(properties)),
+        ]))
+    }
+
+    fn pack(len: usize, bits: u8, packing: Packing, entries: &[(usize, u64)]) -> Tag {
+        let mut array = PackedArray::new(len, bits, packing).unwrap();
+        for &(index, entry) in entries {
+            array.set(index, entry).unwrap();
+        }
+        Tag::LongArray(array.as_longs().iter().map(|&long| long as i64).collect())
+    }
+
+    fn legacy_chunk(data_version: i32, biomes: Vec<i32>) -> Compound {
+        let section = compound(vec![
+            ("Y", Tag::Byte(1)),
+            (
+                "Palette",
+                Tag::List(vec![
+                    palette_entry("minecraft:air", &[]),
+                    palette_entry("minecraft:oak_stairs", &[("facing", "east")]),
+                ]),
+            ),
+            (
+                "BlockStates",
+                pack(4096, 4, Packing::Spanning, &[(0, 1), (4095, 1)]),
+            ),
+            ("BlockLight", Tag::ByteArray(vec![0x21; 2048])),
+            ("SkyLight", Tag::ByteArray(vec![-1; 2048])),
+        ]);
+        let level = compound(vec![
+            ("xPos", Tag::Int(-3)),
+            ("zPos", Tag::Int(7)),
+            ("Sections", Tag::List(vec![Tag::Compound(section)])),
+            ("Biomes", Tag::IntArray(biomes)),
+            (
+                "TileEntities",
+                Tag::List(vec![Tag::Compound(compound(vec![(
+                    "id",
+                    Tag::String("minecraft:chest".into()),
+                )]))]),
+            ),
+        ]);
+
+        compound(vec![
+            ("DataVersion", Tag::Int(data_version)),
+            ("Level", Tag::Compound(level)),
+        ])
+    }
+
+    #[test]
+    fn test_legacy() {
+        let (blocks, biomes) = registries();
+        let mut ids = vec![0; 256];
+        ids[4] = 2;
+
+        let chunk = decode_chunk(
+            &legacy_chunk(1631, ids),
+            ChunkPos { x: -3, z: 7 },
+            WorldHeight::LEGACY,
+            blocks,
+            biomes,
+        )
+        .unwrap();
+        assert_eq!(chunk.data_version, 1631);
+        assert_eq!(chunk.block_entities.len(), 1);
+
+        let column = &chunk.column;
+        let pos = column.pos();
+        assert_eq!(pos, ChunkPos { x: -3, z: 7 });
+
+        let stairs = blocks.parse("oak_stairs[facing=east]").unwrap();
+        assert_eq!(column.block(pos.block(0, 16, 0).unwrap()), Ok(stairs));
+        assert_eq!(column.block(pos.block(15, 31, 15).unwrap()), Ok(stairs));
+        assert_eq!(
+            column.block(pos.block(1, 16, 0).unwrap()),
+            Ok(blocks.parse("air").unwrap())
+        );
+        assert_eq!(column.sections()[1].block_count(), 2);
+
+        assert_eq!(column.biome(pos.block(5, 200, 0).unwrap()), Ok(2));
+        assert_eq!(column.biome(pos.block(0, 200, 0).unwrap()), Ok(0));
+
+        assert_eq!(chunk.light.block(2).unwrap().get(1), 2);
+        assert_eq!(chunk.light.sky(2).unwrap().get(0), 15);
+        assert_eq!(chunk.light.sky(1), None);
+    }
+
+    // Only 1.13.2's registry is bundled, so chunks from later versions are
+    // decoded with it past the data version check; their blocks exist in
+    // 1.13.2 all the same.
+    fn decode_newer(nbt: &Compound, pos: ChunkPos, height: WorldHeight) -> Result<AnvilChunk> {
+        let (blocks, biomes) = registries();
+        let data_version = get_int(nbt, "DataVersion")?;
+        assert!(matches!(
+            decode_chunk(nbt, pos, height, blocks, biomes),
+            Err(AnvilError::UnsupportedDataVersion(Some(version))) if version == data_version
+        ));
+
+        decode_layout(nbt, data_version, pos, height, blocks, biomes)
+    }
+
+    #[test]
+    fn test_biomes_3d() {
+        let mut ids = vec![1; 1024];
+        ids[1023] = 3;
+
+        let pos = ChunkPos { x: -3, z: 7 };
+        let column = decode_newer(&legacy_chunk(2230, ids), pos, WorldHeight::LEGACY)
+            .unwrap()
+            .column;
+        assert_eq!(column.biome(pos.block(15, 255, 15).unwrap()), Ok(3));
+        assert_eq!(column.biome(pos.block(0, 255, 15).unwrap()), Ok(1));
+
+        assert!(matches!(
+            decode_newer(&legacy_chunk(2230, vec![1; 256]), pos, WorldHeight::LEGACY),
+            Err(AnvilError::InvalidTag("Biomes"))
+        ));
+    }
+
+    #[test]
+    fn test_flat_layout() {
+        let (blocks, biomes) = registries();
+
+        let block_states = compound(vec![
+            (
+                "palette",
+                Tag::List(vec![
+                    palette_entry("minecraft:stone", &[]),
+                    palette_entry("minecraft:air", &[]),
+                ]),
+            ),
+            ("data", pack(4096, 4, Packing::Padded, &[(4095, 1)])),
+        ]);
+        let section_biomes = compound(vec![
+            (
+                "palette",
+                Tag::List(vec![
+                    Tag::String("minecraft:plains".into()),
+                    Tag::String("desert".into()),
+                ]),
+            ),
+            ("data", pack(64, 1, Packing::Padded, &[(63, 1)])),
+        ]);
+        let section = compound(vec![
+            ("Y", Tag::Byte(-4)),
+            ("block_states", Tag::Compound(block_states)),
+            ("biomes", Tag::Compound(section_biomes)),
+        ]);
+        let light_only = compound(vec![
+            ("Y", Tag::Byte(-5)),
+            ("SkyLight", Tag::ByteArray(vec![0; 2048])),
+        ]);
+        let nbt = compound(vec![
+            ("DataVersion", Tag::Int(2975)),
+            ("xPos", Tag::Int(0)),
+            ("zPos", Tag::Int(0)),
+            (
+                "sections",
+                Tag::List(vec![Tag::Compound(light_only), Tag::Compound(section)]),
+            ),
+        ]);
+
+        let chunk = decode_newer(&nbt, ChunkPos { x: 0, z: 0 }, WorldHeight::OVERWORLD).unwrap();
+        let column = &chunk.column;
+        let stone = blocks.parse("stone").unwrap();
+        assert_eq!(column.block(Position { x: 0, y: -64, z: 0 }), Ok(stone));
+        assert_eq!(
+            column.block(Position {
+                x: 15,
+                y: -49,
+                z: 15
+            }),
+            Ok(blocks.parse("air").unwrap())
+        );
+        assert_eq!(column.sections()[0].block_count(), 4095);
+        assert_eq!(column.heightmaps()[0].get(0, 0), 16);
+
+        let desert = biomes.id(&Identifier::parse("desert").unwrap()).unwrap() as u32;
+        assert_eq!(
+            column.biome(Position {
+                x: 15,
+                y: -49,
+                z: 15
+            }),
+            Ok(desert)
+        );
+        assert_eq!(column.biome(Position { x: 0, y: -64, z: 0 }), Ok(1));
+
+        assert!(chunk.light.has_sky());
+        assert!(chunk.light.sky(0).unwrap().is_dark());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let (blocks, biomes) = registries();
+        let pos = ChunkPos { x: -3, z: 7 };
+        let decode = |nbt: &Compound| decode_chunk(nbt, pos, WorldHeight::LEGACY, blocks, biomes);
+
+        assert!(matches!(
+            decode(&Compound::new()),
+            Err(AnvilError::UnsupportedDataVersion(None))
+        ));
+        assert!(matches!(
+            decode(&compound(vec![("DataVersion", Tag::Int(1343))])),
+            Err(AnvilError::UnsupportedDataVersion(Some(1343)))
+        ));
+        assert!(matches!(
+            decode(&compound(vec![("DataVersion", Tag::Int(1631))])),
+            Err(AnvilError::MissingTag("Level"))
+        ));
+
+        assert!(matches!(
+            decode(&legacy_chunk(1976, vec![0; 256])),
+            Err(AnvilError::UnsupportedDataVersion(Some(1976)))
+        ));
+        assert!(matches!(
+            decode_chunk(
+                &legacy_chunk(1631, vec![0; 256]),
+                ChunkPos { x: 3, z: 7 },
+                WorldHeight::LEGACY,
+                blocks,
+                biomes
+            ),
+            Err(AnvilError::WrongPosition {
+                expected: ChunkPos { x: 3, z: 7 },
+                found: ChunkPos { x: -3, z: 7 },
+            })
+        ));
+
+        let mut nbt = legacy_chunk(1631, vec![0; 256]);
+        let set_section = |nbt: &mut Compound, name: &str, tag: Tag| {
+            if let Some(Tag::Compound(level)) = nbt.get_mut("Level") {
+                if let Some(Tag::List(sections)) = level.get_mut("Sections") {
+                    if let Tag::Compound(section) = &mut sections[0] {
+                        section.insert(name.to_owned(), tag);
+                    }
+                }
+            }
+        };
+
+        // An index past the end of the palette.
+        set_section(
+            &mut nbt,
+            "BlockStates",
+            pack(4096, 4, Packing::Spanning, &[(7, 2)]),
+        );
+        assert!(matches!(
+            decode(&nbt),
+            Err(AnvilError::InvalidTag("block states"))
+        ));
+
+        set_section(&mut nbt, "BlockStates", Tag::LongArray(vec![0; 3]));
+        assert!(matches!(
+            decode(&nbt),
+            Err(AnvilError::InvalidTag("block states"))
+        ));
+
+        set_section(
+            &mut nbt,
+            "Palette",
+            Tag::List(vec![palette_entry("minecraft:stone", &[("facing", "up")])]),
+        );
+        assert!(matches!(
+            decode(&nbt),
+            Err(AnvilError::InvalidBlock(
+                BlockStateError::UnknownProperty { .. }
+            ))
+        ));
+
+        set_section(
+            &mut nbt,
+            "Palette",
+            Tag::List(vec![palette_entry("mod:thing", &[])]),
+        );
+        assert!(matches!(
+            decode(&nbt),
+            Err(AnvilError::InvalidBlock(BlockStateError::UnknownBlock(_)))
+        ));
+
+        let nbt = legacy_chunk(1631, vec![1 << 20; 256]);
+        assert!(matches!(
+            decode(&nbt),
+            Err(AnvilError::Chunk(ChunkError::UnknownBiome(_)))
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn test_roundtrip(
+            blocks in prop::collection::vec((0..16, 0..256, 0..16, 0..69u32), 0..200),
+            biome_writes in prop::collection::vec((0..16, 0..256, 0..16, 0..10u32), 0..20),
+            data_version in prop::sample::select(vec![1631, 1976, 2230, 2586, 2975]),
+        ) {
+            let (registry, biomes) = registries();
+            let height = if data_version >= FLAT_LAYOUT {
+                WorldHeight::OVERWORLD
+            } else {
+                WorldHeight::LEGACY
+            };
+
+            let mut column = ChunkColumn::new(ChunkPos { x: 5, z: -9 }, height, registry, biomes);
+            let pos = column.pos();
+            for (x, y, z, state) in blocks {
+                let position = pos.block(x, height.min_y() + y, z).unwrap();
+                column.set_block(position, registry.state(state).unwrap()).unwrap();
+            }
+            for (x, y, z, biome) in biome_writes {
+                // Biomes were the same all the way up before 1.15.
+                let ys = if data_version < BIOMES_3D { 0..height.height() } else { y..y + 1 };
+                for y in ys {
+                    column.set_biome(pos.block(x, height.min_y() + y, z).unwrap(), biome).unwrap();
+                }
+            }
+
+            let mut light = ChunkLight::full_sky(height);
+            let mut lit = LightArray::default();
+            lit.set(100, 7);
+            light.set_block(3, Some(lit));
+            light.set_sky(0, None);
+
+            let chunk = AnvilChunk {
+                column,
+                light,
+                block_entities: vec![compound(vec![("id", Tag::String("minecraft:chest".into()))])],
+                data_version,
+            };
+
+            let nbt = encode_chunk(&chunk, biomes).unwrap();
+            let pos = chunk.column.pos();
+            let decoded = if data_version <= 1631 {
+                decode_chunk(&nbt, pos, height, registry, biomes).unwrap()
+            } else {
+                decode_newer(&nbt, pos, height).unwrap()
+            };
+
+            prop_assert_eq!(decoded.data_version, data_version);
+            prop_assert_eq!(&decoded.block_entities, &chunk.block_entities);
+            prop_assert_eq!(decoded.column.heightmaps(), chunk.column.heightmaps());
+
+            for (a, b) in decoded.column.sections().iter().zip(chunk.column.sections()) {
+                prop_assert_eq!(a.block_count(), b.block_count());
+                prop_assert!(a.blocks().iter().eq(b.blocks().iter()));
+                prop_assert!(a.biomes().iter().eq(b.biomes().iter()));
+            }
+
+            // Before 1.14 light was only kept for sections with blocks.
+            if data_version >= SEPARATE_LIGHT {
+                prop_assert_eq!(&decoded.light, &chunk.light);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Gzipped NBT files like `level.dat` and player data, which vanilla replaces
+//! by writing a new file first and keeping the previous one as `<name>_old`.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder};
+
+use super::{
+    super::super::nbt::{self, Compound, Limits, Tag},
+    AnvilError, Result,
+};
+
+fn read(path: &Path) -> Result<Compound> {
+    let file = BufReader::new(File::open(path)?);
+
+    match nbt::read_named(GzDecoder::new(file), Limits::FILE)? {
+        Some((_, Tag::Compound(root))) => Ok(root),
+        Some(_) => Err(AnvilError::InvalidTag("root")),
+        None => Err(AnvilError::MissingTag("root")),
+    }
+}
+
+/// Reads `dir/name` with `decode`, falling back to the previous file if it's
+/// unreadable.
+pub(super) fn load<T>(
+    dir: &Path,
+    name: &str,
+    decode: impl Fn(&Compound) -> Result<T>,
+) -> Result<T> {
+    read(&dir.join(name))
+        .and_then(|root| decode(&root))
+        .or_else(|err| {
+            let old = dir.join(format!("{}_old", name));
+            if old.exists() {
+                decode(&read(&old)?)
+            } else {
+                Err(err)
+            }
+        })
+}
+
+/// Writes `root` to `dir/name`, keeping the file it replaces.
+pub(super) fn save(dir: &Path, name: &str, root: Compound) -> Result<()> {
+    let new = dir.join(format!("{}_new", name));
+    let current = dir.join(name);
+    let old = dir.join(format!("{}_old", name));
+
+    let mut encoder = GzEncoder::new(
+        BufWriter::new(File::create(&new)?),
+        flate2::Compression::default(),
+    );
+    nbt::write_named(&mut encoder, "", &Tag::Compound(root))?;
+    let mut file = encoder.finish()?;
+    file.flush()?;
+    file.get_ref().sync_all()?;
+
+    if current.exists() {
+        std::fs::rename(&current, &old)?;
+    }
+    std::fs::rename(&new, &current)?;
+
+    Ok(())
+}
@@ -0,0 +1,453 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    super::super::{
+        nbt::{Compound, Tag},
+        objs::{Difficulty, GameMode, Position},
+    },
+    dat,
+    tags::{boolean, double, get_compound, get_or, int, long},
+    AnvilError, Result,
+};
+
+/// The version of the Anvil format, which vanilla still writes.
+const ANVIL_VERSION: i32 = 19133;
+
+/// Game rules, which vanilla keeps as strings whatever their type.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct GameRules(BTreeMap<String, String>);
+
+impl GameRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        self.get(name)?.parse().ok()
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        self.get(name)?.parse().ok()
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: impl ToString) {
+        self.0.insert(name.into(), value.to_string());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+/// The weather, with how many ticks are left until it changes.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct Weather {
+    pub raining: bool,
+    pub rain_time: i32,
+    pub thundering: bool,
+    pub thunder_time: i32,
+    /// Ticks of clear weather set by `/weather clear`, during which it won't
+    /// rain.
+    pub clear_weather_time: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WorldBorder {
+    pub center_x: f64,
+    pub center_z: f64,
+    /// The length of a side, in blocks.
+    pub size: f64,
+    /// How far past the border players can go without taking damage.
+    pub safe_zone: f64,
+    pub damage_per_block: f64,
+    pub warning_blocks: f64,
+    pub warning_time: f64,
+    /// The size the border is moving towards, and in how many milliseconds
+    /// it will get there.
+    pub size_lerp_target: f64,
+    pub size_lerp_time: i64,
+}
+
+impl Default for WorldBorder {
+    fn default() -> Self {
+        Self {
+            center_x: 0.0,
+            center_z: 0.0,
+            size: 60_000_000.0,
+            safe_zone: 5.0,
+            damage_per_block: 0.2,
+            warning_blocks: 5.0,
+            warning_time: 15.0,
+            size_lerp_target: 60_000_000.0,
+            size_lerp_time: 0,
+        }
+    }
+}
+
+/// The settings of a world, saved in its `level.dat`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LevelData {
+    pub data_version: i32,
+    pub name: String,
+    pub seed: i64,
+    pub spawn: Position,
+    pub game_mode: GameMode,
+    pub hardcore: bool,
+    pub difficulty: Difficulty,
+    pub allow_commands: bool,
+    /// Ticks the world has been running for.
+    pub time: i64,
+    /// The time of day, which unlike `time` stops with the
+    /// `doDaylightCycle` game rule.
+    pub day_time: i64,
+    pub weather: Weather,
+    pub border: WorldBorder,
+    pub game_rules: GameRules,
+    /// Tags this doesn't cover, kept so saving doesn't lose them.
+    pub other: Compound,
+}
+
+impl LevelData {
+    /// Creates the settings of a new world with vanilla's defaults.
+    pub fn new(name: impl Into<String>, seed: i64, data_version: i32) -> Self {
+        let mut other = Compound::new();
+        other.insert("version".into(), Tag::Int(ANVIL_VERSION));
+        other.insert("initialized".into(), Tag::Byte(1));
+
+        Self {
+            data_version,
+            name: name.into(),
+            seed,
+            spawn: Position { x: 0, y: 64, z: 0 },
+            game_mode: GameMode::default(),
+            hardcore: false,
+            difficulty: Difficulty::default(),
+            allow_commands: false,
+            time: 0,
+            day_time: 0,
+            weather: Weather::default(),
+            border: WorldBorder::default(),
+            game_rules: GameRules::new(),
+            other,
+        }
+    }
+
+    /// Reads the settings from the root tag of a `level.dat`. Like vanilla,
+    /// missing values take their defaults.
+    pub fn from_nbt(root: &Compound) -> Result<Self> {
+        let data = get_compound(root, "Data")?;
+        let defaults = Self::new("", 0, 0);
+        let border = WorldBorder::default();
+
+        // Since 1.16 the seed is part of the generator settings.
+        let seed = match data.get("WorldGenSettings") {
+            Some(Tag::Compound(settings)) if !data.contains_key("RandomSeed") => {
+                get_or(settings, "seed", 0, long)?
+            }
+            _ => get_or(data, "RandomSeed", 0, long)?,
+        };
+
+        let game_rules = match data.get("GameRules") {
+            Some(Tag::Compound(rules)) => rules
+                .iter()
+                .map(|(name, value)| match value {
+                    Tag::String(value) => Ok((name.clone(), value.clone())),
+                    _ => Err(AnvilError::InvalidTag("GameRules")),
+                })
+                .collect::<Result<_>>()
+                .map(GameRules)?,
+            Some(_) => return Err(AnvilError::InvalidTag("GameRules")),
+            None => GameRules::new(),
+        };
+
+        let mut other = data.clone();
+        for name in KNOWN_TAGS {
+            other.remove(*name);
+        }
+
+        Ok(Self {
+            data_version: get_or(data, "DataVersion", 0, int)?,
+            name: get_or(data, "LevelName", String::new(), |tag| {
+                tag.as_str().map(str::to_owned)
+            })?,
+            seed,
+            spawn: Position {
+                x: get_or(data, "SpawnX", defaults.spawn.x, int)?,
+                y: get_or(data, "SpawnY", defaults.spawn.y, int)?,
+                z: get_or(data, "SpawnZ", defaults.spawn.z, int)?,
+            },
+            game_mode: get_or(data, "GameType", GameMode::default(), |tag| {
+                GameMode::from_id(int(tag)?)
+            })?,
+            hardcore: get_or(data, "hardcore", false, boolean)?,
+            difficulty: get_or(
+                data,
+                "Difficulty",
+                Difficulty::default(),
+                |tag| match *tag {
+                    Tag::Byte(id) => Difficulty::from_id(id.into()),
+                    _ => None,
+                },
+            )?,
+            allow_commands: get_or(data, "allowCommands", false, boolean)?,
+            time: get_or(data, "Time", 0, long)?,
+            day_time: get_or(data, "DayTime", 0, long)?,
+            weather: Weather {
+                raining: get_or(data, "raining", false, boolean)?,
+                rain_time: get_or(data, "rainTime", 0, int)?,
+                thundering: get_or(data, "thundering", false, boolean)?,
+                thunder_time: get_or(data, "thunderTime", 0, int)?,
+                clear_weather_time: get_or(data, "clearWeatherTime", 0, int)?,
+            },
+            border: WorldBorder {
+                center_x: get_or(data, "BorderCenterX", border.center_x, double)?,
+                center_z: get_or(data, "BorderCenterZ", border.center_z, double)?,
+                size: get_or(data, "BorderSize", border.size, double)?,
+                safe_zone: get_or(data, "BorderSafeZone", border.safe_zone, double)?,
+                damage_per_block: get_or(
+                    data,
+                    "BorderDamagePerBlock",
+                    border.damage_per_block,
+                    double,
+                )?,
+                warning_blocks: get_or(data, "BorderWarningBlocks", border.warning_blocks, double)?,
+                warning_time: get_or(data, "BorderWarningTime", border.warning_time, double)?,
+                size_lerp_target: get_or(
+                    data,
+                    "BorderSizeLerpTarget",
+                    border.size_lerp_target,
+                    double,
+                )?,
+                size_lerp_time: get_or(data, "BorderSizeLerpTime", border.size_lerp_time, long)?,
+            },
+            game_rules,
+            other,
+        })
+    }
+
+    /// Returns the root tag of a `level.dat` holding the settings.
+    pub fn to_nbt(&self) -> Compound {
+        let mut data = self.other.clone();
+        let mut insert = |name: &str, tag| {
+            data.insert(name.to_owned(), tag);
+        };
+
+        insert("DataVersion", Tag::Int(self.data_version));
+        insert("LevelName", Tag::String(self.name.clone()));
+        insert("SpawnX", Tag::Int(self.spawn.x));
+        insert("SpawnY", Tag::Int(self.spawn.y));
+        insert("SpawnZ", Tag::Int(self.spawn.z));
+        insert("GameType", Tag::Int(self.game_mode.id().into()));
+        insert("hardcore", Tag::Byte(self.hardcore as i8));
+        insert("Difficulty", Tag::Byte(self.difficulty.id() as i8));
+        insert("allowCommands", Tag::Byte(self.allow_commands as i8));
+        insert("Time", Tag::Long(self.time));
+        insert("DayTime", Tag::Long(self.day_time));
+
+        let weather = &self.weather;
+        insert("raining", Tag::Byte(weather.raining as i8));
+        insert("rainTime", Tag::Int(weather.rain_time));
+        insert("thundering", Tag::Byte(weather.thundering as i8));
+        insert("thunderTime", Tag::Int(weather.thunder_time));
+        insert("clearWeatherTime", Tag::Int(weather.clear_weather_time));
+
+        let border = &self.border;
+        insert("BorderCenterX", Tag::Double(border.center_x));
+        insert("BorderCenterZ", Tag::Double(border.center_z));
+        insert("BorderSize", Tag::Double(border.size));
+        insert("BorderSafeZone", Tag::Double(border.safe_zone));
+        insert("BorderDamagePerBlock", Tag::Double(border.damage_per_block));
+        insert("BorderWarningBlocks", Tag::Double(border.warning_blocks));
+        insert("BorderWarningTime", Tag::Double(border.warning_time));
+        insert("BorderSizeLerpTarget", Tag::Double(border.size_lerp_target));
+        insert("BorderSizeLerpTime", Tag::Long(border.size_lerp_time));
+
+        let rules = self
+            .game_rules
+            .iter()
+            .map(|(name, value)| (name.to_owned(), Tag::String(value.to_owned())))
+            .collect();
+        insert("GameRules", Tag::Compound(rules));
+
+        match data.get_mut("WorldGenSettings") {
+            Some(Tag::Compound(settings)) => {
+                settings.insert("seed".into(), Tag::Long(self.seed));
+            }
+            _ => {
+                data.insert("RandomSeed".into(), Tag::Long(self.seed));
+            }
+        }
+
+        let mut root = Compound::new();
+        root.insert("Data".into(), Tag::Compound(data));
+        root
+    }
+
+    /// Loads the `level.dat` of a world, falling back to the copy vanilla
+    /// keeps of the previous one if it's unreadable.
+    pub fn load(world: &Path) -> Result<Self> {
+        dat::load(world, "level.dat", Self::from_nbt)
+    }
+
+    /// Saves the `level.dat` of a world the way vanilla does, keeping the
+    /// previous one as `level.dat_old`.
+    pub fn save(&self, world: &Path) -> Result<()> {
+        let mut root = self.to_nbt();
+        if let Some(Tag::Compound(data)) = root.get_mut("Data") {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as i64);
+            data.insert("LastPlayed".into(), Tag::Long(now));
+        }
+
+        dat::save(world, "level.dat", root)
+    }
+}
+
+/// Tags `LevelData` reads into its own fields.
+const KNOWN_TAGS: &[&str] = &[
+    "DataVersion",
+    "LevelName",
+    "RandomSeed",
+    "SpawnX",
+    "SpawnY",
+    "SpawnZ",
+    "GameType",
+    "hardcore",
+    "Difficulty",
+    "allowCommands",
+    "Time",
+    "DayTime",
+    "raining",
+    "rainTime",
+    "thundering",
+    "thunderTime",
+    "clearWeatherTime",
+    "BorderCenterX",
+    "BorderCenterZ",
+    "BorderSize",
+    "BorderSafeZone",
+    "BorderDamagePerBlock",
+    "BorderWarningBlocks",
+    "BorderWarningTime",
+    "BorderSizeLerpTarget",
+    "BorderSizeLerpTime",
+    "GameRules",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::test_util::temp_dir;
+
+    fn level() -> LevelData {
+        let mut level = LevelData::new("world", -42, 1631);
+        level.spawn = Position {
+            x: 10,
+            y: 70,
+            z: -3,
+        };
+        level.game_mode = GameMode::Creative;
+        level.difficulty = Difficulty::Hard;
+        level.time = 1000;
+        level.day_time = 500;
+        level.weather.raining = true;
+        level.weather.rain_time = 1200;
+        level.border.size = 1000.0;
+        level.game_rules.set("doDaylightCycle", false);
+        level.game_rules.set("randomTickSpeed", 3);
+        level
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let level = level();
+        let nbt = level.to_nbt();
+
+        let data = nbt["Data"].as_compound().unwrap();
+        assert_eq!(data["RandomSeed"], Tag::Long(-42));
+        assert_eq!(data["GameType"], Tag::Int(1));
+        assert_eq!(data["Difficulty"], Tag::Byte(3));
+        assert_eq!(data["version"], Tag::Int(ANVIL_VERSION));
+
+        let read = LevelData::from_nbt(&nbt).unwrap();
+        assert_eq!(read, level);
+        assert_eq!(read.game_rules.get_bool("doDaylightCycle"), Some(false));
+        assert_eq!(read.game_rules.get_int("randomTickSpeed"), Some(3));
+    }
+
+    #[test]
+    fn test_vanilla_defaults() {
+        let mut data = Compound::new();
+        data.insert("LevelName".into(), Tag::String("old".into()));
+        data.insert("Player".into(), Tag::Compound(Compound::new()));
+
+        let mut settings = Compound::new();
+        settings.insert("seed".into(), Tag::Long(7));
+        data.insert("WorldGenSettings".into(), Tag::Compound(settings));
+
+        let mut root = Compound::new();
+        root.insert("Data".into(), Tag::Compound(data));
+
+        let mut level = LevelData::from_nbt(&root).unwrap();
+        assert_eq!(level.seed, 7);
+        assert_eq!(level.border, WorldBorder::default());
+        assert_eq!(level.difficulty, Difficulty::Easy);
+        assert!(level.other.contains_key("Player"));
+
+        // The seed goes back where it came from.
+        level.seed = 8;
+        let data = level.to_nbt()["Data"].as_compound().unwrap().clone();
+        assert!(!data.contains_key("RandomSeed"));
+        assert_eq!(
+            data["WorldGenSettings"].as_compound().unwrap()["seed"],
+            Tag::Long(8)
+        );
+
+        let mut root = level.to_nbt();
+        if let Some(Tag::Compound(data)) = root.get_mut("Data") {
+            data.insert("GameType".into(), Tag::Int(9));
+        }
+        assert!(matches!(
+            LevelData::from_nbt(&root),
+            Err(AnvilError::InvalidTag("GameType"))
+        ));
+        assert!(matches!(
+            LevelData::from_nbt(&Compound::new()),
+            Err(AnvilError::MissingTag("Data"))
+        ));
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = temp_dir("level");
+        let mut level = level();
+
+        level.save(&dir).unwrap();
+        let loaded = LevelData::load(&dir).unwrap();
+        assert!(loaded.other.contains_key("LastPlayed"));
+        level.other = loaded.other.clone();
+        assert_eq!(loaded, level);
+
+        level.time = 2000;
+        level.save(&dir).unwrap();
+        assert!(dir.join("level.dat_old").exists());
+        assert!(!dir.join("level.dat_new").exists());
+
+        // A corrupt file falls back to the previous one.
+        std::fs::write(dir.join("level.dat"), b"garbage").unwrap();
+        assert_eq!(LevelData::load(&dir).unwrap().time, 1000);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
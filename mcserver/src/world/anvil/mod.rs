@@ -0,0 +1,170 @@
+//! The Anvil format vanilla saves worlds in: region files holding 32x32
+//! chunks, each stored as compressed NBT, along with the world's settings in
+//! `level.dat` and each player's in `playerdata/`.
+
+mod chunk;
+mod dat;
+mod level;
+mod player;
+mod region;
+mod tags;
+
+pub use chunk::{decode_chunk, encode_chunk, AnvilChunk};
+pub use level::{GameRules, LevelData, Weather, WorldBorder};
+pub use player::{Experience, Food, Inventory, PlayerData, PlayerStore};
+pub use region::{Compression, RegionFile, SECTOR_SIZE};
+
+use super::super::{
+    nbt,
+    objs::{ChunkPos, RegionPos},
+    registry::BlockStateError,
+};
+use super::ChunkError;
+
+#[derive(Debug)]
+pub enum AnvilError {
+    IoError(std::io::Error),
+    Nbt(nbt::error::Error),
+    NotInRegion {
+        chunk: ChunkPos,
+        region: RegionPos,
+    },
+
+    /// The header points a chunk at sectors that are part of the header or
+    /// past the end of the file.
+    InvalidOffset {
+        chunk: ChunkPos,
+        sector: u32,
+        sectors: u32,
+    },
+
+    /// A chunk claims to be longer than the sectors it was given, or empty.
+    InvalidLength {
+        chunk: ChunkPos,
+        length: u32,
+    },
+
+    UnknownCompression(u8),
+
+    /// A region file has no free sectors left that its header can point at.
+    RegionFull(ChunkPos),
+
+    /// The chunk was saved before 1.13, whose format isn't supported, or by
+    /// a newer version than the block registry's.
+    UnsupportedDataVersion(Option<i32>),
+
+    /// A chunk says it's somewhere else than where it was read from.
+    WrongPosition {
+        expected: ChunkPos,
+        found: ChunkPos,
+    },
+
+    MissingTag(&'static str),
+
+    /// A tag has the wrong type or length.
+    InvalidTag(&'static str),
+
+    InvalidBlock(BlockStateError),
+    UnknownBiome(String),
+    UnknownItem(String),
+    Chunk(ChunkError),
+}
+
+impl std::fmt::Display for AnvilError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use AnvilError::*;
+
+        match self {
+            IoError(err) => write!(f, "{}", err),
+
+            Nbt(err) => write!(f, "{}", err),
+
+            NotInRegion { chunk, region } => write!(
+                f,
+                "chunk {}, {} is not in region {}, {}",
+                chunk.x, chunk.z, region.x, region.z
+            ),
+
+            InvalidOffset {
+                chunk,
+                sector,
+                sectors,
+            } => write!(
+                f,
+                "chunk {}, {} is stored at invalid sectors {}..{}",
+                chunk.x,
+                chunk.z,
+                sector,
+                u64::from(*sector) + u64::from(*sectors)
+            ),
+
+            InvalidLength { chunk, length } => write!(
+                f,
+                "chunk {}, {} has invalid length {}",
+                chunk.x, chunk.z, length
+            ),
+
+            UnknownCompression(id) => write!(f, "unknown compression type {}", id),
+
+            RegionFull(chunk) => write!(
+                f,
+                "region file is full, chunk {}, {} can't be saved",
+                chunk.x, chunk.z
+            ),
+
+            UnsupportedDataVersion(Some(version)) => {
+                write!(f, "unsupported data version {}", version)
+            }
+            UnsupportedDataVersion(None) => write!(f, "chunk has no data version"),
+
+            WrongPosition { expected, found } => write!(
+                f,
+                "chunk {}, {} is saved as chunk {}, {}",
+                expected.x, expected.z, found.x, found.z
+            ),
+
+            MissingTag(name) => write!(f, "missing tag {}", name),
+
+            InvalidTag(name) => write!(f, "invalid tag {}", name),
+
+            InvalidBlock(err) => write!(f, "{}", err),
+
+            UnknownBiome(name) => write!(f, "unknown biome {}", name),
+
+            UnknownItem(name) => write!(f, "unknown item {}", name),
+
+            Chunk(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for AnvilError {}
+
+impl From<std::io::Error> for AnvilError {
+    fn from(value: std::io::Error) -> Self {
+        AnvilError::IoError(value)
+    }
+}
+
+impl From<nbt::error::Error> for AnvilError {
+    fn from(value: nbt::error::Error) -> Self {
+        match value {
+            nbt::error::Error::IoError(err) => AnvilError::IoError(err),
+            err => AnvilError::Nbt(err),
+        }
+    }
+}
+
+impl From<BlockStateError> for AnvilError {
+    fn from(value: BlockStateError) -> Self {
+        AnvilError::InvalidBlock(value)
+    }
+}
+
+impl From<ChunkError> for AnvilError {
+    fn from(value: ChunkError) -> Self {
+        AnvilError::Chunk(value)
+    }
+}
+
+pub type Result<T, E = AnvilError> = std::result::Result<T, E>;
@@ -0,0 +1,580 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use super::{
+    super::super::{
+        nbt::{Compound, Tag},
+        objs::{GameMode, Identifier, ItemStack, Uuid},
+        registry::Registry,
+    },
+    dat,
+    tags::{double, float, get_list, get_or, int, long},
+    AnvilError, LevelData, Result,
+};
+
+/// The first data version saving UUIDs as four ints instead of two longs.
+const UUID_INT_ARRAY: i32 = 2515;
+
+/// The first data version saving the dimension by name instead of by id.
+const DIMENSION_NAMES: i32 = 2554;
+
+/// The items in an inventory, keyed by the slot numbers vanilla saves: in a
+/// player's inventory 0-8 are the hotbar, 9-35 the rest, 100-103 the armor
+/// from boots up and -106 the offhand.
+pub type Inventory = BTreeMap<i8, ItemStack>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Food {
+    pub level: i32,
+    pub saturation: f32,
+    pub exhaustion: f32,
+}
+
+impl Default for Food {
+    fn default() -> Self {
+        Self {
+            level: 20,
+            saturation: 5.0,
+            exhaustion: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Experience {
+    pub level: i32,
+    /// How far the bar is towards the next level, from 0 to 1.
+    pub progress: f32,
+    pub total: i32,
+}
+
+/// A player as saved in `playerdata/<uuid>.dat`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlayerData {
+    pub data_version: i32,
+    pub uuid: Uuid,
+    pub dimension: Identifier,
+    pub position: [f64; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub health: f32,
+    pub food: Food,
+    pub experience: Experience,
+    pub game_mode: GameMode,
+    /// The selected hotbar slot, from 0 to 8.
+    pub selected_slot: i32,
+    pub inventory: Inventory,
+    pub ender_chest: Inventory,
+    /// Tags this doesn't cover, kept so saving doesn't lose them.
+    pub other: Compound,
+}
+
+/// Ids older versions save dimensions by.
+const DIMENSIONS: [(i32, &str); 3] = [
+    (-1, "minecraft:the_nether"),
+    (0, "minecraft:overworld"),
+    (1, "minecraft:the_end"),
+];
+
+fn dimension(tag: &Tag) -> Option<Identifier> {
+    match tag {
+        Tag::String(name) => Identifier::parse(name).ok(),
+        Tag::Int(id) => DIMENSIONS
+            .iter()
+            .find(|(dimension, _)| dimension == id)
+            .and_then(|&(_, name)| Identifier::from_static(name).ok()),
+        _ => None,
+    }
+}
+
+fn uuid(tag: &Tag) -> Option<Uuid> {
+    match tag {
+        Tag::IntArray(ints) if ints.len() == 4 => {
+            Some(Uuid::from_int_array([ints[0], ints[1], ints[2], ints[3]]))
+        }
+        _ => None,
+    }
+}
+
+/// Reads the items saved under `name`. Like vanilla, an item `items` doesn't
+/// know, say from a newer version, is dropped and the rest still load.
+fn decode_inventory(
+    compound: &Compound,
+    name: &'static str,
+    items: &Registry,
+) -> Result<Inventory> {
+    let mut inventory = Inventory::new();
+
+    for tag in get_list(compound, name)? {
+        let item = tag.as_compound().ok_or(AnvilError::InvalidTag(name))?;
+        let slot = match item.get("Slot") {
+            Some(&Tag::Byte(slot)) => slot,
+            _ => return Err(AnvilError::InvalidTag("Slot")),
+        };
+
+        let id = match item.get("id") {
+            Some(Tag::String(id)) => id,
+            _ => return Err(AnvilError::InvalidTag("id")),
+        };
+        let item_id = match Identifier::parse(id).ok().and_then(|id| items.id(&id)) {
+            Some(item_id) => item_id,
+            None => continue,
+        };
+
+        let count = match item.get("Count") {
+            Some(&Tag::Byte(count)) => count,
+            _ => return Err(AnvilError::InvalidTag("Count")),
+        };
+        let mut stack =
+            ItemStack::new(item_id, count).map_err(|_| AnvilError::InvalidTag("Count"))?;
+
+        match item.get("tag") {
+            Some(Tag::Compound(nbt)) => stack.nbt = nbt.clone(),
+            Some(_) => return Err(AnvilError::InvalidTag("tag")),
+            None => {}
+        }
+
+        inventory.insert(slot, stack);
+    }
+
+    Ok(inventory)
+}
+
+fn encode_inventory(inventory: &Inventory, items: &Registry) -> Result<Tag> {
+    inventory
+        .iter()
+        .map(|(&slot, stack)| {
+            let id = items
+                .get(stack.item)
+                .ok_or_else(|| AnvilError::UnknownItem(stack.item.to_string()))?;
+
+            let mut item = Compound::new();
+            item.insert("Slot".into(), Tag::Byte(slot));
+            item.insert("id".into(), Tag::String(id.to_string()));
+            item.insert("Count".into(), Tag::Byte(stack.count()));
+            if !stack.nbt.is_empty() {
+                item.insert("tag".into(), Tag::Compound(stack.nbt.clone()));
+            }
+            Ok(Tag::Compound(item))
+        })
+        .collect::<Result<_>>()
+        .map(Tag::List)
+}
+
+/// Reads a list of exactly `N` numbers, like a position or rotation.
+fn numbers<T: Copy + Default, const N: usize>(
+    compound: &Compound,
+    name: &'static str,
+    default: [T; N],
+    convert: impl Fn(&Tag) -> Option<T>,
+) -> Result<[T; N]> {
+    let list = get_list(compound, name)?;
+    if list.is_empty() {
+        return Ok(default);
+    }
+    if list.len() != N {
+        return Err(AnvilError::InvalidTag(name));
+    }
+
+    let mut numbers = [T::default(); N];
+    for (number, tag) in numbers.iter_mut().zip(list) {
+        *number = convert(tag).ok_or(AnvilError::InvalidTag(name))?;
+    }
+    Ok(numbers)
+}
+
+/// Tags `PlayerData` reads into its own fields.
+const KNOWN_TAGS: &[&str] = &[
+    "DataVersion",
+    "UUID",
+    "UUIDMost",
+    "UUIDLeast",
+    "Dimension",
+    "Pos",
+    "Rotation",
+    "Health",
+    "foodLevel",
+    "foodSaturationLevel",
+    "foodExhaustionLevel",
+    "XpLevel",
+    "XpP",
+    "XpTotal",
+    "playerGameType",
+    "SelectedItemSlot",
+    "Inventory",
+    "EnderItems",
+];
+
+impl PlayerData {
+    /// Creates a player joining `level` for the first time, standing on its
+    /// spawn point.
+    pub fn new(uuid: Uuid, level: &LevelData) -> Self {
+        let spawn = level.spawn;
+
+        Self {
+            data_version: level.data_version,
+            uuid,
+            dimension: Identifier::from_static("minecraft:overworld")
+                .expect("invalid dimension name"),
+            position: [
+                f64::from(spawn.x) + 0.5,
+                f64::from(spawn.y),
+                f64::from(spawn.z) + 0.5,
+            ],
+            yaw: 0.0,
+            pitch: 0.0,
+            health: 20.0,
+            food: Food::default(),
+            experience: Experience::default(),
+            game_mode: level.game_mode,
+            selected_slot: 0,
+            inventory: Inventory::new(),
+            ender_chest: Inventory::new(),
+            other: Compound::new(),
+        }
+    }
+
+    /// Reads a player's saved data, looking up their items in `items`.
+    pub fn from_nbt(nbt: &Compound, items: &Registry) -> Result<Self> {
+        let data_version = get_or(nbt, "DataVersion", 0, int)?;
+
+        // Before 1.16 UUIDs were saved as two longs.
+        let uuid = match nbt.get("UUID") {
+            Some(tag) => uuid(tag).ok_or(AnvilError::InvalidTag("UUID"))?,
+            None => Uuid::from_most_least(
+                get_or(nbt, "UUIDMost", 0, long)?,
+                get_or(nbt, "UUIDLeast", 0, long)?,
+            ),
+        };
+
+        let overworld =
+            Identifier::from_static("minecraft:overworld").expect("invalid dimension name");
+        let food = Food::default();
+
+        let mut other = nbt.clone();
+        for name in KNOWN_TAGS {
+            other.remove(*name);
+        }
+
+        let [yaw, pitch] = numbers(nbt, "Rotation", [0.0; 2], float)?;
+
+        Ok(Self {
+            data_version,
+            uuid,
+            dimension: get_or(nbt, "Dimension", overworld, dimension)?,
+            position: numbers(nbt, "Pos", [0.0; 3], double)?,
+            yaw,
+            pitch,
+            health: get_or(nbt, "Health", 20.0, float)?,
+            food: Food {
+                level: get_or(nbt, "foodLevel", food.level, int)?,
+                saturation: get_or(nbt, "foodSaturationLevel", food.saturation, float)?,
+                exhaustion: get_or(nbt, "foodExhaustionLevel", food.exhaustion, float)?,
+            },
+            experience: Experience {
+                level: get_or(nbt, "XpLevel", 0, int)?,
+                progress: get_or(nbt, "XpP", 0.0, float)?,
+                total: get_or(nbt, "XpTotal", 0, int)?,
+            },
+            game_mode: get_or(nbt, "playerGameType", GameMode::default(), |tag| {
+                GameMode::from_id(int(tag)?)
+            })?,
+            selected_slot: get_or(nbt, "SelectedItemSlot", 0, int)?,
+            inventory: decode_inventory(nbt, "Inventory", items)?,
+            ender_chest: decode_inventory(nbt, "EnderItems", items)?,
+            other,
+        })
+    }
+
+    /// Returns the tag vanilla saves the player as, in the format of their
+    /// data version.
+    pub fn to_nbt(&self, items: &Registry) -> Result<Compound> {
+        let mut nbt = self.other.clone();
+        let mut insert = |name: &str, tag| {
+            nbt.insert(name.to_owned(), tag);
+        };
+
+        insert("DataVersion", Tag::Int(self.data_version));
+        if self.data_version >= UUID_INT_ARRAY {
+            insert("UUID", Tag::IntArray(self.uuid.to_int_array().to_vec()));
+        } else {
+            insert("UUIDMost", Tag::Long(self.uuid.most_significant()));
+            insert("UUIDLeast", Tag::Long(self.uuid.least_significant()));
+        }
+
+        let dimension = if self.data_version >= DIMENSION_NAMES {
+            Tag::String(self.dimension.to_string())
+        } else {
+            let id = DIMENSIONS
+                .iter()
+                .find(|(_, name)| *name == self.dimension.as_str())
+                .map_or(0, |&(id, _)| id);
+            Tag::Int(id)
+        };
+        insert("Dimension", dimension);
+
+        let position = self.position.iter().map(|&n| Tag::Double(n)).collect();
+        insert("Pos", Tag::List(position));
+        insert(
+            "Rotation",
+            Tag::List(vec![Tag::Float(self.yaw), Tag::Float(self.pitch)]),
+        );
+
+        insert("Health", Tag::Float(self.health));
+        insert("foodLevel", Tag::Int(self.food.level));
+        insert("foodSaturationLevel", Tag::Float(self.food.saturation));
+        insert("foodExhaustionLevel", Tag::Float(self.food.exhaustion));
+        insert("XpLevel", Tag::Int(self.experience.level));
+        insert("XpP", Tag::Float(self.experience.progress));
+        insert("XpTotal", Tag::Int(self.experience.total));
+        insert("playerGameType", Tag::Int(self.game_mode.id().into()));
+        insert("SelectedItemSlot", Tag::Int(self.selected_slot));
+        insert("Inventory", encode_inventory(&self.inventory, items)?);
+        insert("EnderItems", encode_inventory(&self.ender_chest, items)?);
+
+        Ok(nbt)
+    }
+
+    /// Loads a player from `dir`, usually the world's `playerdata`, or
+    /// returns `None` if they haven't played before.
+    pub fn load(dir: &Path, uuid: Uuid, items: &Registry) -> Result<Option<Self>> {
+        let name = file_name(uuid);
+        if !dir.join(&name).exists() && !dir.join(format!("{}_old", name)).exists() {
+            return Ok(None);
+        }
+
+        dat::load(dir, &name, |nbt| Self::from_nbt(nbt, items)).map(Some)
+    }
+
+    /// Saves the player to `dir`, keeping their previous file like vanilla.
+    pub fn save(&self, dir: &Path, items: &Registry) -> Result<()> {
+        dat::save(dir, &file_name(self.uuid), self.to_nbt(items)?)
+    }
+}
+
+fn file_name(uuid: Uuid) -> String {
+    format!("{}.dat", uuid)
+}
+
+/// The players online in a world, which are saved when they leave and
+/// periodically while they play.
+pub struct PlayerStore {
+    dir: PathBuf,
+    items: &'static Registry,
+    players: HashMap<Uuid, PlayerData>,
+    autosave_interval: Duration,
+    last_save: Instant,
+}
+
+impl PlayerStore {
+    /// How often vanilla saves, every 6000 ticks.
+    pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(300);
+
+    /// Creates a store for the world in `world`, creating its `playerdata`
+    /// directory if needed.
+    pub fn new(world: &Path, items: &'static Registry) -> Result<Self> {
+        let dir = world.join("playerdata");
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            items,
+            players: HashMap::new(),
+            autosave_interval: Self::AUTOSAVE_INTERVAL,
+            last_save: Instant::now(),
+        })
+    }
+
+    pub fn set_autosave_interval(&mut self, interval: Duration) {
+        self.autosave_interval = interval;
+    }
+
+    /// Loads a joining player where they logged out, or creates them with
+    /// `new_player` if they haven't played before.
+    pub fn join(
+        &mut self,
+        uuid: Uuid,
+        new_player: impl FnOnce() -> PlayerData,
+    ) -> Result<&mut PlayerData> {
+        let player = match PlayerData::load(&self.dir, uuid, self.items)? {
+            Some(player) => player,
+            None => new_player(),
+        };
+
+        self.players.insert(uuid, player);
+        Ok(self.players.get_mut(&uuid).unwrap())
+    }
+
+    pub fn get(&self, uuid: Uuid) -> Option<&PlayerData> {
+        self.players.get(&uuid)
+    }
+
+    pub fn get_mut(&mut self, uuid: Uuid) -> Option<&mut PlayerData> {
+        self.players.get_mut(&uuid)
+    }
+
+    /// Saves a leaving player and forgets them. If saving fails they stay
+    /// online, so the next save tries again.
+    pub fn disconnect(&mut self, uuid: Uuid) -> Result<Option<PlayerData>> {
+        if let Some(player) = self.players.get(&uuid) {
+            player.save(&self.dir, self.items)?;
+        }
+
+        Ok(self.players.remove(&uuid))
+    }
+
+    /// Saves everyone online if the autosave interval has passed since the
+    /// last save, returning whether it did.
+    pub fn tick(&mut self, now: Instant) -> Result<bool> {
+        if now.saturating_duration_since(self.last_save) < self.autosave_interval {
+            return Ok(false);
+        }
+
+        self.save_all()?;
+        self.last_save = now;
+        Ok(true)
+    }
+
+    /// Saves everyone online, returning the first error after trying all of
+    /// them.
+    pub fn save_all(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for player in self.players.values() {
+            if let Err(err) = player.save(&self.dir, self.items) {
+                result = result.and(Err(err));
+            }
+        }
+
+        self.last_save = Instant::now();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{objs::Position, registry::Registries, world::test_util::temp_dir};
+
+    fn items() -> &'static Registry {
+        Registries::for_protocol(404).unwrap().items()
+    }
+
+    fn player(data_version: i32) -> PlayerData {
+        let mut level = LevelData::new("world", 0, data_version);
+        level.spawn = Position { x: 5, y: 70, z: -8 };
+
+        let mut player = PlayerData::new(Uuid(0x069a79f444e94726a5befca90e38aaf5), &level);
+        player.dimension = Identifier::parse("the_nether").unwrap();
+        player.yaw = 90.0;
+        player.health = 12.5;
+        player.food.level = 17;
+        player.experience = Experience {
+            level: 3,
+            progress: 0.5,
+            total: 30,
+        };
+        player.selected_slot = 4;
+
+        let mut sword = ItemStack::new(1, 1).unwrap();
+        sword.set_damage(10);
+        player.inventory.insert(0, sword);
+        player
+            .inventory
+            .insert(-106, ItemStack::new(2, 64).unwrap());
+        player.ender_chest.insert(26, ItemStack::new(1, 3).unwrap());
+        player
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let items = items();
+
+        for &data_version in &[1976, 2975] {
+            let player = player(data_version);
+            assert_eq!(player.position, [5.5, 70.0, -7.5]);
+
+            let nbt = player.to_nbt(items).unwrap();
+            assert_eq!(PlayerData::from_nbt(&nbt, items).unwrap(), player);
+
+            if data_version < UUID_INT_ARRAY {
+                assert_eq!(nbt["Dimension"], Tag::Int(-1));
+                assert!(nbt.contains_key("UUIDMost"));
+            } else {
+                assert_eq!(nbt["Dimension"], Tag::String("minecraft:the_nether".into()));
+                assert!(nbt.contains_key("UUID"));
+            }
+
+            let item = match &nbt["Inventory"] {
+                Tag::List(inventory) => inventory[0].as_compound().unwrap(),
+                _ => panic!("inventory isn't a list"),
+            };
+            assert_eq!(item["id"], Tag::String("minecraft:granite".into()));
+            assert_eq!(item["Slot"], Tag::Byte(-106));
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let items = items();
+        let nbt = player(2975).to_nbt(items).unwrap();
+
+        let mut position = nbt;
+        position.insert("Pos".into(), Tag::List(vec![Tag::Double(0.0)]));
+        assert!(matches!(
+            PlayerData::from_nbt(&position, items),
+            Err(AnvilError::InvalidTag("Pos"))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_item() {
+        let items = items();
+        let player = player(2975);
+        let mut nbt = player.to_nbt(items).unwrap();
+
+        // The offhand stack comes first, then the sword in slot 0.
+        if let Some(Tag::List(inventory)) = nbt.get_mut("Inventory") {
+            if let Tag::Compound(item) = &mut inventory[0] {
+                item.insert("id".into(), Tag::String("minecraft:nope".into()));
+            }
+        }
+
+        let loaded = PlayerData::from_nbt(&nbt, items).unwrap();
+        assert_eq!(loaded.inventory.get(&-106), None);
+        assert_eq!(loaded.inventory.get(&0), player.inventory.get(&0));
+        assert_eq!(loaded.ender_chest, player.ender_chest);
+    }
+
+    #[test]
+    fn test_store() {
+        let world = temp_dir("players");
+        let mut store = PlayerStore::new(&world, items()).unwrap();
+        let player = player(2975);
+        let uuid = player.uuid;
+
+        let joined = store.join(uuid, || player.clone()).unwrap();
+        joined.position = [1.0, 2.0, 3.0];
+        assert!(!world.join("playerdata").join(file_name(uuid)).exists());
+
+        let start = Instant::now();
+        store.set_autosave_interval(Duration::from_secs(60));
+        store.last_save = start;
+        assert!(!store.tick(start + Duration::from_secs(59)).unwrap());
+        assert!(store.tick(start + Duration::from_secs(60)).unwrap());
+        assert!(world.join("playerdata").join(file_name(uuid)).exists());
+
+        store.get_mut(uuid).unwrap().health = 1.0;
+        let left = store.disconnect(uuid).unwrap().unwrap();
+        assert!(store.get(uuid).is_none());
+
+        // They come back where they left.
+        let rejoined = store.join(uuid, || unreachable!()).unwrap();
+        assert_eq!(*rejoined, left);
+        assert_eq!(rejoined.position, [1.0, 2.0, 3.0]);
+
+        std::fs::remove_dir_all(world).unwrap();
+    }
+}
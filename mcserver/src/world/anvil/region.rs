@@ -0,0 +1,773 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+
+use super::{
+    super::super::{
+        nbt::{self, Compound, Limits, Tag},
+        objs::{BitSet, ChunkPos, RegionPos},
+    },
+    AnvilError, Result,
+};
+
+/// Region files are split in sectors of this many bytes, which chunks are
+/// allocated whole.
+pub const SECTOR_SIZE: usize = 4096;
+
+/// The location table and the timestamp table take a sector each.
+const HEADER_SECTORS: u32 = 2;
+
+const CHUNKS: usize = 1024;
+
+/// Chunks can take at most this many sectors, as their count has a byte.
+const MAX_SECTORS: usize = 255;
+
+/// Chunks must start before this sector, as their offset has 24 bits.
+const MAX_SECTOR: u32 = 1 << 24;
+
+/// Set on the compression type of chunks too large for their region file,
+/// which are stored in a `.mcc` file of their own instead.
+const EXTERNAL: u8 = 0x80;
+
+/// How a chunk's NBT is compressed.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Compression {
+    Gzip,
+    Zlib,
+    None,
+}
+
+impl Compression {
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Compression::Gzip),
+            2 => Some(Compression::Zlib),
+            3 => Some(Compression::None),
+            _ => None,
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        match self {
+            Compression::Gzip => 1,
+            Compression::Zlib => 2,
+            Compression::None => 3,
+        }
+    }
+
+    fn decoder<'a, R: Read + 'a>(self, r: R) -> Box<dyn Read + 'a> {
+        match self {
+            Compression::Gzip => Box::new(GzDecoder::new(r)),
+            Compression::Zlib => Box::new(ZlibDecoder::new(r)),
+            Compression::None => Box::new(r),
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let level = flate2::Compression::default();
+
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), level);
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), level);
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::None => Ok(data.to_vec()),
+        }
+    }
+}
+
+/// A region file, holding the chunks of a 32x32 area.
+#[derive(Debug)]
+pub struct RegionFile {
+    dir: PathBuf,
+    pos: RegionPos,
+    file: File,
+    /// The first sector and sector count of every chunk, ordered like
+    /// `ChunkPos::region_index`, with zero for missing chunks.
+    locations: Box<[u32; CHUNKS]>,
+    /// When every chunk was last saved, in seconds since the Unix epoch.
+    timestamps: Box<[u32; CHUNKS]>,
+    /// How many sectors the file holds, counting a partial one at the end.
+    sectors: u32,
+    /// Sectors taken by the header or by chunks.
+    used: BitSet,
+    /// Chunks whose header entries were found to point past the end of the
+    /// file or at sectors another entry uses when it was opened.
+    corrupt: BitSet,
+}
+
+impl RegionFile {
+    /// Opens the file of a region in a world's region directory and reads
+    /// its header, for reading only. An empty file, as left by a crash, has
+    /// no chunks, and a header that's cut off loses the chunks it's missing.
+    pub fn open(dir: &Path, pos: RegionPos) -> Result<Self> {
+        let file = File::open(dir.join(pos.file_name()))?;
+        Self::from_file(dir, pos, file)
+    }
+
+    /// Opens the file of a region for reading and writing, creating it if
+    /// it doesn't exist yet. A file shorter than its header is padded with
+    /// zeros.
+    pub fn create(dir: &Path, pos: RegionPos) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(pos.file_name()))?;
+        let mut region = Self::from_file(dir, pos, file)?;
+
+        if region.sectors < HEADER_SECTORS {
+            region
+                .file
+                .set_len(HEADER_SECTORS as u64 * SECTOR_SIZE as u64)?;
+            region.file.sync_all()?;
+            region.sectors = HEADER_SECTORS;
+        }
+
+        Ok(region)
+    }
+
+    fn from_file(dir: &Path, pos: RegionPos, mut file: File) -> Result<Self> {
+        let len = file.metadata()?.len();
+
+        let mut locations = Box::new([0; CHUNKS]);
+        let mut timestamps = Box::new([0; CHUNKS]);
+
+        // A header cut off by a crash is read as far as it goes, and the
+        // chunks whose entries are missing are too.
+        let header_len = HEADER_SECTORS as usize * SECTOR_SIZE;
+        let mut header = Vec::with_capacity(header_len);
+        (&mut file)
+            .take(header_len as u64)
+            .read_to_end(&mut header)?;
+        header.resize(header_len, 0);
+
+        let mut entries = header
+            .chunks_exact(4)
+            .map(|entry| u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]));
+        locations
+            .iter_mut()
+            .for_each(|l| *l = entries.next().unwrap());
+        timestamps
+            .iter_mut()
+            .for_each(|t| *t = entries.next().unwrap());
+
+        let mut region = Self {
+            dir: dir.to_owned(),
+            pos,
+            file,
+            locations,
+            timestamps,
+            sectors: len.div_ceil(SECTOR_SIZE as u64).min(u32::MAX.into()) as u32,
+            used: (0..HEADER_SECTORS as usize).collect(),
+            corrupt: BitSet::new(),
+        };
+
+        // Find the sectors more than one entry points at, which can't be
+        // told apart, so that every entry sharing them is corrupt.
+        let mut seen = BitSet::new();
+        let mut shared = BitSet::new();
+        for index in 0..CHUNKS {
+            match region.sectors_of(index) {
+                Some((sector, sectors)) => {
+                    for sector in sector as usize..(sector + sectors) as usize {
+                        if seen.get(sector) {
+                            shared.set(sector, true);
+                        }
+                        seen.set(sector, true);
+                    }
+                }
+                None if region.locations[index] != 0 => region.corrupt.set(index, true),
+                None => {}
+            }
+        }
+
+        // Corrupt entries don't reserve anything, so their sectors may be
+        // reused once the chunk is saved again.
+        for index in 0..CHUNKS {
+            if let Some((sector, sectors)) = region.sectors_of(index) {
+                let range = sector as usize..(sector + sectors) as usize;
+                if range.clone().any(|sector| shared.get(sector)) {
+                    region.corrupt.set(index, true);
+                    continue;
+                }
+
+                for sector in range {
+                    region.used.set(sector, true);
+                }
+            }
+        }
+
+        Ok(region)
+    }
+
+    /// Returns the sectors of a chunk, if it has any and they're valid.
+    fn sectors_of(&self, index: usize) -> Option<(u32, u32)> {
+        let location = self.locations[index];
+        let (sector, sectors) = (location >> 8, location & lsb!(8));
+
+        let valid = sector >= HEADER_SECTORS
+            && sectors > 0
+            && sector + sectors <= self.sectors
+            && !self.corrupt.get(index);
+        valid.then_some((sector, sectors))
+    }
+
+    pub fn pos(&self) -> RegionPos {
+        self.pos
+    }
+
+    fn index(&self, chunk: ChunkPos) -> Result<usize> {
+        if chunk.region() != self.pos {
+            return Err(AnvilError::NotInRegion {
+                chunk,
+                region: self.pos,
+            });
+        }
+
+        Ok(chunk.region_index())
+    }
+
+    /// Returns whether the header has an entry for a chunk, which may still
+    /// turn out to be corrupt.
+    pub fn has_chunk(&self, chunk: ChunkPos) -> bool {
+        self.index(chunk)
+            .is_ok_and(|index| self.locations[index] != 0)
+    }
+
+    /// Returns when a chunk was last saved, in seconds since the Unix epoch.
+    pub fn timestamp(&self, chunk: ChunkPos) -> Option<u32> {
+        let index = self.index(chunk).ok()?;
+        (self.locations[index] != 0).then_some(self.timestamps[index])
+    }
+
+    /// Returns every chunk the header has an entry for.
+    pub fn chunks(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        (0..CHUNKS)
+            .filter(move |&index| self.locations[index] != 0)
+            .filter_map(move |index| self.pos.chunk(index as i32 & lsb!(5), index as i32 >> 5))
+    }
+
+    /// Reads a chunk's NBT, or returns `None` if it hasn't been saved.
+    pub fn read_chunk(&mut self, chunk: ChunkPos) -> Result<Option<Compound>> {
+        let (compression, data) = match self.read_compressed(chunk)? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let tag = nbt::read_named(compression.decoder(&data[..]), Limits::FILE)?;
+
+        match tag {
+            Some((_, Tag::Compound(compound))) => Ok(Some(compound)),
+            Some(_) => Err(AnvilError::InvalidTag("root")),
+            None => Err(AnvilError::MissingTag("root")),
+        }
+    }
+
+    /// Reads a chunk's data as it's stored, along with how it's compressed.
+    fn read_compressed(&mut self, chunk: ChunkPos) -> Result<Option<(Compression, Vec<u8>)>> {
+        let index = self.index(chunk)?;
+        let location = self.locations[index];
+        if location == 0 {
+            return Ok(None);
+        }
+
+        let (sector, sectors) = self.sectors_of(index).ok_or(AnvilError::InvalidOffset {
+            chunk,
+            sector: location >> 8,
+            sectors: location & lsb!(8),
+        })?;
+
+        self.file
+            .seek(SeekFrom::Start(u64::from(sector) * SECTOR_SIZE as u64))?;
+
+        let mut prefix = [0; 5];
+        self.file.read_exact(&mut prefix)?;
+        let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]);
+        let compression_id = prefix[4];
+
+        // The length counts the compression type, but not itself.
+        let available = sectors as usize * SECTOR_SIZE - 4;
+        if length == 0 || length as usize > available {
+            return Err(AnvilError::InvalidLength { chunk, length });
+        }
+
+        let compression = Compression::from_id(compression_id & !EXTERNAL)
+            .ok_or(AnvilError::UnknownCompression(compression_id))?;
+
+        let data = if compression_id & EXTERNAL != 0 {
+            std::fs::read(self.external_path(chunk))?
+        } else {
+            let mut data = vec![0; length as usize - 1];
+            self.file.read_exact(&mut data)?;
+            data
+        };
+
+        Ok(Some((compression, data)))
+    }
+
+    fn external_path(&self, chunk: ChunkPos) -> PathBuf {
+        self.dir.join(format!("c.{}.{}.mcc", chunk.x, chunk.z))
+    }
+
+    /// Saves a chunk's NBT, compressed with zlib as vanilla does.
+    ///
+    /// The chunk is written to free sectors before the header points to
+    /// them, so a crash midway leaves either the old or the new chunk.
+    /// Chunks too large for the file go into a `.mcc` file of their own.
+    pub fn write_chunk(&mut self, chunk: ChunkPos, nbt: &Compound) -> Result<()> {
+        self.write_chunk_with(chunk, nbt, Compression::Zlib)
+    }
+
+    pub fn write_chunk_with(
+        &mut self,
+        chunk: ChunkPos,
+        nbt: &Compound,
+        compression: Compression,
+    ) -> Result<()> {
+        let index = self.index(chunk)?;
+
+        let mut raw = Vec::new();
+        nbt::write_named(&mut raw, "", &Tag::Compound(nbt.clone()))?;
+        let data = compression.compress(&raw)?;
+
+        let external = 5 + data.len() > MAX_SECTORS * SECTOR_SIZE;
+        let external_path = self.external_path(chunk);
+
+        let len = if external { 5 } else { 5 + data.len() };
+        let sectors = len.div_ceil(SECTOR_SIZE) as u32;
+        let sector = self.allocate(sectors);
+        if sector >= MAX_SECTOR {
+            return Err(AnvilError::RegionFull(chunk));
+        }
+
+        let payload = if external {
+            // Written aside and renamed, so the old file stays whole until
+            // the new one is.
+            let temp = external_path.with_extension("mcc.tmp");
+            let mut file = File::create(&temp)?;
+            file.write_all(&data)?;
+            file.sync_all()?;
+            std::fs::rename(&temp, &external_path)?;
+
+            let mut payload = 1u32.to_be_bytes().to_vec();
+            payload.push(compression.id() | EXTERNAL);
+            payload
+        } else {
+            let mut payload = (data.len() as u32 + 1).to_be_bytes().to_vec();
+            payload.push(compression.id());
+            payload.extend_from_slice(&data);
+            payload
+        };
+
+        let padding = sectors as usize * SECTOR_SIZE - payload.len();
+        let mut padded = payload;
+        padded.resize(padded.len() + padding, 0);
+
+        self.file
+            .seek(SeekFrom::Start(u64::from(sector) * SECTOR_SIZE as u64))?;
+        self.file.write_all(&padded)?;
+        self.file.sync_data()?;
+        self.sectors = self.sectors.max(sector + sectors);
+
+        let old = self.sectors_of(index);
+        self.set_header(index, sector << 8 | sectors, timestamp())?;
+
+        if let Some((old, old_sectors)) = old {
+            self.free(old, old_sectors);
+        }
+        for sector in sector..sector + sectors {
+            self.used.set(sector as usize, true);
+        }
+
+        if !external && external_path.exists() {
+            std::fs::remove_file(external_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes a chunk, so it will be generated again.
+    pub fn remove_chunk(&mut self, chunk: ChunkPos) -> Result<()> {
+        let index = self.index(chunk)?;
+
+        let old = self.sectors_of(index);
+        self.set_header(index, 0, 0)?;
+        if let Some((sector, sectors)) = old {
+            self.free(sector, sectors);
+        }
+
+        let external_path = self.external_path(chunk);
+        if external_path.exists() {
+            std::fs::remove_file(external_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the first of `sectors` free sectors in a row, which may lie
+    /// past the end of the file.
+    fn allocate(&self, sectors: u32) -> u32 {
+        let mut start = HEADER_SECTORS;
+
+        for sector in HEADER_SECTORS..self.sectors {
+            if self.used.get(sector as usize) {
+                start = sector + 1;
+            } else if sector + 1 - start == sectors {
+                return start;
+            }
+        }
+
+        start
+    }
+
+    fn free(&mut self, sector: u32, sectors: u32) {
+        for sector in sector..sector + sectors {
+            self.used.set(sector as usize, false);
+        }
+    }
+
+    /// Points a chunk at new sectors, which is a single small write so it's
+    /// unlikely to be torn.
+    fn set_header(&mut self, index: usize, location: u32, timestamp: u32) -> Result<()> {
+        self.file.seek(SeekFrom::Start(index as u64 * 4))?;
+        self.file.write_all(&location.to_be_bytes())?;
+        self.file
+            .seek(SeekFrom::Start((SECTOR_SIZE + index * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())?;
+        self.file.sync_data()?;
+
+        self.locations[index] = location;
+        self.timestamps[index] = timestamp;
+        self.corrupt.set(index, false);
+        Ok(())
+    }
+}
+
+fn timestamp() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::test_util::temp_dir;
+
+    fn compound(value: i32) -> Compound {
+        let mut compound = Compound::new();
+        compound.insert("value".into(), Tag::Int(value));
+        compound
+    }
+
+    fn compress(compression: Compression, value: i32) -> Vec<u8> {
+        let mut raw = Vec::new();
+        nbt::write_named(&mut raw, "", &Tag::Compound(compound(value))).unwrap();
+        compression.compress(&raw).unwrap()
+    }
+
+    /// Builds a region file by hand, with chunks given as their location
+    /// entry and the bytes of their sectors.
+    fn region(dir: &Path, chunks: &[(usize, u32, Vec<u8>)]) {
+        let mut file = vec![0; 2 * SECTOR_SIZE];
+
+        for (index, location, data) in chunks {
+            file[index * 4..index * 4 + 4].copy_from_slice(&location.to_be_bytes());
+            file[SECTOR_SIZE + index * 4..SECTOR_SIZE + index * 4 + 4]
+                .copy_from_slice(&1234u32.to_be_bytes());
+
+            let start = (location >> 8) as usize * SECTOR_SIZE;
+            if file.len() < start + data.len() {
+                file.resize(start + data.len(), 0);
+            }
+            file[start..start + data.len()].copy_from_slice(data);
+        }
+
+        std::fs::write(dir.join(RegionPos { x: 0, z: -1 }.file_name()), file).unwrap();
+    }
+
+    fn payload(compression_id: u8, data: &[u8]) -> Vec<u8> {
+        let mut payload = ((data.len() + 1) as u32).to_be_bytes().to_vec();
+        payload.push(compression_id);
+        payload.extend_from_slice(data);
+        payload
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = temp_dir("region-read");
+        let pos = RegionPos { x: 0, z: -1 };
+        let big = compress(Compression::Zlib, 4);
+
+        region(
+            &dir,
+            &[
+                (0, 2 << 8 | 1, payload(1, &compress(Compression::Gzip, 1))),
+                (1, 3 << 8 | 1, payload(2, &compress(Compression::Zlib, 2))),
+                (33, 4 << 8 | 1, payload(3, &compress(Compression::None, 3))),
+                (34, 5 << 8 | 1, payload(2 | EXTERNAL, &[])),
+            ],
+        );
+        std::fs::write(dir.join("c.2.-31.mcc"), big).unwrap();
+
+        let mut region = RegionFile::open(&dir, pos).unwrap();
+        assert_eq!(region.chunks().count(), 4);
+
+        for &(chunk, value) in &[
+            (pos.chunk(0, 0).unwrap(), 1),
+            (pos.chunk(1, 0).unwrap(), 2),
+            (pos.chunk(1, 1).unwrap(), 3),
+            (pos.chunk(2, 1).unwrap(), 4),
+        ] {
+            assert_eq!(region.read_chunk(chunk).unwrap(), Some(compound(value)));
+            assert_eq!(region.timestamp(chunk), Some(1234));
+        }
+
+        assert_eq!(region.read_chunk(pos.chunk(5, 5).unwrap()).unwrap(), None);
+        assert_eq!(region.timestamp(pos.chunk(5, 5).unwrap()), None);
+        assert!(matches!(
+            region.read_chunk(ChunkPos { x: 0, z: 0 }),
+            Err(AnvilError::NotInRegion { .. })
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_corruption() {
+        let dir = temp_dir("region-corruption");
+        let pos = RegionPos { x: 0, z: -1 };
+        let data = compress(Compression::None, 0);
+
+        region(
+            &dir,
+            &[
+                // In the header.
+                (0, 1 << 8 | 1, vec![]),
+                // Past the end of the file.
+                (1, 3 << 8 | 9, payload(3, &data)),
+                // No sectors.
+                (2, 3 << 8, vec![]),
+                // Longer than its sectors.
+                (3, 4 << 8 | 1, 5000u32.to_be_bytes().to_vec()),
+                (4, 5 << 8 | 1, payload(42, &data)),
+                (5, 6 << 8 | 1, payload(3, &data[..data.len() - 1])),
+                (6, 7 << 8 | 1, payload(3 | EXTERNAL, &[])),
+                // Sharing sector 9.
+                (7, 8 << 8 | 2, payload(3, &data)),
+                (8, 9 << 8 | 1, payload(3, &data)),
+            ],
+        );
+
+        let mut region = RegionFile::open(&dir, pos).unwrap();
+        let mut read = |x| region.read_chunk(pos.chunk(x, 0).unwrap());
+
+        assert!(matches!(
+            read(0),
+            Err(AnvilError::InvalidOffset { sector: 1, .. })
+        ));
+        assert!(matches!(
+            read(1),
+            Err(AnvilError::InvalidOffset { sector: 3, .. })
+        ));
+        assert!(matches!(
+            read(2),
+            Err(AnvilError::InvalidOffset { sectors: 0, .. })
+        ));
+        assert!(matches!(
+            read(3),
+            Err(AnvilError::InvalidLength { length: 5000, .. })
+        ));
+        assert!(matches!(read(4), Err(AnvilError::UnknownCompression(42))));
+        assert!(matches!(read(5), Err(AnvilError::IoError(_))));
+        assert!(matches!(read(6), Err(AnvilError::IoError(_))));
+        assert!(matches!(
+            read(7),
+            Err(AnvilError::InvalidOffset { sector: 8, .. })
+        ));
+        assert!(matches!(
+            read(8),
+            Err(AnvilError::InvalidOffset { sector: 9, .. })
+        ));
+
+        // Growing the file doesn't make entries that were past its end valid,
+        // and the shared sectors are free to take.
+        let mut region = RegionFile::create(&dir, pos).unwrap();
+        let big = pos.chunk(10, 0).unwrap();
+        region
+            .write_chunk_with(big, &sized(5 * SECTOR_SIZE), Compression::None)
+            .unwrap();
+        assert_eq!(region.locations[10] >> 8, 8);
+        assert_eq!(
+            region.read_chunk(big).unwrap(),
+            Some(sized(5 * SECTOR_SIZE))
+        );
+        for &x in &[1, 7, 8] {
+            assert!(matches!(
+                region.read_chunk(pos.chunk(x, 0).unwrap()),
+                Err(AnvilError::InvalidOffset { .. })
+            ));
+        }
+
+        // An empty file, as left by a crash, has no chunks.
+        std::fs::write(dir.join(pos.file_name()), []).unwrap();
+        let region = RegionFile::open(&dir, pos).unwrap();
+        assert_eq!(region.chunks().count(), 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        let dir = temp_dir("region-truncated");
+        let pos = RegionPos { x: 0, z: -1 };
+        let path = dir.join(pos.file_name());
+
+        // Cut off in the location table, after a crash.
+        region(
+            &dir,
+            &[(0, 2 << 8 | 1, payload(2, &compress(Compression::Zlib, 1)))],
+        );
+        let mut file = std::fs::read(&path).unwrap();
+        file.truncate(100);
+        std::fs::write(&path, file).unwrap();
+
+        // The entries that are left point past the end of the file.
+        let mut region = RegionFile::open(&dir, pos).unwrap();
+        let chunk = pos.chunk(0, 0).unwrap();
+        assert_eq!(region.chunks().collect::<Vec<_>>(), vec![chunk]);
+        assert_eq!(region.timestamp(chunk), Some(0));
+        assert!(matches!(
+            region.read_chunk(chunk),
+            Err(AnvilError::InvalidOffset { sector: 2, .. })
+        ));
+
+        let mut region = RegionFile::create(&dir, pos).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        assert_eq!(len, 2 * SECTOR_SIZE as u64);
+
+        region.write_chunk(chunk, &compound(2)).unwrap();
+        let mut region = RegionFile::open(&dir, pos).unwrap();
+        assert_eq!(region.read_chunk(chunk).unwrap(), Some(compound(2)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Returns a chunk whose NBT takes about `len` bytes.
+    fn sized(len: usize) -> Compound {
+        let mut compound = Compound::new();
+        compound.insert("data".into(), Tag::ByteArray(vec![7; len]));
+        compound
+    }
+
+    #[test]
+    fn test_write() {
+        let dir = temp_dir("region-write");
+        let pos = RegionPos { x: -1, z: 2 };
+        let (a, b, c) = (
+            pos.chunk(0, 0).unwrap(),
+            pos.chunk(31, 0).unwrap(),
+            pos.chunk(0, 31).unwrap(),
+        );
+
+        let mut region = RegionFile::create(&dir, pos).unwrap();
+        assert_eq!(region.chunks().count(), 0);
+
+        let none = Compression::None;
+        region.write_chunk_with(a, &sized(100), none).unwrap();
+        region.write_chunk_with(b, &sized(5000), none).unwrap();
+        assert_eq!(region.sectors_of(a.region_index()), Some((2, 1)));
+        assert_eq!(region.sectors_of(b.region_index()), Some((3, 2)));
+
+        // A chunk that outgrows its sectors moves to the end of the file,
+        // and the sector it leaves is reused.
+        region.write_chunk_with(a, &sized(6000), none).unwrap();
+        assert_eq!(region.sectors_of(a.region_index()), Some((5, 2)));
+        region.write_chunk_with(c, &sized(10), none).unwrap();
+        assert_eq!(region.sectors_of(c.region_index()), Some((2, 1)));
+
+        // The old sectors stay untouched until the new ones are written, in
+        // case of a crash.
+        region.write_chunk(c, &compound(3)).unwrap();
+        assert_eq!(region.sectors_of(c.region_index()), Some((7, 1)));
+        assert!(region.timestamp(c).unwrap() > 1_500_000_000);
+
+        let len = std::fs::metadata(dir.join(pos.file_name())).unwrap().len();
+        assert_eq!(len, 8 * SECTOR_SIZE as u64);
+
+        let mut region = RegionFile::open(&dir, pos).unwrap();
+        assert_eq!(region.chunks().collect::<Vec<_>>(), vec![a, b, c]);
+        assert_eq!(region.read_chunk(a).unwrap(), Some(sized(6000)));
+        assert_eq!(region.read_chunk(b).unwrap(), Some(sized(5000)));
+        assert_eq!(region.read_chunk(c).unwrap(), Some(compound(3)));
+
+        let mut region = RegionFile::create(&dir, pos).unwrap();
+        region.remove_chunk(b).unwrap();
+        assert_eq!(region.read_chunk(b).unwrap(), None);
+        region.write_chunk_with(b, &sized(10000), none).unwrap();
+        assert_eq!(region.sectors_of(b.region_index()), Some((2, 3)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_full() {
+        let dir = temp_dir("region-full");
+        let pos = RegionPos { x: 0, z: 0 };
+        let chunk = pos.chunk(0, 0).unwrap();
+
+        // Pretend every sector the header can point at is taken.
+        let mut region = RegionFile::create(&dir, pos).unwrap();
+        region.sectors = MAX_SECTOR;
+        region.used = (0..MAX_SECTOR as usize).collect();
+
+        assert!(matches!(
+            region.write_chunk(chunk, &compound(1)),
+            Err(AnvilError::RegionFull(full)) if full == chunk
+        ));
+        assert!(!region.has_chunk(chunk));
+        let len = std::fs::metadata(dir.join(pos.file_name())).unwrap().len();
+        assert_eq!(len, 2 * SECTOR_SIZE as u64);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_external() {
+        let dir = temp_dir("region-external");
+        let pos = RegionPos { x: 0, z: 0 };
+        let chunk = pos.chunk(1, 2).unwrap();
+        let external = dir.join("c.1.2.mcc");
+
+        let mut region = RegionFile::create(&dir, pos).unwrap();
+        let big = sized(MAX_SECTORS * SECTOR_SIZE);
+        region
+            .write_chunk_with(chunk, &big, Compression::None)
+            .unwrap();
+        assert!(external.exists());
+        assert_eq!(region.sectors_of(chunk.region_index()), Some((2, 1)));
+        assert_eq!(region.read_chunk(chunk).unwrap(), Some(big));
+
+        region.write_chunk(chunk, &compound(1)).unwrap();
+        assert!(!external.exists());
+        assert_eq!(region.read_chunk(chunk).unwrap(), Some(compound(1)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
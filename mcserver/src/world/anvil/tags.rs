@@ -0,0 +1,105 @@
+//! Helpers for reading the tags vanilla saves, which fail with the name of
+//! the tag that's missing or of the wrong type.
+
+use super::{
+    super::super::nbt::{Compound, Tag},
+    AnvilError, Result,
+};
+
+pub(super) fn get<'a>(compound: &'a Compound, name: &'static str) -> Result<&'a Tag> {
+    compound.get(name).ok_or(AnvilError::MissingTag(name))
+}
+
+pub(super) fn get_int(compound: &Compound, name: &'static str) -> Result<i32> {
+    match get(compound, name)? {
+        Tag::Int(value) => Ok(*value),
+        _ => Err(AnvilError::InvalidTag(name)),
+    }
+}
+
+pub(super) fn get_compound<'a>(compound: &'a Compound, name: &'static str) -> Result<&'a Compound> {
+    match get(compound, name)? {
+        Tag::Compound(value) => Ok(value),
+        _ => Err(AnvilError::InvalidTag(name)),
+    }
+}
+
+pub(super) fn get_string<'a>(compound: &'a Compound, name: &'static str) -> Result<&'a str> {
+    match get(compound, name)? {
+        Tag::String(value) => Ok(value),
+        _ => Err(AnvilError::InvalidTag(name)),
+    }
+}
+
+/// Returns a list, where a missing one counts as empty, as vanilla leaves
+/// out empty lists in some versions.
+pub(super) fn get_list<'a>(compound: &'a Compound, name: &'static str) -> Result<&'a [Tag]> {
+    match compound.get(name) {
+        Some(Tag::List(values)) => Ok(values),
+        Some(_) => Err(AnvilError::InvalidTag(name)),
+        None => Ok(&[]),
+    }
+}
+
+pub(super) fn get_long_array<'a>(
+    compound: &'a Compound,
+    name: &'static str,
+) -> Result<Option<&'a [i64]>> {
+    match compound.get(name) {
+        Some(Tag::LongArray(values)) => Ok(Some(values)),
+        Some(_) => Err(AnvilError::InvalidTag(name)),
+        None => Ok(None),
+    }
+}
+
+/// Returns a tag converted by `convert`, or `default` if it's missing, as
+/// vanilla reads most settings.
+pub(super) fn get_or<T>(
+    compound: &Compound,
+    name: &'static str,
+    default: T,
+    convert: impl FnOnce(&Tag) -> Option<T>,
+) -> Result<T> {
+    match compound.get(name) {
+        Some(tag) => convert(tag).ok_or(AnvilError::InvalidTag(name)),
+        None => Ok(default),
+    }
+}
+
+// Conversions for `get_or`.
+
+pub(super) fn int(tag: &Tag) -> Option<i32> {
+    match *tag {
+        Tag::Int(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub(super) fn long(tag: &Tag) -> Option<i64> {
+    match *tag {
+        Tag::Long(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub(super) fn float(tag: &Tag) -> Option<f32> {
+    match *tag {
+        Tag::Float(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub(super) fn double(tag: &Tag) -> Option<f64> {
+    match *tag {
+        Tag::Double(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Booleans are saved as bytes.
+pub(super) fn boolean(tag: &Tag) -> Option<bool> {
+    match *tag {
+        Tag::Byte(value) => Some(value != 0),
+        _ => None,
+    }
+}
@@ -0,0 +1,568 @@
+use super::{
+    super::{
+        objs::{ChunkPos, PackedArrayError, Position, PositionError, WorldHeight, CHUNK_SIZE},
+        registry::{BlockRegistry, BlockState, Registry},
+    },
+    palette::{PaletteConfig, PalettedContainer},
+};
+
+/// Blocks that count as empty, for heightmaps and block counts.
+const AIR: &[&str] = &["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ChunkError {
+    NotInChunk {
+        position: Position,
+        chunk: ChunkPos,
+    },
+    /// A chunk is so far out that its blocks' coordinates overflow.
+    OutOfWorld(ChunkPos),
+    OutOfHeight(PositionError),
+    /// A block state belongs to another protocol version's registry.
+    ForeignBlockState(BlockState),
+    /// A block state id the chunk's registry doesn't know.
+    UnknownBlockState(u32),
+    UnknownBiome(u32),
+    Palette(PackedArrayError),
+    /// There's no chunk encoding for this protocol version.
+    UnsupportedProtocol(i32),
+    /// The protocol version can't send chunks of this height.
+    UnsupportedHeight(WorldHeight),
+    /// A chunk's block registry is that of another protocol version than
+    /// the one it's sent with.
+    ForeignRegistry {
+        registry: i32,
+        protocol_version: i32,
+    },
+}
+
+impl std::fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ChunkError::NotInChunk { position, chunk } => {
+                write!(f, "{:?} is not in chunk {}, {}", position, chunk.x, chunk.z)
+            }
+            ChunkError::OutOfWorld(chunk) => {
+                write!(f, "chunk {}, {} is outside of the world", chunk.x, chunk.z)
+            }
+            ChunkError::OutOfHeight(err) => err.fmt(f),
+            ChunkError::ForeignBlockState(state) => {
+                write!(f, "{} is from another registry", state)
+            }
+            ChunkError::UnknownBlockState(id) => write!(f, "unknown block state {}", id),
+            ChunkError::UnknownBiome(id) => write!(f, "unknown biome {}", id),
+            ChunkError::Palette(err) => err.fmt(f),
+            ChunkError::UnsupportedProtocol(version) => {
+                write!(f, "can't send chunks with protocol version {}", version)
+            }
+            ChunkError::UnsupportedHeight(height) => write!(
+                f,
+                "can't send chunks from y {} to {} with this protocol version",
+                height.min_y(),
+                height.max_y()
+            ),
+            ChunkError::ForeignRegistry {
+                registry,
+                protocol_version,
+            } => write!(
+                f,
+                "can't send a chunk of protocol version {} with protocol version {}",
+                registry, protocol_version
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChunkError {}
+
+impl From<PositionError> for ChunkError {
+    fn from(err: PositionError) -> Self {
+        ChunkError::OutOfHeight(err)
+    }
+}
+
+impl From<PackedArrayError> for ChunkError {
+    fn from(err: PackedArrayError) -> Self {
+        ChunkError::Palette(err)
+    }
+}
+
+/// A 16x16x16 cube of blocks, with their biomes.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ChunkSection {
+    blocks: PalettedContainer,
+    biomes: PalettedContainer,
+    /// How many blocks aren't air, which clients use to skip empty sections.
+    block_count: u16,
+}
+
+impl ChunkSection {
+    fn new(blocks: PaletteConfig, air: u32, biomes: PaletteConfig, biome: u32) -> Self {
+        Self {
+            blocks: PalettedContainer::new(blocks, air),
+            biomes: PalettedContainer::new(biomes, biome),
+            block_count: 0,
+        }
+    }
+
+    pub fn blocks(&self) -> &PalettedContainer {
+        &self.blocks
+    }
+
+    pub fn biomes(&self) -> &PalettedContainer {
+        &self.biomes
+    }
+
+    pub fn block_count(&self) -> u16 {
+        self.block_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.block_count == 0
+    }
+}
+
+/// Which blocks a heightmap counts.
+///
+/// `MOTION_BLOCKING` needs to know which blocks collide or hold fluids, which
+/// the reports don't say, so it's left out. Clients and vanilla servers do
+/// without it, and the latter compute it when loading.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum HeightmapKind {
+    /// Every block but air.
+    WorldSurface,
+}
+
+impl HeightmapKind {
+    pub const ALL: [HeightmapKind; 1] = [HeightmapKind::WorldSurface];
+
+    /// Returns the name heightmaps are stored and sent under.
+    pub fn name(self) -> &'static str {
+        match self {
+            HeightmapKind::WorldSurface => "WORLD_SURFACE",
+        }
+    }
+}
+
+/// The height of the highest counted block in every column of a chunk, as
+/// the amount of blocks from the bottom of the world up to and including it.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Heightmap {
+    kind: HeightmapKind,
+    heights: Box<[u16; 256]>,
+}
+
+impl Heightmap {
+    fn new(kind: HeightmapKind) -> Self {
+        Self {
+            kind,
+            heights: Box::new([0; 256]),
+        }
+    }
+
+    pub fn kind(&self) -> HeightmapKind {
+        self.kind
+    }
+
+    /// Returns the height of the column at `x`, `z` relative to the chunk.
+    pub fn get(&self, x: usize, z: usize) -> u16 {
+        self.heights[z * 16 + x]
+    }
+
+    /// Returns every height, ordered by `z` then `x`.
+    pub fn heights(&self) -> &[u16; 256] {
+        &self.heights
+    }
+}
+
+/// A chunk column: a stack of sections covering the height of the world.
+#[derive(Clone, Debug)]
+pub struct ChunkColumn {
+    pos: ChunkPos,
+    height: WorldHeight,
+    registry: &'static BlockRegistry,
+    sections: Vec<ChunkSection>,
+    heightmaps: Vec<Heightmap>,
+}
+
+impl ChunkColumn {
+    /// Creates a chunk filled with air, whose biome is the registry's
+    /// default.
+    pub fn new(
+        pos: ChunkPos,
+        height: WorldHeight,
+        registry: &'static BlockRegistry,
+        biomes: &'static Registry,
+    ) -> Self {
+        let air = registry
+            .block("air")
+            .map_or(0, |air| air.default_state().id());
+        let biome = biomes
+            .default()
+            .and_then(|biome| biomes.id(&biome))
+            .map_or(0, |id| id as u32);
+
+        let max_biome = biomes.iter().map(|(_, id)| id as u32).max().unwrap_or(0);
+        let biome_bits = (u32::BITS - max_biome.leading_zeros()).max(1) as u8;
+
+        let section = ChunkSection::new(
+            PaletteConfig::blocks(registry.bits_per_state()),
+            air,
+            PaletteConfig::biomes(biome_bits),
+            biome,
+        );
+
+        Self {
+            pos,
+            height,
+            registry,
+            sections: vec![section; (height.height() / CHUNK_SIZE) as usize],
+            heightmaps: HeightmapKind::ALL
+                .iter()
+                .map(|&kind| Heightmap::new(kind))
+                .collect(),
+        }
+    }
+
+    pub fn pos(&self) -> ChunkPos {
+        self.pos
+    }
+
+    pub fn height(&self) -> WorldHeight {
+        self.height
+    }
+
+    pub fn registry(&self) -> &'static BlockRegistry {
+        self.registry
+    }
+
+    /// Returns the sections from the bottom of the world up.
+    pub fn sections(&self) -> &[ChunkSection] {
+        &self.sections
+    }
+
+    pub fn heightmap(&self, kind: HeightmapKind) -> &Heightmap {
+        self.heightmaps
+            .iter()
+            .find(|heightmap| heightmap.kind == kind)
+            .unwrap()
+    }
+
+    pub fn heightmaps(&self) -> &[Heightmap] {
+        &self.heightmaps
+    }
+
+    /// Returns the position of the block at the given coordinates relative
+    /// to the chunk's north-west corner.
+    pub fn position(&self, x: i32, y: i32, z: i32) -> Result<Position, ChunkError> {
+        self.pos
+            .block(x, y, z)
+            .ok_or(ChunkError::OutOfWorld(self.pos))
+    }
+
+    /// Returns the section a position is in, and the index of its block in
+    /// the section.
+    fn locate(&self, position: Position) -> Result<(usize, usize), ChunkError> {
+        if !self.pos.contains(position) {
+            return Err(ChunkError::NotInChunk {
+                position,
+                chunk: self.pos,
+            });
+        }
+
+        position.check(self.height)?;
+        let section = position.section().index_in(self.height).unwrap();
+
+        Ok((section, position.section_index()))
+    }
+
+    fn is_air(&self, id: u32) -> bool {
+        self.registry
+            .state(id)
+            .is_none_or(|state| AIR.contains(&state.block().name()))
+    }
+
+    fn state(&self, id: u32) -> Result<BlockState, ChunkError> {
+        self.registry
+            .state(id)
+            .ok_or(ChunkError::UnknownBlockState(id))
+    }
+
+    pub fn block(&self, position: Position) -> Result<BlockState, ChunkError> {
+        let (section, index) = self.locate(position)?;
+        self.state(self.sections[section].blocks.get(index))
+    }
+
+    /// Sets a block and returns the one that was there before.
+    pub fn set_block(
+        &mut self,
+        position: Position,
+        state: BlockState,
+    ) -> Result<BlockState, ChunkError> {
+        let (section, index) = self.locate(position)?;
+        if self.registry.state(state.id()) != Some(state) {
+            return Err(ChunkError::ForeignBlockState(state));
+        }
+
+        let old = self.sections[section].blocks.set(index, state.id())?;
+        let (was_air, is_air) = (self.is_air(old), self.is_air(state.id()));
+
+        let section = &mut self.sections[section];
+        match (was_air, is_air) {
+            (true, false) => section.block_count += 1,
+            (false, true) => section.block_count -= 1,
+            _ => {}
+        }
+
+        if was_air != is_air {
+            self.update_heightmaps(position, is_air)?;
+        }
+
+        self.state(old)
+    }
+
+    fn update_heightmaps(&mut self, position: Position, removed: bool) -> Result<(), ChunkError> {
+        let x = (position.x & lsb!(4)) as usize;
+        let z = (position.z & lsb!(4)) as usize;
+        let height = (position.y - self.height.min_y() + 1) as u16;
+
+        for i in 0..self.heightmaps.len() {
+            let current = self.heightmaps[i].get(x, z);
+
+            let new = if !removed {
+                current.max(height)
+            } else if current == height {
+                self.column_top(x, z, height - 1)?
+            } else {
+                current
+            };
+
+            self.heightmaps[i].heights[z * 16 + x] = new;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the height of the highest block that isn't air in a column,
+    /// looking from `below` down.
+    fn column_top(&self, x: usize, z: usize, below: u16) -> Result<u16, ChunkError> {
+        for height in (1..=below).rev() {
+            let position = self.position(
+                x as i32,
+                self.height.min_y() + i32::from(height) - 1,
+                z as i32,
+            )?;
+            let (section, index) = self.locate(position)?;
+            if !self.is_air(self.sections[section].blocks.get(index)) {
+                return Ok(height);
+            }
+        }
+
+        Ok(0)
+    }
+
+    /// Returns the biome at a position, which is shared by its 4x4x4 cube.
+    pub fn biome(&self, position: Position) -> Result<u32, ChunkError> {
+        let (section, index) = self.locate(position)?;
+        Ok(self.sections[section].biomes.get(biome_index(index)))
+    }
+
+    pub fn set_biome(&mut self, position: Position, biome: u32) -> Result<u32, ChunkError> {
+        let (section, index) = self.locate(position)?;
+
+        let biomes = &mut self.sections[section].biomes;
+        biomes
+            .set(biome_index(index), biome)
+            .map_err(|err| match err {
+                PackedArrayError::ValueTooLarge { .. } => ChunkError::UnknownBiome(biome),
+                err => ChunkError::Palette(err),
+            })
+    }
+
+    /// Shrinks every palette to the values still in use.
+    pub fn compact(&mut self) -> Result<(), ChunkError> {
+        for section in &mut self.sections {
+            section.blocks.compact()?;
+            section.biomes.compact()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns roughly how many bytes the chunk takes in memory.
+    pub fn memory_usage(&self) -> usize {
+        let sections = self
+            .sections
+            .iter()
+            .map(|section| {
+                std::mem::size_of::<ChunkSection>()
+                    + section.blocks.heap_size()
+                    + section.biomes.heap_size()
+            })
+            .sum::<usize>();
+        let heightmaps = self.heightmaps.len()
+            * (std::mem::size_of::<Heightmap>() + std::mem::size_of::<[u16; 256]>());
+
+        std::mem::size_of::<Self>() + sections + heightmaps
+    }
+}
+
+/// Converts the index of a block within its section to the index of its
+/// biome, whose cubes are ordered by `y`, `z`, `x` as well.
+pub(super) fn biome_index(block_index: usize) -> usize {
+    let x = (block_index & lsb!(4)) >> 2;
+    let z = ((block_index >> 4) & lsb!(4)) >> 2;
+    let y = (block_index >> 8) >> 2;
+
+    (y << 4) | (z << 2) | x
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::registry::Registries;
+
+    fn chunk() -> ChunkColumn {
+        ChunkColumn::new(
+            ChunkPos { x: -1, z: 2 },
+            WorldHeight::OVERWORLD,
+            BlockRegistry::for_protocol(404).unwrap(),
+            Registries::for_protocol(404).unwrap().biomes(),
+        )
+    }
+
+    fn state(s: &str) -> BlockState {
+        BlockRegistry::for_protocol(404).unwrap().parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_blocks() {
+        let mut chunk = chunk();
+        let position = chunk.pos().block(3, -64, 15).unwrap();
+        assert_eq!(chunk.sections().len(), 24);
+        assert_eq!(chunk.block(position), Ok(state("air")));
+
+        assert_eq!(chunk.set_block(position, state("stone")), Ok(state("air")));
+        assert_eq!(chunk.block(position), Ok(state("stone")));
+        assert_eq!(chunk.sections()[0].block_count(), 1);
+        assert!(chunk.sections()[1].is_empty());
+
+        let stairs = state("oak_stairs[facing=east]");
+        chunk.set_block(position, stairs).unwrap();
+        assert_eq!(chunk.block(position), Ok(stairs));
+        assert_eq!(chunk.sections()[0].block_count(), 1);
+
+        chunk.set_block(position, state("air")).unwrap();
+        assert!(chunk.sections()[0].is_empty());
+        chunk.compact().unwrap();
+        assert_eq!(chunk.sections()[0].blocks().bits(), 0);
+
+        let outside = Position { x: 0, y: 0, z: 32 };
+        assert!(matches!(
+            chunk.block(outside),
+            Err(ChunkError::NotInChunk { .. })
+        ));
+        let too_high = chunk.pos().block(0, 320, 0).unwrap();
+        assert!(matches!(
+            chunk.set_block(too_high, stairs),
+            Err(ChunkError::OutOfHeight(_))
+        ));
+
+        let far = ChunkPos { x: i32::MAX, z: 0 };
+        let biomes = Registries::for_protocol(404).unwrap().biomes();
+        let chunk = ChunkColumn::new(far, WorldHeight::OVERWORLD, chunk.registry(), biomes);
+        assert_eq!(chunk.position(0, 0, 0), Err(ChunkError::OutOfWorld(far)));
+    }
+
+    #[test]
+    fn test_heightmaps() {
+        let mut chunk = chunk();
+        let pos = chunk.pos();
+        let column = |y| pos.block(5, y, 6).unwrap();
+
+        chunk.set_block(column(10), state("stone")).unwrap();
+        chunk.set_block(column(-20), state("dirt")).unwrap();
+
+        let surface = chunk.heightmap(HeightmapKind::WorldSurface);
+        assert_eq!(surface.get(5, 6), 75);
+        assert_eq!(surface.get(6, 5), 0);
+
+        chunk.set_block(column(10), state("air")).unwrap();
+        assert_eq!(chunk.heightmap(HeightmapKind::WorldSurface).get(5, 6), 45);
+
+        chunk.set_block(column(-20), state("air")).unwrap();
+        assert_eq!(chunk.heightmap(HeightmapKind::WorldSurface).get(5, 6), 0);
+    }
+
+    #[test]
+    fn test_biomes() {
+        let mut chunk = chunk();
+        let position = chunk.pos().block(4, 0, 8).unwrap();
+        assert_eq!(chunk.biome(position), Ok(0));
+
+        assert_eq!(chunk.set_biome(position, 1), Ok(0));
+        assert_eq!(chunk.biome(chunk.pos().block(7, 3, 11).unwrap()), Ok(1));
+        assert_eq!(chunk.biome(chunk.pos().block(8, 3, 11).unwrap()), Ok(0));
+        assert_eq!(
+            chunk.set_biome(position, 1000),
+            Err(ChunkError::UnknownBiome(1000))
+        );
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let mut chunk = chunk();
+        let empty = chunk.memory_usage();
+
+        for x in 0..16 {
+            for z in 0..16 {
+                let position = chunk.pos().block(x, 0, z).unwrap();
+                chunk.set_block(position, state("stone")).unwrap();
+            }
+        }
+        chunk
+            .set_block(chunk.pos().block(0, 1, 0).unwrap(), state("dirt"))
+            .unwrap();
+
+        // One section now stores 4 bits per block.
+        assert!(chunk.memory_usage() >= empty + 2048);
+
+        for x in 0..16 {
+            for z in 0..16 {
+                let position = chunk.pos().block(x, 0, z).unwrap();
+                chunk.set_block(position, state("air")).unwrap();
+            }
+        }
+        chunk
+            .set_block(chunk.pos().block(0, 1, 0).unwrap(), state("air"))
+            .unwrap();
+        chunk.compact().unwrap();
+        assert_eq!(chunk.memory_usage(), empty);
+    }
+
+    proptest! {
+        #[test]
+        fn test_model(writes in prop::collection::vec((0..16, -64..320, 0..16, 0..69u32), 0..100)) {
+            let registry = BlockRegistry::for_protocol(404).unwrap();
+            let mut chunk = chunk();
+            let mut model = std::collections::HashMap::new();
+
+            for (x, y, z, id) in writes {
+                let position = chunk.pos().block(x, y, z).unwrap();
+                let state = registry.state(id).unwrap();
+                chunk.set_block(position, state).unwrap();
+                model.insert(position, state);
+            }
+
+            for (&position, &state) in &model {
+                prop_assert_eq!(chunk.block(position).unwrap(), state);
+            }
+
+            let non_air = model.values().filter(|state| state.id() != 0).count();
+            let counted = chunk.sections().iter().map(|s| usize::from(s.block_count())).sum::<usize>();
+            prop_assert_eq!(counted, non_air);
+        }
+    }
+}
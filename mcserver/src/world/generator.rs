@@ -0,0 +1,563 @@
+//! World generators, which create the chunks of a world that haven't been
+//! saved yet.
+
+use super::{
+    super::{
+        objs::{ChunkPos, Identifier, Position, WorldHeight, CHUNK_SIZE},
+        registry::{BlockRegistry, BlockState, BlockStateError, Registry},
+    },
+    ChunkColumn, ChunkError,
+};
+
+/// Generates the chunks of a world. Chunks may be generated on any thread,
+/// in any order.
+pub trait Generator: Send + Sync {
+    fn generate(&self, pos: ChunkPos) -> Result<ChunkColumn, ChunkError>;
+
+    /// Returns where players spawn in a new world, standing on the block
+    /// below.
+    fn spawn(&self) -> Position;
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum GeneratorError {
+    /// A layer of a flat world preset isn't `<block>` or `<count>*<block>`.
+    InvalidLayer(String),
+    InvalidBlock(BlockStateError),
+    UnknownBiome(String),
+    /// The layers of a flat world are higher than the world.
+    TooManyLayers {
+        layers: i32,
+        height: WorldHeight,
+    },
+    /// A platform would be negative in size or reach past the edge of the
+    /// world.
+    InvalidRadius(i32),
+    Chunk(ChunkError),
+}
+
+impl std::fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GeneratorError::InvalidLayer(layer) => write!(f, "invalid layer {:?}", layer),
+            GeneratorError::InvalidBlock(err) => err.fmt(f),
+            GeneratorError::UnknownBiome(name) => write!(f, "unknown biome {}", name),
+            GeneratorError::TooManyLayers { layers, height } => write!(
+                f,
+                "{} layers don't fit in a world {} blocks high",
+                layers,
+                height.height()
+            ),
+            GeneratorError::InvalidRadius(radius) => {
+                write!(f, "invalid platform radius {}", radius)
+            }
+            GeneratorError::Chunk(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+impl From<BlockStateError> for GeneratorError {
+    fn from(err: BlockStateError) -> Self {
+        GeneratorError::InvalidBlock(err)
+    }
+}
+
+impl From<ChunkError> for GeneratorError {
+    fn from(err: ChunkError) -> Self {
+        GeneratorError::Chunk(err)
+    }
+}
+
+fn biome_id(biomes: &Registry, name: &str) -> Result<u32, GeneratorError> {
+    Identifier::parse(name)
+        .ok()
+        .and_then(|id| biomes.id(&id))
+        .map(|id| id as u32)
+        .ok_or_else(|| GeneratorError::UnknownBiome(name.to_owned()))
+}
+
+/// Creates an empty chunk of a single biome.
+fn empty_chunk(
+    pos: ChunkPos,
+    height: WorldHeight,
+    registry: &'static BlockRegistry,
+    biomes: &'static Registry,
+    biome: u32,
+) -> Result<ChunkColumn, ChunkError> {
+    let mut chunk = ChunkColumn::new(pos, height, registry, biomes);
+
+    // Biomes are stored per 4x4x4 cube.
+    for y in (height.min_y()..height.max_y()).step_by(4) {
+        for z in (0..CHUNK_SIZE).step_by(4) {
+            for x in (0..CHUNK_SIZE).step_by(4) {
+                chunk.set_biome(chunk.position(x, y, z)?, biome)?;
+            }
+        }
+    }
+
+    Ok(chunk)
+}
+
+/// Splits `s` at commas that aren't inside a block state's properties.
+fn split_layers(s: &str) -> impl Iterator<Item = &str> {
+    let mut depth = 0;
+    s.split(move |c| {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        c == ',' && depth == 0
+    })
+}
+
+/// A superflat world: layers of blocks from the bottom of the world up, all
+/// in one biome.
+#[derive(Clone, Debug)]
+pub struct FlatGenerator {
+    height: WorldHeight,
+    registry: &'static BlockRegistry,
+    biomes: &'static Registry,
+    layers: Vec<(BlockState, i32)>,
+    biome: u32,
+}
+
+impl FlatGenerator {
+    /// Vanilla's default, the "Classic Flat" preset without villages.
+    pub const DEFAULT_PRESET: &'static str =
+        "minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains";
+
+    /// Creates a generator from a preset in the format vanilla uses since
+    /// 1.13, like `minecraft:bedrock,2*minecraft:dirt;minecraft:plains`.
+    /// Layers are listed from the bottom up, and the biome defaults to
+    /// plains. Structures, which can follow the biome, are ignored.
+    pub fn new(
+        preset: &str,
+        height: WorldHeight,
+        registry: &'static BlockRegistry,
+        biomes: &'static Registry,
+    ) -> Result<Self, GeneratorError> {
+        let mut parts = preset.splitn(3, ';');
+        let layers = parts.next().unwrap_or_default().trim();
+        let biome = parts
+            .next()
+            .map(str::trim)
+            .filter(|biome| !biome.is_empty())
+            .unwrap_or("minecraft:plains");
+
+        let layers = if layers.is_empty() {
+            Vec::new()
+        } else {
+            split_layers(layers)
+                .map(|layer| {
+                    let layer = layer.trim();
+                    let invalid = || GeneratorError::InvalidLayer(layer.to_owned());
+
+                    // The count can't be part of properties, so look before
+                    // them.
+                    let name_start = layer.find('[').unwrap_or(layer.len());
+                    let (count, block) = match layer[..name_start].find('*') {
+                        Some(star) => {
+                            let count = layer[..star].trim().parse().map_err(|_| invalid())?;
+                            (count, &layer[star + 1..])
+                        }
+                        None => (1, layer),
+                    };
+                    if count < 1 {
+                        return Err(invalid());
+                    }
+
+                    Ok((registry.parse(block.trim())?, count))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let total = layers
+            .iter()
+            .try_fold(0i32, |total, &(_, count)| total.checked_add(count));
+        match total {
+            Some(total) if total <= height.height() => {}
+            _ => {
+                return Err(GeneratorError::TooManyLayers {
+                    layers: total.unwrap_or(i32::MAX),
+                    height,
+                })
+            }
+        }
+
+        Ok(Self {
+            height,
+            registry,
+            biomes,
+            layers,
+            biome: biome_id(biomes, biome)?,
+        })
+    }
+
+    /// Returns the layers from the bottom up, with how many blocks high each
+    /// is.
+    pub fn layers(&self) -> &[(BlockState, i32)] {
+        &self.layers
+    }
+
+    pub fn biome(&self) -> u32 {
+        self.biome
+    }
+}
+
+impl Generator for FlatGenerator {
+    fn generate(&self, pos: ChunkPos) -> Result<ChunkColumn, ChunkError> {
+        let mut chunk = empty_chunk(pos, self.height, self.registry, self.biomes, self.biome)?;
+
+        let mut y = self.height.min_y();
+        for &(state, count) in &self.layers {
+            for y in y..y + count {
+                for z in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        chunk.set_block(chunk.position(x, y, z)?, state)?;
+                    }
+                }
+            }
+            y += count;
+        }
+
+        Ok(chunk)
+    }
+
+    fn spawn(&self) -> Position {
+        let layers: i32 = self.layers.iter().map(|&(_, count)| count).sum();
+
+        Position {
+            x: 0,
+            y: self.height.min_y() + layers,
+            z: 0,
+        }
+    }
+}
+
+/// An empty world, except for a square platform to spawn on.
+#[derive(Clone, Debug)]
+pub struct VoidGenerator {
+    height: WorldHeight,
+    registry: &'static BlockRegistry,
+    biomes: &'static Registry,
+    biome: u32,
+    platform: BlockState,
+    center: Position,
+    radius: i32,
+}
+
+impl VoidGenerator {
+    /// Creates a void world with a 5x5 stone platform at y 64, or as close
+    /// to it as the world's height allows. The biome is `the_void` if
+    /// `biomes` has it, and the registry's default otherwise.
+    pub fn new(
+        height: WorldHeight,
+        registry: &'static BlockRegistry,
+        biomes: &'static Registry,
+    ) -> Result<Self, GeneratorError> {
+        let biome = biome_id(biomes, "minecraft:the_void")
+            .ok()
+            .or_else(|| {
+                let default = biomes.default()?;
+                biomes.id(&default).map(|id| id as u32)
+            })
+            .unwrap_or(0);
+        let platform = registry
+            .block("stone")
+            .ok_or_else(|| BlockStateError::UnknownBlock("minecraft:stone".to_owned()))?
+            .default_state();
+
+        Ok(Self {
+            height,
+            registry,
+            biomes,
+            biome,
+            platform,
+            center: Position {
+                x: 0,
+                y: 64.clamp(height.min_y(), height.max_y()),
+                z: 0,
+            },
+            radius: 2,
+        })
+    }
+
+    /// Changes the platform to a square of `state` reaching `radius` blocks
+    /// out from `center`, which has to stay within the world.
+    pub fn with_platform(
+        mut self,
+        state: BlockState,
+        center: Position,
+        radius: i32,
+    ) -> Result<Self, GeneratorError> {
+        center.check(self.height).map_err(ChunkError::from)?;
+        if self.registry.state(state.id()) != Some(state) {
+            return Err(ChunkError::ForeignBlockState(state).into());
+        }
+
+        // Once the radius is known to be small, `n ± radius` can't overflow.
+        let xz = Position::MIN_XZ..=Position::MAX_XZ;
+        let fits = |n: i32| xz.contains(&(n - radius)) && xz.contains(&(n + radius));
+        if !(0..=Position::MAX_XZ).contains(&radius) || !fits(center.x) || !fits(center.z) {
+            return Err(GeneratorError::InvalidRadius(radius));
+        }
+
+        self.platform = state;
+        self.center = center;
+        self.radius = radius;
+        Ok(self)
+    }
+}
+
+impl Generator for VoidGenerator {
+    fn generate(&self, pos: ChunkPos) -> Result<ChunkColumn, ChunkError> {
+        let mut chunk = empty_chunk(pos, self.height, self.registry, self.biomes, self.biome)?;
+
+        let start = chunk.position(0, self.center.y, 0)?;
+        let min_x = (self.center.x - self.radius).max(start.x);
+        let max_x = (self.center.x + self.radius).min(start.x + CHUNK_SIZE - 1);
+        let min_z = (self.center.z - self.radius).max(start.z);
+        let max_z = (self.center.z + self.radius).min(start.z + CHUNK_SIZE - 1);
+
+        for z in min_z..=max_z {
+            for x in min_x..=max_x {
+                let position = Position {
+                    x,
+                    y: self.center.y,
+                    z,
+                };
+                chunk.set_block(position, self.platform)?;
+            }
+        }
+
+        Ok(chunk)
+    }
+
+    fn spawn(&self) -> Position {
+        Position {
+            y: self.center.y + 1,
+            ..self.center
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{test_util::registries, HeightmapKind};
+
+    fn flat(preset: &str, height: WorldHeight) -> Result<FlatGenerator, GeneratorError> {
+        let (registry, biomes) = registries();
+        FlatGenerator::new(preset, height, registry, biomes)
+    }
+
+    fn name(state: BlockState) -> &'static str {
+        state.block().name()
+    }
+
+    #[test]
+    fn test_flat() {
+        let height = WorldHeight::OVERWORLD;
+        let generator = flat(FlatGenerator::DEFAULT_PRESET, height).unwrap();
+        let pos = ChunkPos { x: -3, z: 7 };
+        let chunk = generator.generate(pos).unwrap();
+
+        let min_y = height.min_y();
+        let expected = [
+            "minecraft:bedrock",
+            "minecraft:dirt",
+            "minecraft:dirt",
+            "minecraft:grass_block",
+            "minecraft:air",
+        ];
+        for (y, &expected) in (min_y..).zip(&expected) {
+            assert_eq!(
+                name(chunk.block(pos.block(15, y, 3).unwrap()).unwrap()),
+                expected
+            );
+        }
+
+        let plains = biome_id(registries().1, "plains").unwrap();
+        assert_eq!(chunk.biome(pos.block(0, 100, 0).unwrap()).unwrap(), plains);
+        assert_eq!(chunk.heightmap(HeightmapKind::WorldSurface).get(4, 9), 4);
+        assert_eq!(generator.spawn().y, min_y + 4);
+
+        // Generators are usable as trait objects.
+        let generator: Box<dyn Generator> = Box::new(generator);
+        assert_eq!(generator.spawn().x, 0);
+    }
+
+    #[test]
+    fn test_presets() {
+        let height = WorldHeight::LEGACY;
+
+        let generator = flat(
+            " minecraft:stone , 3*oak_stairs[half=top,facing=east] ,grass_block[snowy=true];;village",
+            height,
+        )
+        .unwrap();
+        let layers: Vec<_> = generator
+            .layers()
+            .iter()
+            .map(|&(state, count)| (state.to_string(), count))
+            .collect();
+        assert_eq!(
+            layers,
+            [
+                ("minecraft:stone".to_owned(), 1),
+                (
+                    "minecraft:oak_stairs[facing=east,half=top,shape=straight,waterlogged=false]"
+                        .to_owned(),
+                    3
+                ),
+                ("minecraft:grass_block[snowy=true]".to_owned(), 1),
+            ]
+        );
+        assert_eq!(generator.spawn().y, 5);
+
+        let empty = flat(";minecraft:forest", height).unwrap();
+        assert!(empty.layers().is_empty());
+        assert_eq!(empty.spawn().y, 0);
+        assert_eq!(
+            empty
+                .generate(ChunkPos { x: 0, z: 0 })
+                .unwrap()
+                .sections()
+                .iter()
+                .map(|section| section.block_count())
+                .sum::<u16>(),
+            0
+        );
+
+        assert_eq!(
+            flat("0*stone", height).unwrap_err(),
+            GeneratorError::InvalidLayer("0*stone".into())
+        );
+        assert_eq!(
+            flat("x*stone", height).unwrap_err(),
+            GeneratorError::InvalidLayer("x*stone".into())
+        );
+        assert_eq!(
+            flat("stone,,dirt", height).unwrap_err(),
+            GeneratorError::InvalidBlock(BlockStateError::UnknownBlock("".into()))
+        );
+        assert!(matches!(
+            flat("minecraft:nope", height),
+            Err(GeneratorError::InvalidBlock(BlockStateError::UnknownBlock(
+                _
+            )))
+        ));
+        assert_eq!(
+            flat("stone;minecraft:nowhere", height).unwrap_err(),
+            GeneratorError::UnknownBiome("minecraft:nowhere".into())
+        );
+        assert_eq!(
+            flat("200*stone,100*dirt", height).unwrap_err(),
+            GeneratorError::TooManyLayers {
+                layers: 300,
+                height
+            }
+        );
+        assert!(flat("256*stone", height).is_ok());
+    }
+
+    #[test]
+    fn test_void() {
+        let (registry, biomes) = registries();
+        let height = WorldHeight::LEGACY;
+        let generator = VoidGenerator::new(height, registry, biomes).unwrap();
+
+        let spawn = generator.spawn();
+        assert_eq!(spawn, Position { x: 0, y: 65, z: 0 });
+
+        // The platform is split over the four chunks around the origin.
+        let chunk = generator.generate(ChunkPos { x: -1, z: -1 }).unwrap();
+        assert_eq!(
+            name(
+                chunk
+                    .block(Position {
+                        x: -2,
+                        y: 64,
+                        z: -2
+                    })
+                    .unwrap()
+            ),
+            "minecraft:stone"
+        );
+        assert_eq!(
+            name(
+                chunk
+                    .block(Position {
+                        x: -3,
+                        y: 64,
+                        z: -2
+                    })
+                    .unwrap()
+            ),
+            "minecraft:air"
+        );
+        assert_eq!(chunk.sections()[4].block_count(), 4);
+        let the_void = biome_id(biomes, "minecraft:the_void").unwrap();
+        assert_eq!(
+            chunk
+                .biome(Position {
+                    x: -2,
+                    y: 64,
+                    z: -2
+                })
+                .unwrap(),
+            the_void
+        );
+
+        let chunk = generator.generate(ChunkPos { x: 0, z: 0 }).unwrap();
+        assert_eq!(chunk.sections()[4].block_count(), 9);
+
+        let far = generator.generate(ChunkPos { x: 5, z: 0 }).unwrap();
+        assert!(far.sections().iter().all(|section| section.is_empty()));
+
+        let cobblestone = registry.block("cobblestone").unwrap().default_state();
+        let center = Position {
+            x: 100,
+            y: 10,
+            z: 8,
+        };
+        let generator = generator.with_platform(cobblestone, center, 0).unwrap();
+        let chunk = generator.generate(ChunkPos { x: 6, z: 0 }).unwrap();
+        assert_eq!(name(chunk.block(center).unwrap()), "minecraft:cobblestone");
+        assert_eq!(chunk.sections()[0].block_count(), 1);
+        assert_eq!(generator.spawn().y, 11);
+
+        let too_high = Position { x: 0, y: 300, z: 0 };
+        assert!(matches!(
+            generator.clone().with_platform(cobblestone, too_high, 1),
+            Err(GeneratorError::Chunk(ChunkError::OutOfHeight(_)))
+        ));
+
+        let edge = Position {
+            x: Position::MAX_XZ - 1,
+            y: 0,
+            z: 0,
+        };
+        assert!(generator
+            .clone()
+            .with_platform(cobblestone, edge, 1)
+            .is_ok());
+        for &radius in &[-1, 2, i32::MAX] {
+            assert_eq!(
+                generator
+                    .clone()
+                    .with_platform(cobblestone, edge, radius)
+                    .unwrap_err(),
+                GeneratorError::InvalidRadius(radius)
+            );
+        }
+
+        // The platform stays inside worlds that end below y 64.
+        let low = WorldHeight::new(-64, 64).unwrap();
+        let generator = VoidGenerator::new(low, registry, biomes).unwrap();
+        assert_eq!(generator.spawn().y, 0);
+    }
+}
@@ -0,0 +1,159 @@
+use super::super::objs::{WorldHeight, CHUNK_SIZE};
+
+/// Bytes in the light array of a section, which packs two levels per byte.
+pub const LIGHT_ARRAY_SIZE: usize = 2048;
+
+/// The light levels of a section, one nibble per block in the `y`, `z`, `x`
+/// order of `Position::section_index`, lower nibble first.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct LightArray(Box<[u8; LIGHT_ARRAY_SIZE]>);
+
+impl LightArray {
+    /// Creates an array where every block has the same level.
+    pub fn uniform(level: u8) -> Self {
+        let level = level & lsb!(4);
+        LightArray(Box::new([level | (level << 4); LIGHT_ARRAY_SIZE]))
+    }
+
+    pub fn from_bytes(bytes: [u8; LIGHT_ARRAY_SIZE]) -> Self {
+        LightArray(Box::new(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; LIGHT_ARRAY_SIZE] {
+        &self.0
+    }
+
+    pub fn get(&self, index: usize) -> u8 {
+        (self.0[index / 2] >> (4 * (index % 2))) & lsb!(4)
+    }
+
+    pub fn set(&mut self, index: usize, level: u8) {
+        let shift = 4 * (index % 2);
+        let byte = &mut self.0[index / 2];
+        *byte = (*byte & !(lsb!(4) << shift)) | ((level & lsb!(4)) << shift);
+    }
+
+    pub fn is_dark(&self) -> bool {
+        self.0.iter().all(|&byte| byte == 0)
+    }
+}
+
+impl Default for LightArray {
+    fn default() -> Self {
+        Self::uniform(0)
+    }
+}
+
+/// The sky and block light of a chunk column, which also covers the
+/// sections right below and above the world, as light spills into them.
+///
+/// Sections without an array have unknown light, which clients keep as it
+/// was.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ChunkLight {
+    height: WorldHeight,
+    has_sky: bool,
+    sky: Vec<Option<LightArray>>,
+    block: Vec<Option<LightArray>>,
+}
+
+impl ChunkLight {
+    /// Creates light for a column where nothing is known yet. Dimensions like
+    /// the nether have no sky light at all.
+    pub fn new(height: WorldHeight, has_sky: bool) -> Self {
+        let sections = (height.height() / CHUNK_SIZE) as usize + 2;
+
+        Self {
+            height,
+            has_sky,
+            sky: vec![None; sections],
+            block: vec![None; sections],
+        }
+    }
+
+    /// Creates light for a column fully lit by the sky, which is enough for
+    /// clients to see terrain before there's a lighting engine.
+    pub fn full_sky(height: WorldHeight) -> Self {
+        let mut light = Self::new(height, true);
+        for i in 0..light.len() {
+            light.sky[i] = Some(LightArray::uniform(15));
+            light.block[i] = Some(LightArray::default());
+        }
+        light
+    }
+
+    pub fn height(&self) -> WorldHeight {
+        self.height
+    }
+
+    pub fn has_sky(&self) -> bool {
+        self.has_sky
+    }
+
+    /// Returns how many sections there are, including the two outside the
+    /// world.
+    pub fn len(&self) -> usize {
+        self.sky.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sky.is_empty()
+    }
+
+    /// Returns the sky light of a section, where index 0 is the one below the
+    /// world.
+    pub fn sky(&self, index: usize) -> Option<&LightArray> {
+        self.sky.get(index)?.as_ref()
+    }
+
+    pub fn block(&self, index: usize) -> Option<&LightArray> {
+        self.block.get(index)?.as_ref()
+    }
+
+    pub fn set_sky(&mut self, index: usize, light: Option<LightArray>) {
+        if self.has_sky {
+            self.sky[index] = light;
+        }
+    }
+
+    pub fn set_block(&mut self, index: usize, light: Option<LightArray>) {
+        self.block[index] = light;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_light() {
+        let mut light = ChunkLight::new(WorldHeight::LEGACY, false);
+        assert_eq!(light.len(), 18);
+        light.set_sky(0, Some(LightArray::uniform(15)));
+        assert_eq!(light.sky(0), None);
+
+        let light = ChunkLight::full_sky(WorldHeight::OVERWORLD);
+        assert_eq!(light.len(), 26);
+        assert_eq!(light.sky(25).unwrap().get(4095), 15);
+        assert!(light.block(0).unwrap().is_dark());
+    }
+
+    proptest! {
+        #[test]
+        fn test_nibbles(writes in prop::collection::vec((0..4096usize, 0..16u8), 0..100)) {
+            let mut array = LightArray::default();
+            let mut model = vec![0; 4096];
+
+            for (index, level) in writes {
+                array.set(index, level);
+                model[index] = level;
+            }
+
+            for (index, &level) in model.iter().enumerate() {
+                prop_assert_eq!(array.get(index), level);
+            }
+        }
+    }
+}
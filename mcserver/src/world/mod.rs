@@ -0,0 +1,16 @@
+//! The world as the server keeps it in memory.
+
+pub mod anvil;
+pub mod chunk;
+pub mod generator;
+pub mod light;
+pub mod packets;
+pub mod palette;
+#[cfg(test)]
+mod test_util;
+
+pub use chunk::{ChunkColumn, ChunkError, ChunkSection, Heightmap, HeightmapKind};
+pub use generator::{FlatGenerator, Generator, GeneratorError, VoidGenerator};
+pub use light::{ChunkLight, LightArray, LIGHT_ARRAY_SIZE};
+pub use packets::{BlockEntity, ChunkData, ChunkFormat, UpdateLight};
+pub use palette::{Palette, PaletteConfig, PalettedContainer};
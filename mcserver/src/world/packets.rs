@@ -0,0 +1,783 @@
+use std::io::Write;
+
+use serde::ser::{self, Serialize, Serializer};
+
+use super::{
+    super::{
+        coder::{error::Result, ser::Serializer as CoderSerializer},
+        nbt::{Compound, NetworkTag, Tag},
+        objs::{BitSet, ChunkPos, Identifier, PackedArray, Packing, Position, VarInt, WorldHeight},
+    },
+    chunk::{biome_index, ChunkColumn, ChunkError},
+    light::{ChunkLight, LightArray, LIGHT_ARRAY_SIZE},
+    palette::{Palette, PalettedContainer},
+};
+
+/// How chunks are laid out on the wire, which changed with most major
+/// versions.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+pub enum ChunkFormat {
+    /// Light is sent with the sections, and biomes are 2D.
+    V1_13,
+    /// Light moves to its own packet, and heightmaps are added.
+    V1_14,
+    /// Biomes are 3D and move out of the section data.
+    V1_15,
+    /// Data is padded, and old data may be ignored.
+    V1_16,
+    /// Biomes are prefixed by their count.
+    V1_16_2,
+    /// Bit masks become bit sets, so the world can be of any height.
+    V1_17,
+    /// Every section is sent, with biome palettes, along with the light.
+    V1_18,
+}
+
+impl ChunkFormat {
+    /// Returns the format for a protocol version, or `None` if chunks can't
+    /// be sent to it.
+    pub fn for_protocol(protocol_version: i32) -> Option<Self> {
+        match protocol_version {
+            393..=404 => Some(ChunkFormat::V1_13),
+            477..=498 => Some(ChunkFormat::V1_14),
+            573..=578 => Some(ChunkFormat::V1_15),
+            735..=736 => Some(ChunkFormat::V1_16),
+            751..=754 => Some(ChunkFormat::V1_16_2),
+            755..=756 => Some(ChunkFormat::V1_17),
+            757..=758 => Some(ChunkFormat::V1_18),
+            _ => None,
+        }
+    }
+
+    fn packing(self) -> Packing {
+        if self >= ChunkFormat::V1_16 {
+            Packing::Padded
+        } else {
+            Packing::Spanning
+        }
+    }
+
+    /// Returns whether sections may have a single value without any data.
+    fn has_single_values(self) -> bool {
+        self >= ChunkFormat::V1_18
+    }
+
+    /// Returns whether sections are sent as bit masks, which limits the world
+    /// to its legacy height.
+    fn has_int_masks(self) -> bool {
+        self < ChunkFormat::V1_17
+    }
+}
+
+fn format_for(protocol_version: i32) -> std::result::Result<ChunkFormat, ChunkError> {
+    ChunkFormat::for_protocol(protocol_version)
+        .ok_or(ChunkError::UnsupportedProtocol(protocol_version))
+}
+
+/// A block entity, like a chest or a sign, as clients need it to render the
+/// block.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlockEntity {
+    pub position: Position,
+    /// The name of the type, which goes in the data before 1.18.
+    pub id: Identifier,
+    /// The id of the type in the `minecraft:block_entity_type` registry,
+    /// which replaces the name since 1.18.
+    pub kind: i32,
+    pub data: Compound,
+}
+
+impl BlockEntity {
+    /// Returns the data with the position and name added, as older versions
+    /// expect it.
+    fn legacy_data(&self) -> Compound {
+        let mut data = self.data.clone();
+        data.insert("x".into(), Tag::Int(self.position.x));
+        data.insert("y".into(), Tag::Int(self.position.y));
+        data.insert("z".into(), Tag::Int(self.position.z));
+        data.insert("id".into(), Tag::String(self.id.to_string()));
+        data
+    }
+}
+
+/// The body of a Chunk Data packet, which sends a whole chunk column.
+///
+/// Block states and biomes are sent with the ids of the registries the chunk
+/// uses, which must be those of the client's protocol version. `new` checks
+/// this for the block registry.
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkData<'a> {
+    chunk: &'a ChunkColumn,
+    light: &'a ChunkLight,
+    block_entities: &'a [BlockEntity],
+    format: ChunkFormat,
+}
+
+impl<'a> ChunkData<'a> {
+    pub fn new(
+        chunk: &'a ChunkColumn,
+        light: &'a ChunkLight,
+        block_entities: &'a [BlockEntity],
+        protocol_version: i32,
+    ) -> std::result::Result<Self, ChunkError> {
+        let format = format_for(protocol_version)?;
+
+        let registry = chunk.registry().protocol_version();
+        if registry != protocol_version {
+            return Err(ChunkError::ForeignRegistry {
+                registry,
+                protocol_version,
+            });
+        }
+
+        if format.has_int_masks() && chunk.height() != WorldHeight::LEGACY {
+            return Err(ChunkError::UnsupportedHeight(chunk.height()));
+        }
+
+        if let Some(block_entity) = block_entities
+            .iter()
+            .find(|block_entity| !chunk.pos().contains(block_entity.position))
+        {
+            return Err(ChunkError::NotInChunk {
+                position: block_entity.position,
+                chunk: chunk.pos(),
+            });
+        }
+
+        Ok(Self {
+            chunk,
+            light,
+            block_entities,
+            format,
+        })
+    }
+
+    pub fn format(&self) -> ChunkFormat {
+        self.format
+    }
+
+    /// Returns the indices of the sections that are sent, which before 1.18
+    /// are only those with blocks in them.
+    fn sent_sections(&self) -> impl Iterator<Item = usize> + '_ {
+        let all = self.format.has_single_values();
+
+        self.chunk
+            .sections()
+            .iter()
+            .enumerate()
+            .filter(move |(_, section)| all || !section.is_empty())
+            .map(|(index, _)| index)
+    }
+
+    fn write<W: Write>(&self, ser: &mut CoderSerializer<W>) -> Result<()> {
+        let format = self.format;
+        let pos = self.chunk.pos();
+
+        pos.x.serialize(&mut *ser)?;
+        pos.z.serialize(&mut *ser)?;
+
+        if format < ChunkFormat::V1_17 {
+            // Only whole chunks are sent.
+            true.serialize(&mut *ser)?;
+        }
+        if format == ChunkFormat::V1_16 {
+            // Ignore old data.
+            true.serialize(&mut *ser)?;
+        }
+
+        if format < ChunkFormat::V1_17 {
+            let mask = self
+                .sent_sections()
+                .fold(0, |mask, index| mask | 1 << index);
+            ser.write_varint(mask)?;
+        } else if format < ChunkFormat::V1_18 {
+            self.sent_sections()
+                .collect::<BitSet>()
+                .serialize(&mut *ser)?;
+        }
+
+        if format >= ChunkFormat::V1_14 {
+            self.heightmaps().serialize(&mut *ser)?;
+        }
+
+        if format >= ChunkFormat::V1_15 && format < ChunkFormat::V1_18 {
+            let biomes = self
+                .chunk
+                .sections()
+                .iter()
+                .flat_map(|section| section.biomes().iter());
+
+            if format >= ChunkFormat::V1_16_2 {
+                ser.write_varint(self.chunk.sections().len() as i32 * 64)?;
+                for biome in biomes {
+                    ser.write_varint(biome as i32)?;
+                }
+            } else {
+                for biome in biomes {
+                    (biome as i32).serialize(&mut *ser)?;
+                }
+            }
+        }
+
+        let mut data = Vec::new();
+        self.write_sections(&mut CoderSerializer::new(&mut data))?;
+        ser.write_varint(data.len() as i32)?;
+        ser.write_bytes(&data)?;
+
+        ser.write_varint(self.block_entities.len() as i32)?;
+        for block_entity in self.block_entities {
+            if format >= ChunkFormat::V1_18 {
+                let position = block_entity.position;
+                let xz = ((position.x & lsb!(4)) << 4 | (position.z & lsb!(4))) as u8;
+
+                xz.serialize(&mut *ser)?;
+                (position.y as i16).serialize(&mut *ser)?;
+                ser.write_varint(block_entity.kind)?;
+                NetworkTag(Some(Tag::Compound(block_entity.data.clone()))).serialize(&mut *ser)?;
+            } else {
+                NetworkTag(Some(Tag::Compound(block_entity.legacy_data()))).serialize(&mut *ser)?;
+            }
+        }
+
+        if format >= ChunkFormat::V1_18 {
+            write_light(ser, self.light, format)?;
+        }
+
+        Ok(())
+    }
+
+    fn heightmaps(&self) -> NetworkTag {
+        let height = self.chunk.height().height() as u32;
+        let bits = (u32::BITS - height.leading_zeros()) as u8;
+
+        let heightmaps = self
+            .chunk
+            .heightmaps()
+            .iter()
+            .map(|heightmap| {
+                let heights = heightmap.heights().iter().map(|&height| height.into());
+                let longs = PackedArray::from_values(256, bits, self.format.packing(), heights)
+                    .expect("heights are at most the height of the world")
+                    .as_longs()
+                    .iter()
+                    .map(|&long| long as i64)
+                    .collect();
+
+                (heightmap.kind().name().to_owned(), Tag::LongArray(longs))
+            })
+            .collect();
+
+        NetworkTag(Some(Tag::Compound(heightmaps)))
+    }
+
+    fn write_sections<W: Write>(&self, ser: &mut CoderSerializer<W>) -> Result<()> {
+        let format = self.format;
+        let has_sky = self.light.has_sky();
+
+        for index in self.sent_sections() {
+            let section = &self.chunk.sections()[index];
+
+            if format >= ChunkFormat::V1_14 {
+                (section.block_count() as i16).serialize(&mut *ser)?;
+            }
+
+            write_container(ser, section.blocks(), format)?;
+
+            if format >= ChunkFormat::V1_18 {
+                write_container(ser, section.biomes(), format)?;
+            }
+
+            if format == ChunkFormat::V1_13 {
+                // The light of the section itself, past the one below the
+                // world.
+                let dark = LightArray::default();
+                ser.write_bytes(self.light.block(index + 1).unwrap_or(&dark).as_bytes())?;
+                if has_sky {
+                    ser.write_bytes(self.light.sky(index + 1).unwrap_or(&dark).as_bytes())?;
+                }
+            }
+        }
+
+        if format < ChunkFormat::V1_15 {
+            // A biome for every column, taken at sea level since they were
+            // still 2D.
+            let sea_level = Position { x: 0, y: 64, z: 0 }.section_index();
+            let biomes = self.chunk.sections()[4].biomes();
+
+            for z in 0..16 {
+                for x in 0..16 {
+                    let index = sea_level + (z << 4) + x;
+                    (biomes.get(biome_index(index)) as i32).serialize(&mut *ser)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a paletted container, turning a single value into a palette where
+/// the format doesn't have them, and repacking the data for it.
+fn write_container<W: Write>(
+    ser: &mut CoderSerializer<W>,
+    container: &PalettedContainer,
+    format: ChunkFormat,
+) -> Result<()> {
+    let config = container.config();
+    let packing = format.packing();
+
+    let (bits, palette, data) = match (container.palette(), container.data()) {
+        (&Palette::Single(value), _) if format.has_single_values() => (0, Some(vec![value]), None),
+        (&Palette::Single(value), _) => (
+            config.min_bits,
+            Some(vec![value]),
+            PackedArray::new(config.entries, config.min_bits, packing).ok(),
+        ),
+        (Palette::Indirect(values), Some(data)) => (
+            data.bits(),
+            Some(values.clone()),
+            Some(repack(data, packing)),
+        ),
+        (_, data) => (
+            config.direct_bits,
+            None,
+            data.map(|data| repack(data, packing)),
+        ),
+    };
+
+    bits.serialize(&mut *ser)?;
+
+    match palette {
+        // A single value isn't prefixed by a length.
+        Some(values) if bits == 0 => ser.write_varint(values[0] as i32)?,
+        Some(values) => {
+            ser.write_varint(values.len() as i32)?;
+            for value in values {
+                ser.write_varint(value as i32)?;
+            }
+        }
+        None => {}
+    }
+
+    match data {
+        Some(data) => data.serialize(&mut *ser),
+        None => ser.write_varint(0),
+    }
+}
+
+fn repack(data: &PackedArray, packing: Packing) -> PackedArray {
+    if data.packing() == packing {
+        return data.clone();
+    }
+
+    data.repack(data.bits(), packing)
+        .expect("the entries already fit in that many bits")
+}
+
+impl Serialize for ChunkData<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut buf = Vec::new();
+        self.write(&mut CoderSerializer::new(&mut buf))
+            .map_err(ser::Error::custom)?;
+
+        serializer.serialize_bytes(&buf)
+    }
+}
+
+/// The body of an Update Light packet, which since 1.14 sends the light of a
+/// chunk column apart from its blocks.
+#[derive(Clone, Copy, Debug)]
+pub struct UpdateLight<'a> {
+    pos: ChunkPos,
+    light: &'a ChunkLight,
+    format: ChunkFormat,
+}
+
+impl<'a> UpdateLight<'a> {
+    pub fn new(
+        pos: ChunkPos,
+        light: &'a ChunkLight,
+        protocol_version: i32,
+    ) -> std::result::Result<Self, ChunkError> {
+        let format = format_for(protocol_version)?;
+
+        if format < ChunkFormat::V1_14 {
+            return Err(ChunkError::UnsupportedProtocol(protocol_version));
+        }
+        if format.has_int_masks() && light.height() != WorldHeight::LEGACY {
+            return Err(ChunkError::UnsupportedHeight(light.height()));
+        }
+
+        Ok(Self { pos, light, format })
+    }
+
+    fn write<W: Write>(&self, ser: &mut CoderSerializer<W>) -> Result<()> {
+        ser.write_varint(self.pos.x)?;
+        ser.write_varint(self.pos.z)?;
+
+        write_light(ser, self.light, self.format)
+    }
+}
+
+impl Serialize for UpdateLight<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut buf = Vec::new();
+        self.write(&mut CoderSerializer::new(&mut buf))
+            .map_err(ser::Error::custom)?;
+
+        serializer.serialize_bytes(&buf)
+    }
+}
+
+/// Which sections have their light sent, and which are known to be dark so
+/// clients can clear them without an array.
+#[derive(Default)]
+struct LightMasks<'a> {
+    sent: BitSet,
+    dark: BitSet,
+    arrays: Vec<&'a LightArray>,
+}
+
+impl<'a> LightMasks<'a> {
+    fn new(arrays: impl Iterator<Item = Option<&'a LightArray>>) -> Self {
+        let mut masks = Self::default();
+
+        for (index, array) in arrays.enumerate() {
+            match array {
+                Some(array) if array.is_dark() => masks.dark.set(index, true),
+                Some(array) => {
+                    masks.sent.set(index, true);
+                    masks.arrays.push(array);
+                }
+                None => {}
+            }
+        }
+
+        masks
+    }
+}
+
+/// Writes the light of a column after the position, as Update Light and,
+/// since 1.18, Chunk Data have it.
+fn write_light<W: Write>(
+    ser: &mut CoderSerializer<W>,
+    light: &ChunkLight,
+    format: ChunkFormat,
+) -> Result<()> {
+    let sky = LightMasks::new((0..light.len()).map(|index| light.sky(index)));
+    let block = LightMasks::new((0..light.len()).map(|index| light.block(index)));
+
+    if format >= ChunkFormat::V1_16 {
+        // Trust edges, as the light is complete.
+        true.serialize(&mut *ser)?;
+    }
+
+    for mask in &[&sky.sent, &block.sent, &sky.dark, &block.dark] {
+        if format.has_int_masks() {
+            let longs = mask.to_longs();
+            ser.write_varint(longs.first().map_or(0, |&long| long as i32))?;
+        } else {
+            mask.serialize(&mut *ser)?;
+        }
+    }
+
+    for masks in &[sky, block] {
+        if !format.has_int_masks() {
+            ser.write_varint(masks.arrays.len() as i32)?;
+        }
+
+        for array in &masks.arrays {
+            VarInt(LIGHT_ARRAY_SIZE as i32).serialize(&mut *ser)?;
+            ser.write_bytes(array.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{
+        coder::de::Deserializer,
+        registry::{BlockRegistry, Registries},
+    };
+
+    fn chunk(height: WorldHeight, protocol_version: i32) -> ChunkColumn {
+        ChunkColumn::new(
+            ChunkPos { x: 3, z: -2 },
+            height,
+            BlockRegistry::for_test(protocol_version),
+            Registries::for_protocol(404).unwrap().biomes(),
+        )
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut buf = Vec::new();
+        value
+            .serialize(&mut CoderSerializer::new(&mut buf))
+            .unwrap();
+        buf
+    }
+
+    fn byte<R: std::io::Read>(de: &mut Deserializer<R>) -> u8 {
+        let mut buf = [0];
+        de.read_bytes(&mut buf).unwrap();
+        buf[0]
+    }
+
+    /// Reads sections back the way clients do, returning the block states
+    /// of each one.
+    fn read_sections(
+        data: &[u8],
+        count: usize,
+        format: ChunkFormat,
+        has_sky: bool,
+    ) -> Vec<Vec<u32>> {
+        let mut de = Deserializer::new(Cursor::new(data));
+
+        let mut sections = Vec::new();
+        for _ in 0..count {
+            if format >= ChunkFormat::V1_14 {
+                byte(&mut de);
+                byte(&mut de);
+            }
+
+            let bits = byte(&mut de);
+            let palette = if bits == 0 {
+                vec![de.read_varint().unwrap() as u32]
+            } else if bits <= 8 {
+                let len = de.read_varint().unwrap() as usize;
+                (0..len).map(|_| de.read_varint().unwrap() as u32).collect()
+            } else {
+                vec![]
+            };
+
+            let len = de.read_varint().unwrap() as usize;
+            let mut longs = vec![0; len];
+            for long in &mut longs {
+                let mut buf = [0; 8];
+                de.read_bytes(&mut buf).unwrap();
+                *long = i64::from_be_bytes(buf);
+            }
+
+            let states = match bits {
+                0 => vec![palette[0]; 4096],
+                _ => PackedArray::from_longs(4096, bits, format.packing(), longs)
+                    .unwrap()
+                    .iter()
+                    .map(|entry| match palette.get(entry as usize) {
+                        Some(&state) if bits <= 8 => state,
+                        _ => entry as u32,
+                    })
+                    .collect(),
+            };
+            sections.push(states);
+
+            if format >= ChunkFormat::V1_18 {
+                // Skip the biomes, which are a single value here.
+                assert_eq!(byte(&mut de), 0);
+                de.read_varint().unwrap();
+                assert_eq!(de.read_varint().unwrap(), 0);
+            }
+
+            if format == ChunkFormat::V1_13 {
+                let mut light = vec![0; LIGHT_ARRAY_SIZE * if has_sky { 2 } else { 1 }];
+                de.read_bytes(&mut light).unwrap();
+            }
+        }
+
+        sections
+    }
+
+    #[test]
+    fn test_formats() {
+        for &(version, format) in &[
+            (404, ChunkFormat::V1_13),
+            (498, ChunkFormat::V1_14),
+            (578, ChunkFormat::V1_15),
+            (736, ChunkFormat::V1_16),
+            (754, ChunkFormat::V1_16_2),
+            (756, ChunkFormat::V1_17),
+            (758, ChunkFormat::V1_18),
+        ] {
+            assert_eq!(ChunkFormat::for_protocol(version), Some(format));
+        }
+
+        assert_eq!(ChunkFormat::for_protocol(340), None);
+
+        let modern = chunk(WorldHeight::OVERWORLD, 756);
+        let chunk = chunk(WorldHeight::OVERWORLD, 404);
+        let light = ChunkLight::full_sky(chunk.height());
+        assert!(matches!(
+            ChunkData::new(&chunk, &light, &[], 404),
+            Err(ChunkError::UnsupportedHeight(_))
+        ));
+
+        // The ids of the chunk's registry only mean something to its own
+        // protocol version.
+        assert!(matches!(
+            ChunkData::new(&chunk, &light, &[], 756),
+            Err(ChunkError::ForeignRegistry {
+                registry: 404,
+                protocol_version: 756
+            })
+        ));
+        assert!(ChunkData::new(&modern, &light, &[], 756).is_ok());
+        assert!(matches!(
+            UpdateLight::new(chunk.pos(), &light, 404),
+            Err(ChunkError::UnsupportedProtocol(404))
+        ));
+    }
+
+    #[test]
+    fn test_legacy_chunk() {
+        let registry = BlockRegistry::for_protocol(404).unwrap();
+        let mut chunk = chunk(WorldHeight::LEGACY, 404);
+        chunk
+            .set_block(
+                chunk.pos().block(1, 17, 2).unwrap(),
+                registry.parse("stone").unwrap(),
+            )
+            .unwrap();
+
+        let light = ChunkLight::full_sky(chunk.height());
+        let data = encode(&ChunkData::new(&chunk, &light, &[], 404).unwrap());
+
+        // x, z, whole chunk and a mask with only the second section.
+        assert_eq!(&data[..9], &[0, 0, 0, 3, 0xff, 0xff, 0xff, 0xfe, 1]);
+        assert_eq!(data[9], 0b10);
+
+        let mut de = Deserializer::new(Cursor::new(&data[10..]));
+        let len = de.read_varint().unwrap() as usize;
+        let start = 10 + de.offset() as usize;
+        let sections = &data[start..start + len];
+
+        let states = read_sections(sections, 1, ChunkFormat::V1_13, true);
+        assert_eq!(states[0][(1 << 8) | (2 << 4) | 1], 1);
+        assert_eq!(states[0].iter().filter(|&&state| state != 0).count(), 1);
+
+        // The biomes follow the sections.
+        let section_size = 1 + 3 + 2 + 256 * 8 + 2 * LIGHT_ARRAY_SIZE;
+        assert_eq!(len, section_size + 256 * 4);
+
+        // No block entities.
+        assert_eq!(&data[start + len..], &[0]);
+    }
+
+    #[test]
+    fn test_light() {
+        let mut light = ChunkLight::new(WorldHeight::LEGACY, true);
+        let mut lit = LightArray::default();
+        lit.set(0, 15);
+        light.set_sky(1, Some(lit.clone()));
+        light.set_block(2, Some(LightArray::default()));
+
+        let data = encode(&UpdateLight::new(ChunkPos { x: 1, z: 2 }, &light, 578).unwrap());
+        let mut expected = vec![1, 2, 0b10, 0, 0, 0b100, 0x80, 0x10];
+        expected.extend_from_slice(lit.as_bytes());
+        assert_eq!(data, expected);
+
+        // Since 1.17 masks are bit sets, and arrays are counted.
+        let data = encode(&UpdateLight::new(ChunkPos { x: 1, z: 2 }, &light, 756).unwrap());
+        let mut expected = vec![1, 2, 1];
+        for &mask in &[0b10, 0, 0, 0b100] {
+            match mask {
+                0 => expected.push(0),
+                mask => expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, mask]),
+            }
+        }
+        expected.extend_from_slice(&[1, 0x80, 0x10]);
+        expected.extend_from_slice(lit.as_bytes());
+        expected.push(0);
+        assert_eq!(data, expected);
+
+        assert!(matches!(
+            UpdateLight::new(
+                ChunkPos { x: 1, z: 2 },
+                &ChunkLight::full_sky(WorldHeight::OVERWORLD),
+                754
+            ),
+            Err(ChunkError::UnsupportedHeight(_))
+        ));
+    }
+
+    #[test]
+    fn test_block_entities() {
+        let chunk = chunk(WorldHeight::OVERWORLD, 758);
+        let light = ChunkLight::new(chunk.height(), false);
+        let block_entity = BlockEntity {
+            position: chunk.pos().block(15, -3, 1).unwrap(),
+            id: Identifier::parse("chest").unwrap(),
+            kind: 1,
+            data: Compound::new(),
+        };
+
+        let data = encode(
+            &ChunkData::new(&chunk, &light, std::slice::from_ref(&block_entity), 758).unwrap(),
+        );
+        // One block entity with empty data, then trust edges, four empty
+        // masks and no arrays.
+        assert!(data.ends_with(&[
+            1, 0xf1, 0xff, 0xfd, 1, 10, 0, 0, 0, //
+            1, 0, 0, 0, 0, 0, 0,
+        ]));
+
+        let legacy = block_entity.legacy_data();
+        assert_eq!(legacy.get("y"), Some(&Tag::Int(-3)));
+        assert_eq!(
+            legacy.get("id"),
+            Some(&Tag::String("minecraft:chest".into()))
+        );
+
+        let outside = BlockEntity {
+            position: Position { x: 0, y: 0, z: 0 },
+            ..block_entity
+        };
+        assert!(matches!(
+            ChunkData::new(&chunk, &light, &[outside], 758),
+            Err(ChunkError::NotInChunk { .. })
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn test_sections(
+            blocks in prop::collection::vec((0..16, 0..256, 0..16, 0..69u32), 0..300),
+            version in prop::sample::select(vec![404, 477, 573, 735, 751, 755, 757]),
+        ) {
+            let registry = BlockRegistry::for_test(version);
+            let mut chunk = chunk(WorldHeight::LEGACY, version);
+            for (x, y, z, state) in blocks {
+                let state = registry.state(state).unwrap();
+                chunk.set_block(chunk.pos().block(x, y, z).unwrap(), state).unwrap();
+            }
+
+            let format = ChunkFormat::for_protocol(version).unwrap();
+            let light = ChunkLight::full_sky(chunk.height());
+            let packet = ChunkData::new(&chunk, &light, &[], version).unwrap();
+            let sent = packet.sent_sections().collect::<Vec<_>>();
+
+            // Find the section data from the end, past the block entities
+            // and, since 1.18, the light.
+            let mut data = Vec::new();
+            packet.write_sections(&mut CoderSerializer::new(&mut data)).unwrap();
+            let encoded = encode(&packet);
+            let sizes = encode(&VarInt(data.len() as i32));
+            let start = encoded
+                .windows(sizes.len() + data.len())
+                .position(|window| window[..sizes.len()] == sizes[..] && window[sizes.len()..] == data[..]);
+            prop_assert!(start.is_some());
+
+            let sections = read_sections(&data, sent.len(), format, true);
+            for (states, &index) in sections.iter().zip(&sent) {
+                let expected = chunk.sections()[index].blocks().iter().collect::<Vec<_>>();
+                prop_assert_eq!(states, &expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use super::super::objs::{PackedArray, PackedArrayError, Packing};
+
+/// The shape of a paletted container: how many entries it holds and how many
+/// bits they may take depending on how many different values there are.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct PaletteConfig {
+    pub entries: usize,
+    /// Fewest bits indirect entries take.
+    pub min_bits: u8,
+    /// Most bits indirect entries take, after which values are stored
+    /// directly.
+    pub max_indirect_bits: u8,
+    /// Bits values themselves take, i.e. enough for the highest id in the
+    /// registry.
+    pub direct_bits: u8,
+}
+
+impl PaletteConfig {
+    /// The block states of a 16x16x16 section.
+    pub fn blocks(direct_bits: u8) -> Self {
+        Self {
+            entries: 4096,
+            min_bits: 4,
+            max_indirect_bits: 8,
+            direct_bits,
+        }
+    }
+
+    /// The biomes of a section, one for every 4x4x4 cube.
+    pub fn biomes(direct_bits: u8) -> Self {
+        Self {
+            entries: 64,
+            min_bits: 1,
+            max_indirect_bits: 3,
+            direct_bits,
+        }
+    }
+
+    /// Returns how many bits entries take with `len` different values, or
+    /// `None` if they'd be stored directly.
+    fn indirect_bits(self, len: usize) -> Option<u8> {
+        let needed = (usize::BITS - len.saturating_sub(1).leading_zeros()) as u8;
+        let bits = needed.max(self.min_bits);
+
+        Some(bits).filter(|&bits| bits <= self.max_indirect_bits)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Palette {
+    /// Every entry has the same value, so there's no data at all.
+    Single(u32),
+    /// Entries are indices into a list of values.
+    Indirect(Vec<u32>),
+    /// Entries are the values themselves.
+    Direct,
+}
+
+/// A fixed amount of values, like the block states of a section, stored
+/// with as few bits as the different values in it allow.
+///
+/// The data is always padded, as since 1.16; encoders for older versions
+/// repack it.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PalettedContainer {
+    config: PaletteConfig,
+    palette: Palette,
+    /// Absent when the palette is `Single`.
+    data: Option<PackedArray>,
+}
+
+impl PalettedContainer {
+    /// Creates a container where every entry is `value`.
+    pub fn new(config: PaletteConfig, value: u32) -> Self {
+        Self {
+            config,
+            palette: Palette::Single(value),
+            data: None,
+        }
+    }
+
+    pub fn config(&self) -> PaletteConfig {
+        self.config
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn data(&self) -> Option<&PackedArray> {
+        self.data.as_ref()
+    }
+
+    /// Returns how many bits each entry takes, which is 0 for a single value.
+    pub fn bits(&self) -> u8 {
+        self.data.as_ref().map_or(0, PackedArray::bits)
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        match (&self.palette, &self.data) {
+            (Palette::Single(value), _) => *value,
+            (Palette::Indirect(values), Some(data)) => values[data.get(index) as usize],
+            (Palette::Direct, Some(data)) => data.get(index) as u32,
+            _ => unreachable!("only single values have no data"),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.config.entries).map(move |index| self.get(index))
+    }
+
+    /// Sets every entry to `value`, dropping the data.
+    pub fn fill(&mut self, value: u32) {
+        self.palette = Palette::Single(value);
+        self.data = None;
+    }
+
+    fn check(&self, value: u32) -> Result<(), PackedArrayError> {
+        if u64::from(value) >> self.config.direct_bits != 0 {
+            return Err(PackedArrayError::ValueTooLarge {
+                value: value.into(),
+                bits: self.config.direct_bits,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Sets an entry, growing the palette if `value` isn't in it yet, and
+    /// returns the previous value.
+    pub fn set(&mut self, index: usize, value: u32) -> Result<u32, PackedArrayError> {
+        self.check(value)?;
+
+        let old = self.get(index);
+        if old == value {
+            return Ok(old);
+        }
+
+        let entry = match &self.palette {
+            Palette::Single(current) => {
+                let current = *current;
+                self.repack(vec![current, value])?;
+                1
+            }
+            Palette::Indirect(values) => match values.iter().position(|&v| v == value) {
+                Some(entry) => entry as u64,
+                None if values.len() < 1 << self.bits() => {
+                    let entry = values.len() as u64;
+                    if let Palette::Indirect(values) = &mut self.palette {
+                        values.push(value);
+                    }
+                    entry
+                }
+                None => {
+                    // The palette is full, but some of its values may have
+                    // been overwritten everywhere, so compacting it could
+                    // make room without growing the entries.
+                    self.data.as_mut().unwrap().set(index, 0)?;
+                    self.compact_with(Some(value))?;
+                    return self.set(index, value).map(|_| old);
+                }
+            },
+            Palette::Direct => value.into(),
+        };
+
+        self.data.as_mut().unwrap().set(index, entry)?;
+        Ok(old)
+    }
+
+    /// Stores the entries with `values` as the palette, which must contain
+    /// every value in use, choosing how many bits they take from its length.
+    fn repack(&mut self, values: Vec<u32>) -> Result<(), PackedArrayError> {
+        let entries = self.config.entries;
+
+        let data = match self.config.indirect_bits(values.len()) {
+            Some(bits) => {
+                let indices = self
+                    .iter()
+                    .map(|value| values.iter().position(|&v| v == value).unwrap_or(0) as u64);
+                let data = PackedArray::from_values(entries, bits, Packing::Padded, indices)?;
+
+                self.palette = Palette::Indirect(values);
+                data
+            }
+            None => {
+                let direct = self.iter().map(u64::from);
+                let data = PackedArray::from_values(
+                    entries,
+                    self.config.direct_bits,
+                    Packing::Padded,
+                    direct,
+                )?;
+
+                self.palette = Palette::Direct;
+                data
+            }
+        };
+
+        self.data = Some(data);
+        Ok(())
+    }
+
+    /// Drops values that are no longer used from the palette, shrinking the
+    /// entries or going back to a single value where possible.
+    pub fn compact(&mut self) -> Result<(), PackedArrayError> {
+        self.compact_with(None)
+    }
+
+    /// Compacts the palette, keeping room for `extra` as well.
+    fn compact_with(&mut self, extra: Option<u32>) -> Result<(), PackedArrayError> {
+        if let Palette::Single(_) = self.palette {
+            return Ok(());
+        }
+
+        let mut values = Vec::new();
+        for value in self.iter().chain(extra) {
+            if !values.contains(&value) {
+                values.push(value);
+            }
+
+            // Past this many values they're stored directly anyway.
+            if values.len() > 1 << self.config.max_indirect_bits {
+                break;
+            }
+        }
+
+        if let [value] = values[..] {
+            self.fill(value);
+            return Ok(());
+        }
+
+        self.repack(values)
+    }
+
+    /// Returns how many bytes the container takes on the heap.
+    pub fn heap_size(&self) -> usize {
+        let palette = match &self.palette {
+            Palette::Indirect(values) => values.capacity() * std::mem::size_of::<u32>(),
+            _ => 0,
+        };
+        let data = self
+            .data
+            .as_ref()
+            .map_or(0, |data| std::mem::size_of_val(data.as_longs()));
+
+        palette + data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_growth() {
+        let mut container = PalettedContainer::new(PaletteConfig::blocks(14), 0);
+        assert_eq!(container.bits(), 0);
+        assert_eq!(container.set(0, 0), Ok(0));
+        assert_eq!(container.palette(), &Palette::Single(0));
+
+        assert_eq!(container.set(1, 7), Ok(0));
+        assert_eq!(container.palette(), &Palette::Indirect(vec![0, 7]));
+        assert_eq!(container.bits(), 4);
+        assert_eq!((container.get(0), container.get(1)), (0, 7));
+
+        for value in 2..=16 {
+            container.set(value as usize, value * 10).unwrap();
+        }
+        assert_eq!(container.bits(), 5);
+
+        for value in 17..=256 {
+            container.set(value as usize, value * 10).unwrap();
+        }
+        assert_eq!(container.palette(), &Palette::Direct);
+        assert_eq!(container.bits(), 14);
+        assert_eq!(container.get(256), 2560);
+        assert_eq!(container.get(4095), 0);
+
+        assert!(container.set(0, 1 << 14).is_err());
+    }
+
+    #[test]
+    fn test_compaction() {
+        let mut container = PalettedContainer::new(PaletteConfig::biomes(6), 1);
+
+        // Values that were overwritten everywhere make room for new ones
+        // instead of growing the entries.
+        container.set(0, 2).unwrap();
+        container.set(0, 3).unwrap();
+        assert_eq!(container.palette(), &Palette::Indirect(vec![1, 3]));
+        assert_eq!(container.bits(), 1);
+
+        container.set(1, 4).unwrap();
+        assert_eq!(container.bits(), 2);
+        assert_eq!(
+            (container.get(0), container.get(1), container.get(2)),
+            (3, 4, 1)
+        );
+
+        container.set(0, 1).unwrap();
+        container.set(1, 1).unwrap();
+        container.compact().unwrap();
+        assert_eq!(container.palette(), &Palette::Single(1));
+        assert_eq!(container.heap_size(), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn test_model(writes in prop::collection::vec((0..64usize, 0..40u32), 0..200), compact: bool) {
+            let mut container = PalettedContainer::new(PaletteConfig::biomes(6), 0);
+            let mut model = vec![0; 64];
+
+            for (index, value) in writes {
+                prop_assert_eq!(container.set(index, value).unwrap(), model[index]);
+                model[index] = value;
+            }
+
+            if compact {
+                container.compact().unwrap();
+            }
+
+            prop_assert_eq!(container.iter().collect::<Vec<_>>(), model);
+        }
+    }
+}
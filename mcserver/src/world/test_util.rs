@@ -0,0 +1,21 @@
+//! Helpers shared by the world's tests.
+
+use std::path::PathBuf;
+
+use crate::registry::{BlockRegistry, Registries, Registry};
+
+/// Returns an empty directory that's unique to the test.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mcserver-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Returns the 1.13.2 block and biome registries.
+pub fn registries() -> (&'static BlockRegistry, &'static Registry) {
+    (
+        BlockRegistry::for_protocol(404).unwrap(),
+        Registries::for_protocol(404).unwrap().biomes(),
+    )
+}